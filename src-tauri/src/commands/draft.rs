@@ -0,0 +1,73 @@
+use crate::database::connection::Database;
+use crate::models::draft::Draft;
+use crate::models::outgoing::OutgoingMessage;
+use crate::services::blob_store::BlobStore;
+use crate::services::draft_service;
+use tauri::State;
+use std::sync::Mutex;
+
+/// 新建草稿，账户有草稿箱文件夹时同时上传到服务器
+///
+/// 上传失败不影响本地保存，原因记录在返回草稿的 `server_error` 中。
+#[tauri::command]
+pub async fn save_draft(
+    account_id: Option<i32>,
+    message: OutgoingMessage,
+    source_email_id: Option<String>,
+    db: State<'_, Mutex<Database>>,
+    blobs: State<'_, BlobStore>
+) -> Result<Draft, String> {
+    draft_service::create_draft(db.inner(), &blobs, account_id, source_email_id, message)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 保存草稿的新内容（自动保存）
+///
+/// `version` 为编辑所基于的版本，草稿已被更新的版本覆盖时返回错误。
+#[tauri::command]
+pub async fn update_draft(
+    draft_id: String,
+    version: i64,
+    account_id: Option<i32>,
+    message: OutgoingMessage,
+    db: State<'_, Mutex<Database>>,
+    blobs: State<'_, BlobStore>
+) -> Result<Draft, String> {
+    draft_service::update_draft(db.inner(), &blobs, &draft_id, version, account_id, &message)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 删除草稿及其服务器副本
+#[tauri::command]
+pub async fn discard_draft(
+    draft_id: String,
+    db: State<'_, Mutex<Database>>
+) -> Result<(), String> {
+    draft_service::delete_draft(db.inner(), &draft_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_drafts(
+    account_id: Option<i32>,
+    db: State<'_, Mutex<Database>>
+) -> Result<Vec<Draft>, String> {
+    let db = db.lock().unwrap();
+
+    db.get_drafts(account_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_draft(
+    draft_id: String,
+    db: State<'_, Mutex<Database>>
+) -> Result<Option<Draft>, String> {
+    let db = db.lock().unwrap();
+
+    db.get_draft(&draft_id)
+        .map_err(|e| e.to_string())
+}
@@ -1,6 +1,17 @@
+use crate::commands::provider::push_pending_operations;
 use crate::database::connection::Database;
+use crate::models::attachment::Attachment;
 use crate::models::email::{Email, EmailFilter};
-use tauri::State;
+use crate::models::pending_operation::{FLAG_FLAGGED, FLAG_SEEN};
+use crate::models::outgoing::{ComposeDraft, OutgoingAttachment};
+use crate::models::thread::EmailThread;
+use crate::services::blob_store::{sanitize_filename, BlobStore};
+use crate::services::desktop;
+use crate::services::provider_service::ProviderService;
+use crate::services::reply;
+use crate::services::threading;
+use tauri::{AppHandle, State};
+use std::path::Path;
 use std::sync::Mutex;
 
 #[tauri::command]
@@ -22,20 +33,11 @@ pub async fn create_email(
 ) -> Result<String, String> {
     let db = db.lock().map_err(|e| e.to_string())?;
     
-    let email = Email {
-        id: uuid::Uuid::new_v4().to_string(),
-        sender,
-        recipient,
-        subject,
-        body,
-        category,
-        is_read: false,
-        is_important: false,
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
-    };
+    let email = Email::new(sender, recipient, subject, body, category);
     
     db.insert_email(&email).map_err(|e| e.to_string())?;
+    threading::thread_new_messages(&db, None, std::slice::from_ref(&email.id))
+        .map_err(|e| e.to_string())?;
     Ok(email.id)
 }
 
@@ -46,6 +48,7 @@ pub async fn search_emails(
     category: Option<String>,
     unread_only: Option<bool>,
     important_only: Option<bool>,
+    attachments_only: Option<bool>,
 ) -> Result<Vec<Email>, String> {
     let db = db.lock().map_err(|e| e.to_string())?;
     
@@ -63,6 +66,9 @@ pub async fn search_emails(
     if let Some(true) = important_only {
         filter = filter.important_only();
     }
+    if let Some(true) = attachments_only {
+        filter = filter.with_attachments();
+    }
     
     db.search_emails(&filter).map_err(|e| e.to_string())
 }
@@ -78,15 +84,27 @@ pub async fn get_email(
 
 #[tauri::command]
 pub async fn mark_email_as_read(
+    app: AppHandle,
     db: State<'_, Mutex<Database>>,
     id: String,
 ) -> Result<(), String> {
-    let db = db.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(mut email) = db.get_email_by_id(&id).map_err(|e| e.to_string())? {
-        email.is_read = true;
-        email.updated_at = chrono::Utc::now();
-        db.update_email(&email).map_err(|e| e.to_string())?;
+    let account_id = {
+        let db = db.lock().map_err(|e| e.to_string())?;
+        
+        match db.get_email_by_id(&id).map_err(|e| e.to_string())? {
+            Some(mut email) => {
+                email.is_read = true;
+                email.updated_at = chrono::Utc::now();
+                db.update_email(&email).map_err(|e| e.to_string())?;
+                db.queue_flag_change(&email, FLAG_SEEN, true).map_err(|e| e.to_string())?;
+                email.account_id
+            }
+            None => None,
+        }
+    };
+
+    if let Some(account_id) = account_id {
+        spawn_flag_push(app, account_id);
     }
     
     Ok(())
@@ -94,20 +112,42 @@ pub async fn mark_email_as_read(
 
 #[tauri::command]
 pub async fn mark_email_as_important(
+    app: AppHandle,
     db: State<'_, Mutex<Database>>,
     id: String,
 ) -> Result<(), String> {
-    let db = db.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(mut email) = db.get_email_by_id(&id).map_err(|e| e.to_string())? {
-        email.is_important = !email.is_important;
-        email.updated_at = chrono::Utc::now();
-        db.update_email(&email).map_err(|e| e.to_string())?;
+    let account_id = {
+        let db = db.lock().map_err(|e| e.to_string())?;
+        
+        match db.get_email_by_id(&id).map_err(|e| e.to_string())? {
+            Some(mut email) => {
+                email.is_important = !email.is_important;
+                email.updated_at = chrono::Utc::now();
+                db.update_email(&email).map_err(|e| e.to_string())?;
+                db.queue_flag_change(&email, FLAG_FLAGGED, email.is_important)
+                    .map_err(|e| e.to_string())?;
+                email.account_id
+            }
+            None => None,
+        }
+    };
+
+    if let Some(account_id) = account_id {
+        spawn_flag_push(app, account_id);
     }
     
     Ok(())
 }
 
+/// 在后台把标记修改推送到服务器，不阻塞界面操作
+fn spawn_flag_push(app: AppHandle, account_id: i32) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = push_pending_operations(&app, account_id).await {
+            eprintln!("推送标记修改失败，将在下次同步时重试: {}", e);
+        }
+    });
+}
+
 #[tauri::command]
 pub async fn delete_email(
     db: State<'_, Mutex<Database>>,
@@ -117,6 +157,114 @@ pub async fn delete_email(
     db.delete_email(&id).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_email_attachments(
+    db: State<'_, Mutex<Database>>,
+    email_id: String,
+) -> Result<Vec<Attachment>, String> {
+    let db = db.lock().map_err(|e| e.to_string())?;
+    db.get_attachments(&email_id).map_err(|e| e.to_string())
+}
+
+/// 将附件保存到用户选择的路径
+#[tauri::command]
+pub async fn save_attachment(
+    db: State<'_, Mutex<Database>>,
+    blobs: State<'_, BlobStore>,
+    attachment_id: i64,
+    path: String,
+) -> Result<(), String> {
+    let attachment = find_attachment(&db, attachment_id)?;
+    blobs.export(&attachment.sha256, Path::new(&path)).map_err(|e| e.to_string())
+}
+
+/// 将附件复制到临时目录后用系统默认程序打开
+#[tauri::command]
+pub async fn open_attachment(
+    db: State<'_, Mutex<Database>>,
+    blobs: State<'_, BlobStore>,
+    attachment_id: i64,
+) -> Result<(), String> {
+    let attachment = find_attachment(&db, attachment_id)?;
+    let prefix = attachment.sha256.get(..16).ok_or("附件数据无效")?;
+
+    // 每个附件使用单独的目录，保留原始文件名以便系统按扩展名选择程序
+    let dir = std::env::temp_dir()
+        .join("xmail-attachments")
+        .join(prefix);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(sanitize_filename(&attachment.filename));
+
+    blobs.export(&attachment.sha256, &path).map_err(|e| e.to_string())?;
+    desktop::open_external(&path.to_string_lossy()).map_err(|e| e.to_string())
+}
+
+fn find_attachment(db: &Mutex<Database>, attachment_id: i64) -> Result<Attachment, String> {
+    let db = db.lock().map_err(|e| e.to_string())?;
+    db.get_attachment(attachment_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "未找到附件".to_string())
+}
+
+/// 生成回复草稿，`reply_all` 为 true 时回复全部（不包括自己所有账户的地址）
+#[tauri::command]
+pub async fn create_reply_draft(
+    db: State<'_, Mutex<Database>>,
+    email_id: String,
+    reply_all: bool,
+) -> Result<ComposeDraft, String> {
+    let db = db.lock().map_err(|e| e.to_string())?;
+    let original = find_email(&db, &email_id)?;
+
+    let own_addresses: Vec<String> = ProviderService::new(&db.conn)
+        .get_all_accounts()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|account| account.email_address)
+        .collect();
+
+    Ok(ComposeDraft {
+        account_id: original.account_id,
+        source_email_id: original.id.clone(),
+        message: reply::reply(&original, &own_addresses, reply_all),
+    })
+}
+
+/// 生成转发草稿，原邮件的附件从附件存储中带上
+#[tauri::command]
+pub async fn create_forward_draft(
+    db: State<'_, Mutex<Database>>,
+    email_id: String,
+) -> Result<ComposeDraft, String> {
+    let db = db.lock().map_err(|e| e.to_string())?;
+    let original = find_email(&db, &email_id)?;
+
+    let attachments = db
+        .get_attachments(&original.id)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|attachment| OutgoingAttachment {
+            path: String::new(),
+            sha256: Some(attachment.sha256),
+            filename: Some(attachment.filename),
+            mime_type: Some(attachment.mime_type),
+            content_id: attachment.content_id.filter(|_| attachment.is_inline),
+        })
+        .collect();
+
+    Ok(ComposeDraft {
+        account_id: original.account_id,
+        source_email_id: original.id.clone(),
+        message: reply::forward(&original, attachments),
+    })
+}
+
+fn find_email(db: &Database, email_id: &str) -> Result<Email, String> {
+    db.get_email_by_id(email_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "未找到邮件".to_string())
+}
+
 #[tauri::command]
 pub async fn get_categories(
     db: State<'_, Mutex<Database>>
@@ -142,4 +290,24 @@ pub async fn get_statistics(
     });
     
     Ok(json)
-}
\ No newline at end of file
+}
+
+/// 会话列表，`account_id` 为空时返回所有账户的会话
+#[tauri::command]
+pub async fn get_threads(
+    db: State<'_, Mutex<Database>>,
+    account_id: Option<i32>,
+) -> Result<Vec<EmailThread>, String> {
+    let db = db.lock().map_err(|e| e.to_string())?;
+    db.get_threads(account_id).map_err(|e| e.to_string())
+}
+
+/// 会话中的全部邮件，按时间顺序排列
+#[tauri::command]
+pub async fn get_thread_emails(
+    db: State<'_, Mutex<Database>>,
+    thread_id: String,
+) -> Result<Vec<Email>, String> {
+    let db = db.lock().map_err(|e| e.to_string())?;
+    db.get_thread_emails(&thread_id).map_err(|e| e.to_string())
+}
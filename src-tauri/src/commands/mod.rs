@@ -1,2 +1,5 @@
+pub mod draft;
 pub mod email;
-pub mod provider;
\ No newline at end of file
+pub mod outbox;
+pub mod provider;
+pub mod security;
\ No newline at end of file
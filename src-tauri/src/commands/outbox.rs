@@ -0,0 +1,165 @@
+use crate::database::connection::Database;
+use crate::models::draft::Draft;
+use crate::models::outbox::{OutboxMessage, OutboxStatus};
+use crate::models::outgoing::OutgoingMessage;
+use crate::services::blob_store::BlobStore;
+use crate::services::draft_service;
+use crate::services::outbox_service::{self, OutboxManager};
+use tauri::State;
+use std::sync::Mutex;
+
+/// 把邮件放入发件箱，由后台线程在撤回窗口结束后（或 `send_at` 指定的时间）发送
+///
+/// 从草稿发送时草稿在邮件发出后才删除，撤回后恢复为原草稿。
+#[tauri::command]
+pub async fn queue_email(
+    account_id: i32,
+    message: OutgoingMessage,
+    draft_id: Option<String>,
+    send_at: Option<String>,
+    db: State<'_, Mutex<Database>>,
+    blobs: State<'_, BlobStore>,
+    outbox: State<'_, OutboxManager>
+) -> Result<OutboxMessage, String> {
+    let send_at = send_at
+        .map(|send_at| {
+            chrono::DateTime::parse_from_rfc3339(&send_at)
+                .map(|time| time.with_timezone(&chrono::Utc))
+                .map_err(|e| format!("发送时间无效: {}", e))
+        })
+        .transpose()?;
+
+    let queued = {
+        let db = db.lock().unwrap();
+        let source_email_id = match &draft_id {
+            Some(draft_id) => db.get_draft(draft_id)
+                .map_err(|e| e.to_string())?
+                .and_then(|draft| draft.source_email_id),
+            None => None,
+        };
+        outbox_service::enqueue(&db, &blobs, account_id, message, source_email_id, draft_id, send_at)
+            .map_err(|e| e.to_string())?
+    };
+    outbox.wake();
+
+    Ok(queued)
+}
+
+/// 发件箱中等待发送和发送失败的邮件
+#[tauri::command]
+pub async fn get_outbox(
+    account_id: Option<i32>,
+    db: State<'_, Mutex<Database>>
+) -> Result<Vec<OutboxMessage>, String> {
+    let db = db.lock().unwrap();
+
+    db.get_outbox_messages(account_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 撤回还没有开始发送的邮件，放回草稿
+///
+/// 从草稿发送的邮件恢复到原草稿（ID 和 Message-ID 不变），原草稿已删除时新建草稿。
+#[tauri::command]
+pub async fn undo_send(
+    outbox_id: String,
+    db: State<'_, Mutex<Database>>,
+    blobs: State<'_, BlobStore>
+) -> Result<Draft, String> {
+    let recalled = {
+        let db = db.lock().unwrap();
+        db.recall_outbox_message(&outbox_id)
+            .map_err(|e| e.to_string())?
+            .ok_or("邮件正在发送或已经发出，无法撤回")?
+    };
+
+    let result = restore_draft(db.inner(), &blobs, &recalled).await;
+
+    result.map_err(|e| {
+        // 保存草稿失败时放回发件箱，避免邮件丢失
+        let failed = OutboxMessage {
+            status: OutboxStatus::Failed,
+            last_error: Some(e.to_string()),
+            ..recalled
+        };
+        if let Err(e) = db.lock().unwrap().insert_outbox_message(&failed) {
+            eprintln!("放回发件箱失败: {}", e);
+        }
+        e.to_string()
+    })
+}
+
+/// 把撤回的邮件放回原草稿，没有原草稿时新建
+async fn restore_draft(db: &Mutex<Database>, blobs: &BlobStore, recalled: &OutboxMessage) -> anyhow::Result<Draft> {
+    let draft = match &recalled.draft_id {
+        Some(draft_id) => db.lock().unwrap().get_draft(draft_id)?,
+        None => None,
+    };
+
+    match draft {
+        Some(draft) => {
+            draft_service::update_draft(
+                db,
+                blobs,
+                &draft.id,
+                draft.version,
+                Some(recalled.account_id),
+                &recalled.message,
+            )
+            .await
+        }
+        None => {
+            draft_service::create_draft(
+                db,
+                blobs,
+                Some(recalled.account_id),
+                recalled.source_email_id.clone(),
+                recalled.message.clone(),
+            )
+            .await
+        }
+    }
+}
+
+/// 重新发送失败的邮件
+#[tauri::command]
+pub async fn retry_outbox_email(
+    outbox_id: String,
+    db: State<'_, Mutex<Database>>,
+    outbox: State<'_, OutboxManager>
+) -> Result<(), String> {
+    let retried = {
+        let db = db.lock().unwrap();
+        db.retry_outbox_message(&outbox_id)
+            .map_err(|e| e.to_string())?
+    };
+    if !retried {
+        return Err("只能重试发送失败的邮件".to_string());
+    }
+
+    outbox.wake();
+    Ok(())
+}
+
+/// 撤回窗口（秒）
+#[tauri::command]
+pub async fn get_undo_send_delay(
+    db: State<'_, Mutex<Database>>
+) -> Result<u32, String> {
+    let db = db.lock().unwrap();
+
+    outbox_service::undo_send_delay(&db)
+        .map_err(|e| e.to_string())
+}
+
+/// 设置撤回窗口，0 表示入队后立即发送
+#[tauri::command]
+pub async fn set_undo_send_delay(
+    seconds: u32,
+    db: State<'_, Mutex<Database>>
+) -> Result<(), String> {
+    let db = db.lock().unwrap();
+
+    outbox_service::set_undo_send_delay(&db, seconds)
+        .map_err(|e| e.to_string())
+}
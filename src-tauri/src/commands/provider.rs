@@ -1,9 +1,18 @@
 use crate::database::connection::Database;
-use crate::models::email_provider::{EmailProvider, EmailAccount, EmailCategory};
+use crate::models::email_provider::{AuthMethod, EmailProvider, EmailAccount, EmailAccountInfo, EmailCategory};
+use crate::models::folder::MailFolder;
+use crate::models::outgoing::OutgoingMessage;
+use crate::services::blob_store::BlobStore;
+use crate::services::draft_service;
+use crate::services::idle_service::IdleManager;
+use crate::services::oauth_service::OAuthService;
+use crate::services::outbox_service;
 use crate::services::provider_service::ProviderService;
-use crate::services::sync_service::{EmailSyncService, SyncManager};
+use crate::services::crypto_service::CryptoService;
+use crate::services::imap_response::ImapClientId;
+use crate::services::sync_service::{self, ConnectionTestResult, DeliveryResult, EmailSyncService, SyncManager};
 use anyhow::Result;
-use tauri::State;
+use tauri::{AppHandle, Manager, State};
 use std::sync::Mutex;
 
 #[tauri::command]
@@ -24,7 +33,9 @@ pub async fn add_email_account(
     display_name: String,
     username: String,
     password: String,
-    db: State<'_, Mutex<Database>>
+    app: AppHandle,
+    db: State<'_, Mutex<Database>>,
+    idle: State<'_, IdleManager>
 ) -> Result<i64, String> {
     let db = db.lock().unwrap();
     let service = ProviderService::new(&db.conn);
@@ -36,24 +47,116 @@ pub async fn add_email_account(
         display_name,
         username,
         password,
+        auth_method: AuthMethod::Password,
         is_active: true,
         last_sync: None,
         created_at: chrono::Utc::now().to_rfc3339(),
     };
     
-    service.add_email_account(&account)
-        .map_err(|e| e.to_string())
+    let account_id = service.add_email_account(&account)
+        .map_err(|e| e.to_string())?;
+
+    // 开始监听新邮件
+    idle.start(app, account_id as i32);
+
+    Ok(account_id)
+}
+
+/// 通过浏览器完成 OAuth2 授权并添加账户（Gmail、Outlook）
+#[tauri::command]
+pub async fn authorize_oauth_account(
+    provider_id: i32,
+    email_address: String,
+    display_name: String,
+    app: AppHandle,
+    db: State<'_, Mutex<Database>>,
+    idle: State<'_, IdleManager>
+) -> Result<i64, String> {
+    let provider = {
+        let db = db.lock().unwrap();
+        let service = ProviderService::new(&db.conn);
+        let providers = service.get_all_providers().map_err(|e| e.to_string())?;
+        providers.into_iter()
+            .find(|p| p.id == provider_id)
+            .ok_or("未找到邮件服务商")?
+    };
+
+    // 等待用户在浏览器中完成授权，期间不持有数据库锁
+    let mut token = OAuthService::authorize(&provider, &email_address)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let db = db.lock().unwrap();
+    let service = ProviderService::new(&db.conn);
+
+    let account = EmailAccount {
+        id: 0,
+        provider_id,
+        email_address: email_address.clone(),
+        display_name,
+        username: email_address,
+        password: String::new(),
+        auth_method: AuthMethod::OAuth2,
+        is_active: true,
+        last_sync: None,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    let account_id = service.add_email_account(&account)
+        .map_err(|e| e.to_string())?;
+
+    token.account_id = account_id as i32;
+    db.save_oauth_token(&token)
+        .map_err(|e| e.to_string())?;
+
+    // 开始监听新邮件
+    idle.start(app, account_id as i32);
+
+    Ok(account_id)
 }
 
 #[tauri::command]
 pub async fn get_email_accounts(
     db: State<'_, Mutex<Database>>
-) -> Result<Vec<EmailAccount>, String> {
+) -> Result<Vec<EmailAccountInfo>, String> {
     let db = db.lock().unwrap();
     let service = ProviderService::new(&db.conn);
     
-    service.get_all_accounts()
-        .map_err(|e| e.to_string())
+    let accounts = service.get_all_accounts()
+        .map_err(|e| e.to_string())?;
+
+    Ok(accounts.iter().map(EmailAccountInfo::from).collect())
+}
+
+/// 修改账户的用户名和密码，并使用新凭据重新连接
+#[tauri::command]
+pub async fn update_account_credentials(
+    account_id: i32,
+    username: String,
+    password: String,
+    app: AppHandle,
+    db: State<'_, Mutex<Database>>,
+    idle: State<'_, IdleManager>
+) -> Result<(), String> {
+    let is_active = {
+        let db = db.lock().unwrap();
+        let service = ProviderService::new(&db.conn);
+
+        service.update_account_credentials(account_id, &username, &password)
+            .map_err(|e| e.to_string())?;
+
+        service.get_account(account_id)
+            .map_err(|e| e.to_string())?
+            .is_some_and(|account| account.is_active)
+    };
+
+    // 重启新邮件监听，使用新凭据登录
+    idle.stop(account_id);
+    if is_active {
+        idle.start(app, account_id);
+    }
+
+    Ok(())
 }
 
 #[tauri::command]
@@ -62,15 +165,20 @@ pub async fn test_email_connection(
     username: String,
     password: String,
     db: State<'_, Mutex<Database>>
-) -> Result<bool, String> {
-    let db = db.lock().unwrap();
-    let service = ProviderService::new(&db.conn);
-    
+) -> Result<ConnectionTestResult, String> {
     // 获取服务商信息
-    let providers = service.get_all_providers().map_err(|e| e.to_string())?;
-    let provider = providers.into_iter()
-        .find(|p| p.id == provider_id)
-        .ok_or("未找到邮件服务商")?;
+    let (provider, client_id) = {
+        let db = db.lock().unwrap();
+        let service = ProviderService::new(&db.conn);
+        let providers = service.get_all_providers().map_err(|e| e.to_string())?;
+        let provider = providers.into_iter()
+            .find(|p| p.id == provider_id)
+            .ok_or("未找到邮件服务商")?;
+        (provider, sync_service::imap_client_id(&db).map_err(|e| e.to_string())?)
+    };
+    
+    // 连接时会解密账户密码，这里与已保存的账户保持一致
+    let password = CryptoService::encrypt_password(&password).map_err(|e| e.to_string())?;
     
     // 创建临时账户用于测试
     let test_account = EmailAccount {
@@ -80,66 +188,177 @@ pub async fn test_email_connection(
         display_name: "Test".to_string(),
         username,
         password,
+        auth_method: AuthMethod::Password,
         is_active: true,
         last_sync: None,
         created_at: chrono::Utc::now().to_rfc3339(),
     };
     
-    let sync_service = EmailSyncService::new(provider, test_account);
+    let sync_service = EmailSyncService::new(provider, test_account).with_client_id(client_id);
     sync_service.test_connection().await
         .map_err(|e| e.to_string())
 }
 
+/// 登录后通过 IMAP ID 命令上报的客户端信息
+#[tauri::command]
+pub async fn get_imap_client_id(
+    db: State<'_, Mutex<Database>>
+) -> Result<ImapClientId, String> {
+    let db = db.lock().unwrap();
+
+    sync_service::imap_client_id(&db)
+        .map_err(|e| e.to_string())
+}
+
+/// 设置 IMAP ID 上报的名称、版本和厂商，下次连接时生效
+#[tauri::command]
+pub async fn set_imap_client_id(
+    client_id: ImapClientId,
+    db: State<'_, Mutex<Database>>
+) -> Result<(), String> {
+    let db = db.lock().unwrap();
+
+    sync_service::set_imap_client_id(&db, &client_id)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn sync_account_emails(
     account_id: i32,
-    db: State<'_, Mutex<Database>>
+    db: State<'_, Mutex<Database>>,
+    blobs: State<'_, BlobStore>
 ) -> Result<Vec<crate::models::email::Email>, String> {
-    let db = db.lock().unwrap();
-    let service = ProviderService::new(&db.conn);
-    
-    // 获取账户信息
-    let accounts = service.get_all_accounts().map_err(|e| e.to_string())?;
-    let account = accounts.into_iter()
-        .find(|a| a.id == account_id)
-        .ok_or("未找到邮件账户")?;
-    
-    // 获取服务商信息
-    let providers = service.get_all_providers().map_err(|e| e.to_string())?;
-    let provider = providers.into_iter()
-        .find(|p| p.id == account.provider_id)
-        .ok_or("未找到邮件服务商")?;
+    // 读取账户和服务商信息（OAuth2 账户同时刷新访问令牌），同步期间不持有数据库锁
+    let sync_service = EmailSyncService::for_account(db.inner(), account_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // 获取各文件夹的同步进度和离线期间的标记修改
+    let (states, pending) = {
+        let db = db.lock().unwrap();
+        let states = db.get_sync_states(account_id).map_err(|e| e.to_string())?;
+        let pending = db.get_pending_operations(account_id).map_err(|e| e.to_string())?;
+        (states, pending)
+    };
     
     // 同步邮件
     let sync_manager = SyncManager::new();
-    let emails = sync_manager.sync_account_emails(provider, account)
+    let sync_result = sync_manager.sync_account_emails(sync_service, &states, &pending)
         .await
         .map_err(|e| e.to_string())?;
     
+    let db = db.lock().unwrap();
+
+    // 保存到本地数据库，只返回新增的邮件
+    let saved = SyncManager::save_sync_result(&db, &blobs, account_id, sync_result)
+        .map_err(|e| e.to_string())?;
+
     // 更新同步时间
-    service.update_account_sync_time(account_id)
+    ProviderService::new(&db.conn)
+        .update_account_sync_time(account_id)
         .map_err(|e| e.to_string())?;
     
-    Ok(emails)
+    Ok(saved)
 }
 
+/// 立即通过账户的 SMTP 服务器发送邮件，成功后在本地保存一份到发件箱，
+/// 服务商不会自动保存时同时追加到服务器的已发送文件夹
 #[tauri::command]
-pub async fn toggle_account_status(
+pub async fn send_email(
+    account_id: i32,
+    message: OutgoingMessage,
+    draft_id: Option<String>,
+    db: State<'_, Mutex<Database>>,
+    blobs: State<'_, BlobStore>
+) -> Result<DeliveryResult, String> {
+    let result = outbox_service::deliver(db.inner(), &blobs, account_id, &message, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // 邮件已发出，删除对应的草稿及其服务器副本
+    if let Some(draft_id) = draft_id {
+        if let Err(e) = draft_service::delete_draft(db.inner(), &draft_id).await {
+            eprintln!("删除草稿失败: {}", e);
+        }
+    }
+
+    Ok(result)
+}
+
+/// 获取账户在服务器上的文件夹列表（同步时更新）
+#[tauri::command]
+pub async fn get_email_folders(
     account_id: i32,
     db: State<'_, Mutex<Database>>
+) -> Result<Vec<MailFolder>, String> {
+    let db = db.lock().unwrap();
+    
+    db.get_folders(account_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 重放账户离线队列中的标记修改，失败时操作保留在队列中等待下次同步
+pub(crate) async fn push_pending_operations(app: &AppHandle, account_id: i32) -> Result<()> {
+    let db = app.state::<Mutex<Database>>();
+    let operations = {
+        let db = db.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        db.get_pending_operations(account_id)?
+    };
+    if operations.is_empty() {
+        return Ok(());
+    }
+
+    let result = match EmailSyncService::for_account(&db, account_id).await {
+        Ok(sync_service) => SyncManager::new().push_flag_changes(sync_service, &operations).await,
+        Err(e) => Err(e),
+    };
+
+    let db = db.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+    match result {
+        Ok(done) => db.complete_pending_operations(&done),
+        Err(e) => {
+            db.record_pending_failure(account_id, &e.to_string())?;
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn toggle_account_status(
+    account_id: i32,
+    app: AppHandle,
+    db: State<'_, Mutex<Database>>,
+    idle: State<'_, IdleManager>
 ) -> Result<(), String> {
     let db = db.lock().unwrap();
     let service = ProviderService::new(&db.conn);
     
     service.toggle_account_status(account_id)
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+
+    // 根据新的状态启动或停止新邮件监听
+    let is_active = service.get_active_accounts()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .any(|account| account.id == account_id);
+    if is_active {
+        idle.start(app, account_id);
+    } else {
+        idle.stop(account_id);
+    }
+
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn delete_email_account(
     account_id: i32,
-    db: State<'_, Mutex<Database>>
+    db: State<'_, Mutex<Database>>,
+    idle: State<'_, IdleManager>
 ) -> Result<(), String> {
+    idle.stop(account_id);
+
     let db = db.lock().unwrap();
     let service = ProviderService::new(&db.conn);
     
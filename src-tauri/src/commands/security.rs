@@ -0,0 +1,71 @@
+use crate::database::connection::Database;
+use crate::models::encryption::EncryptionStatus;
+use crate::services::app_paths::AppPaths;
+use crate::services::crypto_service::CryptoService;
+use crate::services::idle_service::IdleManager;
+use crate::services::outbox_service::OutboxManager;
+use tauri::{AppHandle, State};
+use std::sync::Mutex;
+
+#[tauri::command]
+pub async fn get_encryption_status(
+    db: State<'_, Mutex<Database>>
+) -> Result<EncryptionStatus, String> {
+    let db = db.lock().unwrap();
+
+    CryptoService::encryption_status(&db)
+        .map_err(|e| e.to_string())
+}
+
+/// 使用主密码解锁账户凭据，解锁后开始监听新邮件
+#[tauri::command]
+pub async fn unlock_master_password(
+    master_password: String,
+    app: AppHandle,
+    db: State<'_, Mutex<Database>>,
+    idle: State<'_, IdleManager>,
+    outbox: State<'_, OutboxManager>
+) -> Result<(), String> {
+    {
+        let db = db.lock().unwrap();
+        CryptoService::unlock(&db, &master_password)
+            .map_err(|e| e.to_string())?;
+    }
+
+    // 唤醒发件箱，发送解锁前到期的邮件
+    outbox.wake();
+
+    idle.start_active_accounts(&app)
+        .map_err(|e| e.to_string())
+}
+
+/// 设置、修改或移除主密码（`new_password` 为空时改回使用密钥文件）
+#[tauri::command]
+pub async fn change_master_password(
+    current_password: Option<String>,
+    new_password: Option<String>,
+    paths: State<'_, AppPaths>,
+    db: State<'_, Mutex<Database>>
+) -> Result<(), String> {
+    let db = db.lock().unwrap();
+
+    CryptoService::change_master_password(
+        &db,
+        paths.data_dir(),
+        current_password.as_deref(),
+        new_password.as_deref().filter(|password| !password.is_empty()),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// 密钥文件丢失或不匹配时重置账户凭据，之后需要重新输入各账户的密码
+#[tauri::command]
+pub async fn reset_credential_store(
+    paths: State<'_, AppPaths>,
+    db: State<'_, Mutex<Database>>
+) -> Result<(), String> {
+    let db = db.lock().unwrap();
+
+    CryptoService::reset_credentials(&db, paths.data_dir())
+        .map_err(|e| e.to_string())
+}
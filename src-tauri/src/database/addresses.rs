@@ -0,0 +1,154 @@
+use anyhow::Result;
+use rusqlite::{params, params_from_iter, Connection};
+use std::collections::HashMap;
+use crate::database::connection::Database;
+use crate::models::address::{AddressRole, EmailAddress};
+use crate::models::email::Email;
+
+impl Database {
+    /// 保存邮件的结构化地址，替换已有记录
+    ///
+    /// 没有结构化地址的邮件（例如本地创建的邮件）从 `sender` 和 `recipient` 解析。
+    pub fn save_addresses(&self, email: &Email) -> Result<()> {
+        save_addresses(&self.conn, email)
+    }
+
+    /// 读取邮件的结构化地址
+    pub fn load_addresses(&self, email: &mut Email) -> Result<()> {
+        self.load_addresses_batch(std::slice::from_mut(email))
+    }
+
+    /// 一次查询读取多封邮件的结构化地址，避免列表中每封邮件单独查询
+    pub fn load_addresses_batch(&self, emails: &mut [Email]) -> Result<()> {
+        let mut index = HashMap::new();
+        for (i, email) in emails.iter_mut().enumerate() {
+            email.from = None;
+            email.to.clear();
+            email.cc.clear();
+            email.bcc.clear();
+            email.reply_to.clear();
+            index.insert(email.id.clone(), i);
+        }
+
+        // 分批查询，避免超过 SQLite 的参数个数限制
+        let ids: Vec<&String> = index.keys().collect();
+        for chunk in ids.chunks(MAX_BATCH_IDS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT email_id, role, name, address FROM email_addresses
+                 WHERE email_id IN ({}) ORDER BY email_id, role, position",
+                placeholders
+            ))?;
+            let mut rows = stmt.query(params_from_iter(chunk))?;
+            while let Some(row) = rows.next()? {
+                let email_id: String = row.get("email_id")?;
+                let role: String = row.get("role")?;
+                let address = EmailAddress::new(row.get("name")?, row.get("address")?);
+
+                let email = &mut emails[index[&email_id]];
+                match AddressRole::parse(&role) {
+                    Some(AddressRole::From) => email.from = Some(address),
+                    Some(AddressRole::To) => email.to.push(address),
+                    Some(AddressRole::Cc) => email.cc.push(address),
+                    Some(AddressRole::Bcc) => email.bcc.push(address),
+                    Some(AddressRole::ReplyTo) => email.reply_to.push(address),
+                    None => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 每次 `IN (...)` 查询最多包含的邮件数
+const MAX_BATCH_IDS: usize = 500;
+
+fn save_addresses(conn: &Connection, email: &Email) -> Result<()> {
+    conn.execute("DELETE FROM email_addresses WHERE email_id = ?1", [&email.id])?;
+
+    let from = match &email.from {
+        Some(from) => vec![from.clone()],
+        None => EmailAddress::parse_list(&email.sender).into_iter().take(1).collect(),
+    };
+    let to = if email.to.is_empty() && email.cc.is_empty() {
+        EmailAddress::parse_list(&email.recipient)
+    } else {
+        email.to.clone()
+    };
+
+    let roles = [
+        (AddressRole::From, from.as_slice()),
+        (AddressRole::To, to.as_slice()),
+        (AddressRole::Cc, email.cc.as_slice()),
+        (AddressRole::Bcc, email.bcc.as_slice()),
+        (AddressRole::ReplyTo, email.reply_to.as_slice()),
+    ];
+
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO email_addresses (email_id, role, position, name, address)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for (role, addresses) in roles {
+        for (position, address) in addresses.iter().enumerate() {
+            stmt.execute(params![
+                email.id,
+                role.as_str(),
+                position as i64,
+                address.name,
+                address.address
+            ])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::email::EmailFilter;
+
+    #[test]
+    fn test_recipient_filter_matches_any_role() {
+        let db = Database::new(":memory:").unwrap();
+
+        let mut email = Email::new(String::new(), String::new(), "周报".into(), String::new(), "收件箱".into());
+        email.set_addresses(
+            EmailAddress::parse_list("张三 <zhangsan@example.com>").pop(),
+            EmailAddress::parse_list("lisi@example.com, wangwu@example.com"),
+            EmailAddress::parse_list("\"Zhao, Liu\" <zhaoliu@example.com>"),
+            Vec::new(),
+            Vec::new(),
+        );
+        db.insert_email(&email).unwrap();
+        assert_eq!(email.sender, "张三 <zhangsan@example.com>");
+
+        let search = |recipient: &str| {
+            let filter = EmailFilter { recipient: Some(recipient.into()), ..EmailFilter::new() };
+            db.search_emails(&filter).unwrap()
+        };
+        assert_eq!(search("wangwu").len(), 1);
+        assert_eq!(search("Zhao, Liu").len(), 1);
+        assert!(search("zhangsan").is_empty());
+
+        let loaded = db.get_email_by_id(&email.id).unwrap().unwrap();
+        assert_eq!(loaded.to.len(), 2);
+        assert_eq!(loaded.cc[0].address, "zhaoliu@example.com");
+        assert_eq!(loaded.from.unwrap().name.as_deref(), Some("张三"));
+    }
+
+    #[test]
+    fn test_batch_load_keeps_addresses_per_email() {
+        let db = Database::new(":memory:").unwrap();
+        let first = Email::new("a@example.com".into(), "b@example.com".into(), "一".into(), String::new(), "收件箱".into());
+        let second = Email::new("c@example.com".into(), "d@example.com, e@example.com".into(), "二".into(), String::new(), "收件箱".into());
+        db.insert_email(&first).unwrap();
+        db.insert_email(&second).unwrap();
+
+        let mut emails = db.get_all_emails().unwrap();
+        emails.sort_by(|a, b| a.subject.cmp(&b.subject));
+        assert_eq!(emails[0].from.as_ref().unwrap().address, "a@example.com");
+        assert_eq!(emails[0].to.len(), 1);
+        assert_eq!(emails[1].from.as_ref().unwrap().address, "c@example.com");
+        assert_eq!(emails[1].to.len(), 2);
+    }
+}
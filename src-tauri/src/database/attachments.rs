@@ -0,0 +1,116 @@
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension};
+use std::collections::HashSet;
+use crate::database::connection::Database;
+use crate::models::attachment::Attachment;
+use crate::models::outgoing::OutgoingMessage;
+
+const ATTACHMENT_COLUMNS: &str =
+    "id, email_id, filename, mime_type, size, content_id, is_inline, sha256, created_at";
+
+impl Database {
+    fn row_to_attachment(row: &rusqlite::Row) -> rusqlite::Result<Attachment> {
+        Ok(Attachment {
+            id: row.get("id")?,
+            email_id: row.get("email_id")?,
+            filename: row.get("filename")?,
+            mime_type: row.get("mime_type")?,
+            size: row.get("size")?,
+            content_id: row.get("content_id")?,
+            is_inline: row.get("is_inline")?,
+            sha256: row.get("sha256")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+
+    /// 获取邮件的附件，按邮件中出现的顺序排列
+    pub fn get_attachments(&self, email_id: &str) -> Result<Vec<Attachment>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM attachments WHERE email_id = ?1 ORDER BY id",
+            ATTACHMENT_COLUMNS
+        ))?;
+
+        let attachments = stmt
+            .query_map([email_id], Self::row_to_attachment)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(attachments)
+    }
+
+    pub fn get_attachment(&self, attachment_id: i64) -> Result<Option<Attachment>> {
+        let attachment = self.conn.query_row(
+            &format!("SELECT {} FROM attachments WHERE id = ?1", ATTACHMENT_COLUMNS),
+            [attachment_id],
+            Self::row_to_attachment,
+        ).optional()?;
+
+        Ok(attachment)
+    }
+
+    pub fn insert_attachment(&self, attachment: &Attachment) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO attachments (email_id, filename, mime_type, size, content_id, is_inline, sha256, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                attachment.email_id,
+                attachment.filename,
+                attachment.mime_type,
+                attachment.size,
+                attachment.content_id,
+                attachment.is_inline,
+                attachment.sha256,
+                attachment.created_at
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// 邮件附件、草稿和发件箱中引用的全部附件内容
+    ///
+    /// 草稿或发件箱中的邮件无法解析时返回错误，避免误删其附件。
+    pub fn referenced_blobs(&self) -> Result<HashSet<String>> {
+        let mut referenced = HashSet::new();
+
+        let mut stmt = self.conn.prepare("SELECT DISTINCT sha256 FROM attachments")?;
+        for sha256 in stmt.query_map([], |row| row.get::<_, String>(0))? {
+            referenced.insert(sha256?);
+        }
+
+        for table in ["drafts", "outbox"] {
+            let mut stmt = self.conn.prepare(&format!("SELECT message FROM {}", table))?;
+            for message in stmt.query_map([], |row| row.get::<_, String>(0))? {
+                let message: OutgoingMessage = serde_json::from_str(&message?)?;
+                referenced.extend(message.attachments.into_iter().filter_map(|a| a.sha256));
+            }
+        }
+        Ok(referenced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::email::Email;
+
+    #[test]
+    fn test_deleting_email_removes_attachments() {
+        let db = Database::new(":memory:").unwrap();
+        let email = Email::new("a@example.com".into(), "b@example.com".into(), "附件".into(), String::new(), "收件箱".into());
+        db.insert_email(&email).unwrap();
+        db.insert_attachment(&Attachment {
+            id: 0,
+            email_id: email.id.clone(),
+            filename: "report.pdf".into(),
+            mime_type: "application/pdf".into(),
+            size: 4,
+            content_id: None,
+            is_inline: false,
+            sha256: "ab".repeat(32),
+            created_at: "2024-01-01T00:00:00Z".into(),
+        }).unwrap();
+        assert!(db.referenced_blobs().unwrap().contains(&"ab".repeat(32)));
+
+        db.delete_email(&email.id).unwrap();
+        assert!(db.get_attachments(&email.id).unwrap().is_empty());
+        assert!(db.referenced_blobs().unwrap().is_empty());
+    }
+}
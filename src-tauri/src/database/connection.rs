@@ -1,106 +1,34 @@
 use anyhow::Result;
 use rusqlite::{Connection, params};
+use std::path::Path;
+use crate::models::address::AddressRole;
 use crate::models::email::{Email, EmailFilter};
 
+/// 查询邮件时使用的列，与 `row_to_email` 保持一致
+pub(crate) const EMAIL_COLUMNS: &str =
+    "id, sender, recipient, subject, body, html_body, category, is_read, is_important, \
+     account_id, folder, uid, message_id, in_reply_to, message_references, thread_id, gmail_thread_id, \
+     has_attachment, sent_at, received_at, created_at, updated_at";
+
+/// 邮件列表的排序依据：邮件日期，本地创建且没有日期的邮件使用创建时间
+const EMAIL_ORDER: &str = "COALESCE(sent_at, created_at)";
+
 pub struct Database {
     pub conn: Connection,
 }
 
 impl Database {
-    pub fn new(db_path: &str) -> Result<Self> {
+    pub fn new(db_path: impl AsRef<Path>) -> Result<Self> {
         let conn = Connection::open(db_path)?;
+        // SQLite 默认不检查外键，账户和邮件删除时依赖 ON DELETE CASCADE 清理令牌、文件夹、附件等记录
+        conn.pragma_update(None, "foreign_keys", true)?;
         let db = Self { conn };
         db.init_tables()?;
         Ok(db)
     }
 
     fn init_tables(&self) -> Result<()> {
-        // 邮件表
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS emails (
-                id TEXT PRIMARY KEY,
-                sender TEXT NOT NULL,
-                recipient TEXT NOT NULL,
-                subject TEXT NOT NULL,
-                body TEXT NOT NULL,
-                category TEXT NOT NULL,
-                is_read BOOLEAN NOT NULL DEFAULT 0,
-                is_important BOOLEAN NOT NULL DEFAULT 0,
-                account_id INTEGER,
-                message_id TEXT,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                FOREIGN KEY (account_id) REFERENCES email_accounts (id)
-            )",
-            [],
-        )?;
-
-        // 邮件服务商表
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS email_providers (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL,
-                provider_type TEXT NOT NULL UNIQUE,
-                imap_server TEXT NOT NULL,
-                imap_port INTEGER NOT NULL,
-                smtp_server TEXT NOT NULL,
-                smtp_port INTEGER NOT NULL,
-                use_ssl BOOLEAN NOT NULL DEFAULT 1,
-                use_tls BOOLEAN NOT NULL DEFAULT 1
-            )",
-            [],
-        )?;
-
-        // 邮件账户表
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS email_accounts (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                provider_id INTEGER NOT NULL,
-                email_address TEXT NOT NULL UNIQUE,
-                display_name TEXT NOT NULL,
-                username TEXT NOT NULL,
-                password TEXT NOT NULL,
-                is_active BOOLEAN NOT NULL DEFAULT 1,
-                last_sync TEXT,
-                created_at TEXT NOT NULL,
-                FOREIGN KEY (provider_id) REFERENCES email_providers (id)
-            )",
-            [],
-        )?;
-
-        // 邮件分类表
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS email_categories (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL UNIQUE,
-                color TEXT NOT NULL DEFAULT '#007bff',
-                description TEXT,
-                is_system BOOLEAN NOT NULL DEFAULT 0,
-                created_at TEXT NOT NULL
-            )",
-            [],
-        )?;
-
-        // 创建索引以提高查询性能
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_emails_sender ON emails(sender)",
-            [],
-        )?;
-        
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_emails_recipient ON emails(recipient)",
-            [],
-        )?;
-        
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_emails_category ON emails(category)",
-            [],
-        )?;
-
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_emails_account ON emails(account_id)",
-            [],
-        )?;
+        self.migrate()?;
 
         // 初始化默认数据
         self.init_default_data()?;
@@ -115,9 +43,14 @@ impl Database {
         let providers = EmailProvider::get_predefined_providers();
         for provider in providers {
             self.conn.execute(
-                "INSERT OR IGNORE INTO email_providers 
-                 (id, name, provider_type, imap_server, imap_port, smtp_server, smtp_port, use_ssl, use_tls)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                "INSERT INTO email_providers 
+                 (id, name, provider_type, imap_server, imap_port, smtp_server, smtp_port, imap_security, smtp_security, requires_imap_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                 ON CONFLICT(id) DO UPDATE SET
+                    imap_security = excluded.imap_security,
+                    smtp_security = excluded.smtp_security,
+                    requires_imap_id = excluded.requires_imap_id
+                 ON CONFLICT DO NOTHING",
                 params![
                     provider.id,
                     provider.name,
@@ -126,8 +59,9 @@ impl Database {
                     provider.imap_port,
                     provider.smtp_server,
                     provider.smtp_port,
-                    provider.use_ssl,
-                    provider.use_tls
+                    provider.imap_security.as_str(),
+                    provider.smtp_security.as_str(),
+                    provider.requires_imap_id
                 ],
             )?;
         }
@@ -138,6 +72,8 @@ impl Database {
             ("发件箱", "#28a745", "发送的邮件", true),
             ("草稿箱", "#ffc107", "草稿邮件", true),
             ("垃圾箱", "#dc3545", "已删除的邮件", true),
+            ("垃圾邮件", "#6c757d", "被识别为垃圾的邮件", true),
+            ("归档", "#17a2b8", "已归档的邮件", true),
             ("工作", "#6f42c1", "工作相关邮件", false),
             ("个人", "#fd7e14", "个人邮件", false),
             ("重要", "#e83e8c", "重要邮件", false),
@@ -160,55 +96,152 @@ impl Database {
         Ok(())
     }
 
-    fn row_to_email(row: &rusqlite::Row) -> rusqlite::Result<Email> {
+    pub(crate) fn row_to_email(row: &rusqlite::Row) -> rusqlite::Result<Email> {
         Ok(Email {
-            id: row.get(0)?,
-            sender: row.get(1)?,
-            recipient: row.get(2)?,
-            subject: row.get(3)?,
-            body: row.get(4)?,
-            category: row.get(5)?,
-            is_read: row.get(6)?,
-            is_important: row.get(7)?,
-            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(8)?)
+            id: row.get("id")?,
+            sender: row.get("sender")?,
+            recipient: row.get("recipient")?,
+            subject: row.get("subject")?,
+            body: row.get("body")?,
+            html_body: row.get("html_body")?,
+            category: row.get("category")?,
+            is_read: row.get("is_read")?,
+            is_important: row.get("is_important")?,
+            account_id: row.get("account_id")?,
+            folder: row.get("folder")?,
+            uid: row.get("uid")?,
+            message_id: row.get("message_id")?,
+            in_reply_to: row.get("in_reply_to")?,
+            references: row
+                .get::<_, Option<String>>("message_references")?
+                .map(|r| r.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+            thread_id: row.get("thread_id")?,
+            gmail_thread_id: row.get("gmail_thread_id")?,
+            has_attachment: row.get("has_attachment")?,
+            // 地址由 `load_addresses` 从 email_addresses 表读取
+            from: None,
+            to: Vec::new(),
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            reply_to: Vec::new(),
+            sent_at: Self::parse_optional_time(row.get("sent_at")?),
+            received_at: Self::parse_optional_time(row.get("received_at")?),
+            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>("created_at")?)
                 .unwrap()
                 .with_timezone(&chrono::Utc),
-            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(9)?)
+            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>("updated_at")?)
                 .unwrap()
                 .with_timezone(&chrono::Utc),
         })
     }
 
+    fn parse_optional_time(value: Option<String>) -> Option<chrono::DateTime<chrono::Utc>> {
+        value
+            .and_then(|value| chrono::DateTime::parse_from_rfc3339(&value).ok())
+            .map(|time| time.with_timezone(&chrono::Utc))
+    }
+
     pub fn insert_email(&self, email: &Email) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO emails (id, sender, recipient, subject, body, category, is_read, is_important, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            "INSERT INTO emails (id, sender, recipient, subject, body, html_body, category, is_read, is_important,
+                                 account_id, folder, uid, message_id, in_reply_to, message_references, thread_id,
+                                 gmail_thread_id, has_attachment, sent_at, received_at, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)",
             params![
                 email.id,
                 email.sender,
                 email.recipient,
                 email.subject,
                 email.body,
+                email.html_body,
                 email.category,
                 email.is_read,
                 email.is_important,
+                email.account_id,
+                email.folder,
+                email.uid,
+                email.message_id,
+                email.in_reply_to,
+                Some(email.references.join(" ")).filter(|r| !r.is_empty()),
+                email.thread_id,
+                email.gmail_thread_id,
+                email.has_attachment,
+                email.sent_at.map(|time| time.to_rfc3339()),
+                email.received_at.map(|time| time.to_rfc3339()),
                 email.created_at.to_rfc3339(),
                 email.updated_at.to_rfc3339()
             ],
         )?;
+        self.save_addresses(email)?;
+        self.save_references(email)?;
         Ok(())
     }
 
+    /// 查找已同步的邮件：同一账户、同一文件夹下 UID 或 Message-ID 相同即视为同一封
+    pub fn find_synced_email_id(
+        &self,
+        account_id: i32,
+        folder: &str,
+        uid: Option<u32>,
+        message_id: Option<&str>,
+    ) -> Result<Option<String>> {
+        let id = self.conn.query_row(
+            "SELECT id FROM emails
+             WHERE account_id = ?1 AND folder = ?2
+               AND ((?3 IS NOT NULL AND uid = ?3) OR (?4 IS NOT NULL AND message_id = ?4))
+             LIMIT 1",
+            params![account_id, folder, uid, message_id],
+            |row| row.get(0),
+        );
+
+        match id {
+            Ok(id) => Ok(Some(id)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 保存同步得到的邮件，已存在的邮件会被跳过，返回实际新增的邮件
+    pub fn save_synced_emails(&self, emails: Vec<Email>) -> Result<Vec<Email>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut saved = Vec::new();
+
+        for email in emails {
+            let (account_id, folder) = match (email.account_id, email.folder.as_deref()) {
+                (Some(account_id), Some(folder)) => (account_id, folder),
+                _ => return Err(anyhow::anyhow!("同步的邮件缺少账户或文件夹信息")),
+            };
+
+            let existing = self.find_synced_email_id(
+                account_id,
+                folder,
+                email.uid,
+                email.message_id.as_deref(),
+            )?;
+
+            if existing.is_none() {
+                self.insert_email(&email)?;
+                saved.push(email);
+            }
+        }
+
+        tx.commit()?;
+        Ok(saved)
+    }
+
     pub fn get_email_by_id(&self, id: &str) -> Result<Option<Email>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, sender, recipient, subject, body, category, is_read, is_important, created_at, updated_at
-             FROM emails WHERE id = ?1"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM emails WHERE id = ?1",
+            EMAIL_COLUMNS
+        ))?;
 
-        let email_iter = stmt.query_map([id], Self::row_to_email)?;
+        let mut email_iter = stmt.query_map([id], Self::row_to_email)?;
 
-        for email in email_iter {
-            return Ok(Some(email?));
+        if let Some(email) = email_iter.next() {
+            let mut email = email?;
+            self.load_addresses(&mut email)?;
+            return Ok(Some(email));
         }
         Ok(None)
     }
@@ -216,7 +249,7 @@ impl Database {
     pub fn update_email(&self, email: &Email) -> Result<()> {
         self.conn.execute(
             "UPDATE emails SET sender = ?2, recipient = ?3, subject = ?4, body = ?5, 
-             category = ?6, is_read = ?7, is_important = ?8, updated_at = ?9 WHERE id = ?1",
+             category = ?6, is_read = ?7, is_important = ?8, updated_at = ?9, html_body = ?10 WHERE id = ?1",
             params![
                 email.id,
                 email.sender,
@@ -226,7 +259,8 @@ impl Database {
                 email.category,
                 email.is_read,
                 email.is_important,
-                email.updated_at.to_rfc3339()
+                email.updated_at.to_rfc3339(),
+                email.html_body
             ],
         )?;
         Ok(())
@@ -238,25 +272,20 @@ impl Database {
     }
 
     pub fn get_all_emails(&self) -> Result<Vec<Email>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, sender, recipient, subject, body, category, is_read, is_important, created_at, updated_at
-             FROM emails ORDER BY created_at DESC"
-        )?;
-
-        let email_iter = stmt.query_map([], Self::row_to_email)?;
-
-        let mut emails = Vec::new();
-        for email in email_iter {
-            emails.push(email?);
-        }
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM emails ORDER BY {} DESC",
+            EMAIL_COLUMNS, EMAIL_ORDER
+        ))?;
+
+        let mut emails = stmt
+            .query_map([], Self::row_to_email)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        self.load_addresses_batch(&mut emails)?;
         Ok(emails)
     }
 
     pub fn search_emails(&self, filter: &EmailFilter) -> Result<Vec<Email>> {
-        let mut query = String::from(
-            "SELECT id, sender, recipient, subject, body, category, is_read, is_important, created_at, updated_at
-             FROM emails WHERE 1=1"
-        );
+        let mut query = format!("SELECT {} FROM emails WHERE 1=1", EMAIL_COLUMNS);
         let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
 
         if let Some(sender) = &filter.sender {
@@ -264,9 +293,23 @@ impl Database {
             params.push(Box::new(format!("%{}%", sender)));
         }
 
+        // 收件人匹配任意收件角色的地址或显示名称
         if let Some(recipient) = &filter.recipient {
-            query.push_str(" AND recipient LIKE ?");
-            params.push(Box::new(format!("%{}%", recipient)));
+            let roles = AddressRole::RECIPIENTS
+                .iter()
+                .map(|role| format!("'{}'", role.as_str()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            query.push_str(&format!(
+                " AND (recipient LIKE ? OR EXISTS (SELECT 1 FROM email_addresses a
+                     WHERE a.email_id = emails.id AND a.role IN ({})
+                       AND (a.address LIKE ? OR a.name LIKE ?)))",
+                roles
+            ));
+            let pattern = format!("%{}%", recipient);
+            params.push(Box::new(pattern.clone()));
+            params.push(Box::new(pattern.clone()));
+            params.push(Box::new(pattern));
         }
 
         if let Some(category) = &filter.category {
@@ -274,6 +317,16 @@ impl Database {
             params.push(Box::new(category.clone()));
         }
 
+        if let Some(account_id) = filter.account_id {
+            query.push_str(" AND account_id = ?");
+            params.push(Box::new(account_id));
+        }
+
+        if let Some(folder) = &filter.folder {
+            query.push_str(" AND folder = ?");
+            params.push(Box::new(folder.clone()));
+        }
+
         if let Some(is_read) = filter.is_read {
             query.push_str(" AND is_read = ?");
             params.push(Box::new(is_read));
@@ -284,6 +337,11 @@ impl Database {
             params.push(Box::new(is_important));
         }
 
+        if let Some(has_attachment) = filter.has_attachment {
+            query.push_str(" AND has_attachment = ?");
+            params.push(Box::new(has_attachment));
+        }
+
         if let Some(keyword) = &filter.keyword {
             query.push_str(" AND (subject LIKE ? OR body LIKE ?)");
             let keyword_pattern = format!("%{}%", keyword);
@@ -291,17 +349,15 @@ impl Database {
             params.push(Box::new(keyword_pattern));
         }
 
-        query.push_str(" ORDER BY created_at DESC");
+        query.push_str(&format!(" ORDER BY {} DESC", EMAIL_ORDER));
 
         let mut stmt = self.conn.prepare(&query)?;
         let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
         
-        let email_iter = stmt.query_map(&param_refs[..], Self::row_to_email)?;
-
-        let mut emails = Vec::new();
-        for email in email_iter {
-            emails.push(email?);
-        }
+        let mut emails = stmt
+            .query_map(&param_refs[..], Self::row_to_email)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        self.load_addresses_batch(&mut emails)?;
         Ok(emails)
     }
 
@@ -0,0 +1,213 @@
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Row};
+use crate::database::connection::Database;
+use crate::models::draft::Draft;
+use crate::models::outgoing::OutgoingMessage;
+
+const DRAFT_COLUMNS: &str =
+    "id, account_id, source_email_id, message, version, message_id, \
+     server_folder, server_version, server_error, created_at, updated_at";
+
+impl Database {
+    pub fn insert_draft(&self, draft: &Draft) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO drafts (id, account_id, source_email_id, message, version, message_id,
+                                 server_folder, server_version, server_error, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                draft.id,
+                draft.account_id,
+                draft.source_email_id,
+                serde_json::to_string(&draft.message)?,
+                draft.version,
+                draft.message_id,
+                draft.server_folder,
+                draft.server_version,
+                draft.server_error,
+                draft.created_at,
+                draft.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_draft(&self, id: &str) -> Result<Option<Draft>> {
+        let mut stmt = self.conn.prepare(&format!("SELECT {} FROM drafts WHERE id = ?1", DRAFT_COLUMNS))?;
+        let mut rows = stmt.query([id])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row_to_draft(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 草稿列表，按最后修改时间倒序排列，`account_id` 为 None 时返回全部草稿
+    ///
+    /// 已放入发件箱的草稿不列出，发出后删除，撤回后重新出现。
+    pub fn get_drafts(&self, account_id: Option<i32>) -> Result<Vec<Draft>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM drafts
+             WHERE (?1 IS NULL OR account_id = ?1)
+               AND id NOT IN (SELECT draft_id FROM outbox WHERE draft_id IS NOT NULL)
+             ORDER BY updated_at DESC",
+            DRAFT_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query([account_id])?;
+        let mut drafts = Vec::new();
+        while let Some(row) = rows.next()? {
+            drafts.push(row_to_draft(row)?);
+        }
+        Ok(drafts)
+    }
+
+    /// 保存草稿的新内容，版本加一
+    ///
+    /// `version` 为编辑所基于的版本，与当前版本不同说明草稿已在别处保存过，
+    /// 此时拒绝覆盖，避免较早发出的自动保存覆盖较新的内容。
+    pub fn update_draft(
+        &self,
+        id: &str,
+        version: i64,
+        account_id: Option<i32>,
+        message: &OutgoingMessage,
+    ) -> Result<Draft> {
+        let changed = self.conn.execute(
+            "UPDATE drafts SET account_id = ?3, message = ?4, version = version + 1, updated_at = ?5
+             WHERE id = ?1 AND version = ?2",
+            params![
+                id,
+                version,
+                account_id,
+                serde_json::to_string(message)?,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+
+        let draft = self.get_draft(id)?.ok_or_else(|| anyhow!("草稿不存在"))?;
+        if changed == 0 {
+            return Err(anyhow!(
+                "草稿已被修改（当前版本 {}，提交的版本 {}），请重新加载后再保存",
+                draft.version,
+                version
+            ));
+        }
+        Ok(draft)
+    }
+
+    /// 记录上传到服务器的版本，并发的上传中较早的版本不会覆盖较新的记录
+    pub fn record_draft_upload(&self, id: &str, version: i64, folder: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE drafts SET server_version = MAX(COALESCE(server_version, 0), ?2),
+                               server_folder = ?3, server_error = NULL
+             WHERE id = ?1",
+            params![id, version, folder],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_draft_error(&self, id: &str, error: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE drafts SET server_error = ?2 WHERE id = ?1",
+            params![id, error],
+        )?;
+        Ok(())
+    }
+
+    /// 服务器副本已删除（例如更换了发件账户）
+    pub fn clear_draft_server_copy(&self, id: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE drafts SET server_folder = NULL, server_version = NULL WHERE id = ?1",
+            [id],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_draft(&self, id: &str) -> Result<()> {
+        self.conn.execute("DELETE FROM drafts WHERE id = ?1", [id])?;
+        Ok(())
+    }
+}
+
+fn row_to_draft(row: &Row) -> Result<Draft> {
+    let message: String = row.get("message")?;
+    Ok(Draft {
+        id: row.get("id")?,
+        account_id: row.get("account_id")?,
+        source_email_id: row.get("source_email_id")?,
+        message: serde_json::from_str(&message)?,
+        version: row.get("version")?,
+        message_id: row.get("message_id")?,
+        server_folder: row.get("server_folder")?,
+        server_version: row.get("server_version")?,
+        server_error: row.get("server_error")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_autosave_is_rejected() {
+        let db = Database::new(":memory:").unwrap();
+
+        let message = OutgoingMessage { subject: "周报".into(), ..Default::default() };
+        let draft = Draft::new(None, None, message.clone(), "draft@localhost".into());
+        db.insert_draft(&draft).unwrap();
+
+        let edited = OutgoingMessage { subject: "周报（修改）".into(), ..message.clone() };
+        let saved = db.update_draft(&draft.id, 1, None, &edited).unwrap();
+        assert_eq!(saved.version, 2);
+        assert_eq!(saved.message.subject, "周报（修改）");
+
+        // 基于第 1 版的另一次保存不能覆盖第 2 版
+        assert!(db.update_draft(&draft.id, 1, None, &message).is_err());
+        assert_eq!(db.get_draft(&draft.id).unwrap().unwrap().message.subject, "周报（修改）");
+
+        db.record_draft_upload(&draft.id, 2, "Drafts").unwrap();
+        db.record_draft_upload(&draft.id, 1, "Drafts").unwrap();
+        assert!(db.get_draft(&draft.id).unwrap().unwrap().is_uploaded());
+    }
+
+    #[test]
+    fn test_queued_draft_is_kept_but_not_listed() {
+        use crate::models::outbox::{OutboxMessage, OutboxStatus};
+
+        let db = Database::new(":memory:").unwrap();
+        db.conn.execute(
+            "INSERT INTO email_accounts (id, provider_id, email_address, display_name, username, password, created_at)
+             VALUES (1, 1, 'me@example.com', '', 'me', '', '')",
+            [],
+        ).unwrap();
+
+        let message = OutgoingMessage { subject: "周报".into(), ..Default::default() };
+        let draft = Draft::new(Some(1), None, message.clone(), "draft@example.com".into());
+        db.insert_draft(&draft).unwrap();
+
+        let time = "2030-01-01T00:00:00Z".to_string();
+        db.insert_outbox_message(&OutboxMessage {
+            id: "queued".into(),
+            account_id: 1,
+            message,
+            message_id: "queued@example.com".into(),
+            source_email_id: None,
+            draft_id: Some(draft.id.clone()),
+            status: OutboxStatus::Queued,
+            send_at: time.clone(),
+            next_attempt_at: time.clone(),
+            attempts: 0,
+            last_error: None,
+            created_at: time.clone(),
+            updated_at: time,
+        }).unwrap();
+        assert!(db.get_drafts(Some(1)).unwrap().is_empty());
+        assert!(db.get_draft(&draft.id).unwrap().is_some());
+
+        // 撤回后原草稿重新出现在列表中
+        let recalled = db.recall_outbox_message("queued").unwrap().unwrap();
+        assert_eq!(recalled.draft_id.as_deref(), Some(draft.id.as_str()));
+        assert_eq!(db.get_drafts(Some(1)).unwrap()[0].id, draft.id);
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::database::connection::Database;
+use crate::models::encryption::{EncryptionConfig, KeySource};
+
+impl Database {
+    /// 获取加密配置，首次启动时为 None
+    pub fn get_encryption_config(&self) -> Result<Option<EncryptionConfig>> {
+        let config = self.conn.query_row(
+            "SELECT key_source, salt, verifier, updated_at FROM encryption_config WHERE id = 1",
+            [],
+            |row| {
+                let key_source: String = row.get("key_source")?;
+                let key_source = KeySource::parse(&key_source).ok_or_else(|| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        format!("未知的密钥来源: {}", key_source).into(),
+                    )
+                })?;
+
+                Ok(EncryptionConfig {
+                    key_source,
+                    salt: row.get("salt")?,
+                    verifier: row.get("verifier")?,
+                    updated_at: row.get("updated_at")?,
+                })
+            },
+        ).optional()?;
+
+        Ok(config)
+    }
+
+    /// 在同一事务中重新加密所有账户密码和 OAuth2 令牌，并保存新的加密配置
+    ///
+    /// 任意一条记录处理失败都会回滚，数据库中不会出现新旧密钥混用的情况。
+    pub fn reencrypt_secrets<F>(&self, config: &EncryptionConfig, reencrypt: F) -> Result<()>
+    where
+        F: Fn(&str) -> Result<String>,
+    {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let passwords = {
+            let mut stmt = tx.prepare("SELECT id, password FROM email_accounts")?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        for (id, password) in passwords {
+            tx.execute(
+                "UPDATE email_accounts SET password = ?1 WHERE id = ?2",
+                params![reencrypt(&password)?, id],
+            )?;
+        }
+
+        let tokens = {
+            let mut stmt = tx.prepare("SELECT account_id, access_token, refresh_token FROM oauth_tokens")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, i32>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        for (account_id, access_token, refresh_token) in tokens {
+            let refresh_token = refresh_token.as_deref().map(&reencrypt).transpose()?;
+            tx.execute(
+                "UPDATE oauth_tokens SET access_token = ?1, refresh_token = ?2 WHERE account_id = ?3",
+                params![reencrypt(&access_token)?, refresh_token, account_id],
+            )?;
+        }
+
+        save_encryption_config(&tx, config)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 清空所有账户密码并删除 OAuth2 令牌，同时保存新的加密配置
+    ///
+    /// `empty_password` 为用新密钥加密的空密码，账户需要重新输入密码后才能登录。
+    pub fn clear_secrets(&self, config: &EncryptionConfig, empty_password: &str) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("UPDATE email_accounts SET password = ?1", [empty_password])?;
+        tx.execute("DELETE FROM oauth_tokens", [])?;
+        save_encryption_config(&tx, config)?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn save_encryption_config(conn: &Connection, config: &EncryptionConfig) -> Result<()> {
+    conn.execute(
+        "INSERT INTO encryption_config (id, key_source, salt, verifier, updated_at)
+         VALUES (1, ?1, ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET
+            key_source = excluded.key_source,
+            salt = excluded.salt,
+            verifier = excluded.verifier,
+            updated_at = excluded.updated_at",
+        params![
+            config.key_source.as_str(),
+            config.salt,
+            config.verifier,
+            config.updated_at
+        ],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+-- 1.0 版本创建的数据库：没有设置 user_version，服务商使用 use_ssl/use_tls
+CREATE TABLE emails (
+    id TEXT PRIMARY KEY,
+    sender TEXT NOT NULL,
+    recipient TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    category TEXT NOT NULL,
+    is_read BOOLEAN NOT NULL DEFAULT 0,
+    is_important BOOLEAN NOT NULL DEFAULT 0,
+    account_id INTEGER,
+    message_id TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES email_accounts (id)
+);
+
+CREATE TABLE email_providers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    provider_type TEXT NOT NULL UNIQUE,
+    imap_server TEXT NOT NULL,
+    imap_port INTEGER NOT NULL,
+    smtp_server TEXT NOT NULL,
+    smtp_port INTEGER NOT NULL,
+    use_ssl BOOLEAN NOT NULL DEFAULT 1,
+    use_tls BOOLEAN NOT NULL DEFAULT 1
+);
+
+CREATE TABLE email_accounts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    provider_id INTEGER NOT NULL,
+    email_address TEXT NOT NULL UNIQUE,
+    display_name TEXT NOT NULL,
+    username TEXT NOT NULL,
+    password TEXT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT 1,
+    last_sync TEXT,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (provider_id) REFERENCES email_providers (id)
+);
+
+CREATE TABLE email_categories (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    color TEXT NOT NULL DEFAULT '#007bff',
+    description TEXT,
+    is_system BOOLEAN NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_emails_sender ON emails(sender);
+CREATE INDEX idx_emails_recipient ON emails(recipient);
+CREATE INDEX idx_emails_category ON emails(category);
+CREATE INDEX idx_emails_account ON emails(account_id);
+
+INSERT INTO email_providers (id, name, provider_type, imap_server, imap_port, smtp_server, smtp_port, use_ssl, use_tls) VALUES
+    (1, 'Gmail', 'gmail', 'imap.gmail.com', 993, 'smtp.gmail.com', 587, 1, 1),
+    (2, 'QQ邮箱', 'qq', 'imap.qq.com', 993, 'smtp.qq.com', 587, 1, 1),
+    (3, '163邮箱', '163', 'imap.163.com', 993, 'smtp.163.com', 994, 1, 0),
+    (4, '126邮箱', '126', 'imap.126.com', 993, 'smtp.126.com', 994, 1, 0),
+    (5, 'Outlook', 'outlook', 'outlook.office365.com', 993, 'smtp-mail.outlook.com', 587, 1, 1),
+    (6, '公司邮箱', 'custom', 'mail.example.com', 143, 'mail.example.com', 25, 0, 0);
+
+INSERT INTO email_categories (name, color, description, is_system, created_at) VALUES
+    ('收件箱', '#007bff', '接收的邮件', 1, '2024-03-01T08:00:00+00:00'),
+    ('发件箱', '#28a745', '发送的邮件', 1, '2024-03-01T08:00:00+00:00'),
+    ('草稿箱', '#ffc107', '草稿邮件', 1, '2024-03-01T08:00:00+00:00'),
+    ('垃圾箱', '#dc3545', '已删除的邮件', 1, '2024-03-01T08:00:00+00:00'),
+    ('工作', '#6f42c1', '工作相关邮件', 0, '2024-03-01T08:00:00+00:00'),
+    ('个人', '#fd7e14', '个人邮件', 0, '2024-03-01T08:00:00+00:00'),
+    ('重要', '#e83e8c', '重要邮件', 0, '2024-03-01T08:00:00+00:00');
+
+INSERT INTO email_accounts (id, provider_id, email_address, display_name, username, password, is_active, last_sync, created_at) VALUES
+    (1, 6, 'alice@example.com', 'Alice', 'alice', 'secret', 1, '2024-03-02T09:00:00+00:00', '2024-03-01T08:00:00+00:00');
+
+INSERT INTO emails (id, sender, recipient, subject, body, category, is_read, is_important, account_id, message_id, created_at, updated_at) VALUES
+    ('email-1', 'Alice <alice@example.com>', 'bob@example.com', '季度报告', '附件是第一季度的报告。', '收件箱', 1, 0, 1,
+     '<report-1@example.com>', '2024-03-02T09:00:00+00:00', '2024-03-02T09:00:00+00:00');
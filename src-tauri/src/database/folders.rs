@@ -0,0 +1,141 @@
+use anyhow::Result;
+use rusqlite::params;
+use crate::database::connection::Database;
+use crate::models::folder::MailFolder;
+
+impl Database {
+    /// 获取账户的文件夹列表，收件箱和特殊文件夹排在前面
+    pub fn get_folders(&self, account_id: i32) -> Result<Vec<MailFolder>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, account_id, name, display_name, delimiter, special_use, category, is_selectable, updated_at
+             FROM folders WHERE account_id = ?1 AND missing_since IS NULL
+             ORDER BY UPPER(name) = 'INBOX' DESC, special_use IS NULL, display_name"
+        )?;
+
+        let folder_iter = stmt.query_map([account_id], |row| {
+            Ok(MailFolder {
+                id: row.get("id")?,
+                account_id: row.get("account_id")?,
+                name: row.get("name")?,
+                display_name: row.get("display_name")?,
+                delimiter: row.get("delimiter")?,
+                special_use: row.get("special_use")?,
+                category: row.get("category")?,
+                is_selectable: row.get("is_selectable")?,
+                updated_at: row.get("updated_at")?,
+            })
+        })?;
+
+        let mut folders = Vec::new();
+        for folder in folder_iter {
+            folders.push(folder?);
+        }
+        Ok(folders)
+    }
+
+    /// 用服务器返回的列表更新账户的文件夹
+    ///
+    /// 列表中没有的文件夹先隐藏，之后的同步仍然没有时才连同本地邮件一起删除，
+    /// 避免不完整的 LIST 响应或文件夹改名清空本地缓存。空列表视为 LIST 失败，不做任何修改。
+    pub fn save_folders(&self, account_id: i32, folders: &[MailFolder]) -> Result<()> {
+        if folders.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.unchecked_transaction()?;
+
+        for folder in folders {
+            tx.execute(
+                "INSERT INTO folders (account_id, name, display_name, delimiter, special_use, category, is_selectable, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                 ON CONFLICT(account_id, name) DO UPDATE SET
+                    display_name = excluded.display_name,
+                    delimiter = excluded.delimiter,
+                    special_use = excluded.special_use,
+                    category = excluded.category,
+                    is_selectable = excluded.is_selectable,
+                    missing_since = NULL,
+                    updated_at = excluded.updated_at",
+                params![
+                    account_id,
+                    folder.name,
+                    folder.display_name,
+                    folder.delimiter,
+                    folder.special_use,
+                    folder.category,
+                    folder.is_selectable,
+                    folder.updated_at
+                ],
+            )?;
+        }
+
+        let existing: Vec<(String, Option<String>)> = {
+            let mut stmt = tx.prepare("SELECT name, missing_since FROM folders WHERE account_id = ?1")?;
+            let names = stmt.query_map([account_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            names.collect::<rusqlite::Result<_>>()?
+        };
+
+        for (name, missing_since) in existing {
+            if folders.iter().any(|folder| folder.name == name) {
+                continue;
+            }
+            if missing_since.is_none() {
+                tx.execute(
+                    "UPDATE folders SET missing_since = ?3 WHERE account_id = ?1 AND name = ?2",
+                    params![account_id, name, chrono::Utc::now().to_rfc3339()],
+                )?;
+                continue;
+            }
+
+            self.clear_folder_emails(account_id, &name)?;
+            tx.execute(
+                "DELETE FROM sync_states WHERE account_id = ?1 AND folder = ?2",
+                params![account_id, name],
+            )?;
+            tx.execute(
+                "DELETE FROM folders WHERE account_id = ?1 AND name = ?2",
+                params![account_id, name],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::email::Email;
+
+    #[test]
+    fn test_missing_folder_is_hidden_before_deletion() {
+        let db = Database::new(":memory:").unwrap();
+        db.conn.execute(
+            "INSERT INTO email_accounts (id, provider_id, email_address, display_name, username, password, created_at)
+             VALUES (1, 1, 'me@example.com', '', 'me', '', '')",
+            [],
+        ).unwrap();
+        let folder = |name: &str| MailFolder::from_list(1, name, name, Some("/"), &[]);
+
+        db.save_folders(1, &[folder("INBOX"), folder("Projects")]).unwrap();
+        let mut email = Email::new("a@example.com".into(), "b@example.com".into(), "进度".into(), String::new(), "收件箱".into());
+        email.account_id = Some(1);
+        email.folder = Some("Projects".into());
+        db.insert_email(&email).unwrap();
+
+        // 第一次缺失只隐藏，空列表不做任何修改
+        db.save_folders(1, &[folder("INBOX")]).unwrap();
+        db.save_folders(1, &[]).unwrap();
+        assert_eq!(db.get_folders(1).unwrap().len(), 1);
+        assert!(db.get_email_by_id(&email.id).unwrap().is_some());
+
+        // 再次出现时恢复
+        db.save_folders(1, &[folder("INBOX"), folder("Projects")]).unwrap();
+        assert_eq!(db.get_folders(1).unwrap().len(), 2);
+
+        // 连续两次缺失才删除本地邮件
+        db.save_folders(1, &[folder("INBOX")]).unwrap();
+        db.save_folders(1, &[folder("INBOX")]).unwrap();
+        assert!(db.get_email_by_id(&email.id).unwrap().is_none());
+    }
+}
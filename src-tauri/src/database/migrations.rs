@@ -0,0 +1,642 @@
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection};
+use crate::database::connection::Database;
+use crate::models::address::EmailAddress;
+
+/// 数据库结构的一个升级步骤
+struct Migration {
+    description: &'static str,
+    up: fn(&Connection) -> Result<()>,
+}
+
+/// 按顺序排列，执行完第 N 项后 `PRAGMA user_version` 为 N
+///
+/// 已发布的步骤不能再修改，结构变化只能在末尾追加新的步骤。
+const MIGRATIONS: &[Migration] = &[
+    Migration { description: "初始结构", up: initial_schema },
+    Migration { description: "邮件解析与增量同步", up: mail_sync },
+    Migration { description: "服务商连接安全设置", up: provider_security },
+    Migration { description: "OAuth2 与凭据加密", up: credentials },
+    Migration { description: "附件与结构化地址", up: attachments_and_addresses },
+    Migration { description: "邮件会话", up: threading },
+    Migration { description: "草稿与发件箱", up: drafts_and_outbox },
+    Migration { description: "文件夹缺失标记", up: folder_missing_since },
+    Migration { description: "补齐邮件地址", up: backfill_addresses },
+    Migration { description: "会话查询索引", up: thread_lookup_indexes },
+    Migration { description: "发件箱附件引用附件存储", up: outgoing_attachment_blobs },
+    Migration { description: "邮件引用表与会话主题表", up: references_and_thread_subjects },
+    Migration { description: "发件箱记录来源草稿", up: outbox_draft_id },
+];
+
+/// 当前程序使用的数据库结构版本
+pub const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
+impl Database {
+    /// 把数据库结构升级到 `SCHEMA_VERSION`
+    ///
+    /// 引入版本号之前创建的数据库 `user_version` 为 0，与新数据库一样从第一步开始，
+    /// 因此每一步都只创建或补充缺少的表和列。已有数据时先备份到 `<数据库>.v<版本>.bak`，
+    /// 每一步在单独的事务中执行，失败时数据库停留在上一个版本。
+    pub(crate) fn migrate(&self) -> Result<()> {
+        let version = self.schema_version()?;
+        if version > SCHEMA_VERSION {
+            bail!("数据库版本 {} 高于程序支持的版本 {}，请使用新版程序打开", version, SCHEMA_VERSION);
+        }
+        if version == SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        if self.has_tables()? {
+            self.backup(version)?;
+        }
+        apply(&self.conn, MIGRATIONS, version)
+    }
+
+    pub fn schema_version(&self) -> Result<i32> {
+        let version = self.conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        Ok(version)
+    }
+
+    fn has_tables(&self) -> Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// 升级前复制一份完整的数据库，内存数据库不备份
+    fn backup(&self, version: i32) -> Result<()> {
+        let path = match self.conn.path() {
+            Some(path) if !path.is_empty() => path,
+            _ => return Ok(()),
+        };
+
+        let backup = format!("{}.v{}.bak", path, version);
+        match std::fs::remove_file(&backup) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        self.conn.execute("VACUUM INTO ?1", [&backup])
+            .with_context(|| format!("升级前备份数据库到 {} 失败", backup))?;
+        Ok(())
+    }
+}
+
+/// 从 `version` 之后的步骤开始依次执行，每一步与版本号的更新在同一事务中提交
+fn apply(conn: &Connection, migrations: &[Migration], version: i32) -> Result<()> {
+    for (index, migration) in migrations.iter().enumerate().skip(version as usize) {
+        let target = index as i32 + 1;
+        let tx = conn.unchecked_transaction()?;
+        (migration.up)(&tx)
+            .with_context(|| format!("数据库升级到版本 {}（{}）失败", target, migration.description))?;
+        tx.pragma_update(None, "user_version", target)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        if row.get::<_, String>("name")? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// 列已经存在时跳过，兼容引入版本号之前已经包含部分新列的数据库
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+/// 版本 1：最初发布的邮件、服务商、账户和分类表
+fn initial_schema(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS emails (
+            id TEXT PRIMARY KEY,
+            sender TEXT NOT NULL,
+            recipient TEXT NOT NULL,
+            subject TEXT NOT NULL,
+            body TEXT NOT NULL,
+            category TEXT NOT NULL,
+            is_read BOOLEAN NOT NULL DEFAULT 0,
+            is_important BOOLEAN NOT NULL DEFAULT 0,
+            account_id INTEGER,
+            message_id TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES email_accounts (id)
+        );
+
+        CREATE TABLE IF NOT EXISTS email_providers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            provider_type TEXT NOT NULL UNIQUE,
+            imap_server TEXT NOT NULL,
+            imap_port INTEGER NOT NULL,
+            smtp_server TEXT NOT NULL,
+            smtp_port INTEGER NOT NULL,
+            use_ssl BOOLEAN NOT NULL DEFAULT 1,
+            use_tls BOOLEAN NOT NULL DEFAULT 1
+        );
+
+        CREATE TABLE IF NOT EXISTS email_accounts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            provider_id INTEGER NOT NULL,
+            email_address TEXT NOT NULL UNIQUE,
+            display_name TEXT NOT NULL,
+            username TEXT NOT NULL,
+            password TEXT NOT NULL,
+            is_active BOOLEAN NOT NULL DEFAULT 1,
+            last_sync TEXT,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (provider_id) REFERENCES email_providers (id)
+        );
+
+        CREATE TABLE IF NOT EXISTS email_categories (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            color TEXT NOT NULL DEFAULT '#007bff',
+            description TEXT,
+            is_system BOOLEAN NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_emails_sender ON emails(sender);
+        CREATE INDEX IF NOT EXISTS idx_emails_recipient ON emails(recipient);
+        CREATE INDEX IF NOT EXISTS idx_emails_category ON emails(category);
+        CREATE INDEX IF NOT EXISTS idx_emails_account ON emails(account_id);",
+    )?;
+    Ok(())
+}
+
+/// 版本 2：MIME 解析结果、IMAP 文件夹与 UID、同步进度和离线标记队列
+fn mail_sync(conn: &Connection) -> Result<()> {
+    add_column(conn, "emails", "html_body", "TEXT")?;
+    add_column(conn, "emails", "folder", "TEXT")?;
+    add_column(conn, "emails", "uid", "INTEGER")?;
+    add_column(conn, "emails", "has_attachment", "BOOLEAN NOT NULL DEFAULT 0")?;
+    add_column(conn, "emails", "sent_at", "TEXT")?;
+    add_column(conn, "emails", "received_at", "TEXT")?;
+
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS folders (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            display_name TEXT NOT NULL,
+            delimiter TEXT,
+            special_use TEXT,
+            category TEXT NOT NULL,
+            is_selectable BOOLEAN NOT NULL DEFAULT 1,
+            updated_at TEXT NOT NULL,
+            UNIQUE (account_id, name),
+            FOREIGN KEY (account_id) REFERENCES email_accounts (id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS sync_states (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            folder TEXT NOT NULL,
+            uid_validity INTEGER NOT NULL,
+            last_uid INTEGER NOT NULL DEFAULT 0,
+            highest_modseq INTEGER NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL,
+            UNIQUE (account_id, folder),
+            FOREIGN KEY (account_id) REFERENCES email_accounts (id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS pending_operations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            folder TEXT NOT NULL,
+            uid INTEGER NOT NULL,
+            flag TEXT NOT NULL,
+            value BOOLEAN NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            created_at TEXT NOT NULL,
+            UNIQUE (account_id, folder, uid, flag),
+            FOREIGN KEY (account_id) REFERENCES email_accounts (id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_emails_message_id ON emails(message_id);",
+    )?;
+    Ok(())
+}
+
+/// 版本 3：`use_ssl`/`use_tls` 换成 IMAP 与 SMTP 各自的安全模式
+///
+/// 旧版本的 IMAP 连接总是使用 TLS，`use_ssl` 没有生效，一律转换为 `implicit_tls`；
+/// SMTP 中 `use_tls` 为 false 的服务商（163、126 的 994 端口）使用 SSL 直连。
+/// 旧版本从不使用明文连接，转换后也不会出现 `none`。
+fn provider_security(conn: &Connection) -> Result<()> {
+    add_column(conn, "email_providers", "imap_security", "TEXT NOT NULL DEFAULT 'implicit_tls'")?;
+    add_column(conn, "email_providers", "smtp_security", "TEXT NOT NULL DEFAULT 'starttls'")?;
+    add_column(conn, "email_providers", "requires_imap_id", "BOOLEAN NOT NULL DEFAULT 0")?;
+
+    if has_column(conn, "email_providers", "use_ssl")? {
+        conn.execute_batch(
+            "UPDATE email_providers SET
+                imap_security = 'implicit_tls',
+                smtp_security = CASE WHEN use_tls THEN 'starttls' ELSE 'implicit_tls' END;
+
+            ALTER TABLE email_providers DROP COLUMN use_ssl;
+            ALTER TABLE email_providers DROP COLUMN use_tls;",
+        )?;
+    }
+    Ok(())
+}
+
+/// 版本 4：账户认证方式、OAuth2 令牌和主密码配置
+fn credentials(conn: &Connection) -> Result<()> {
+    add_column(conn, "email_accounts", "auth_method", "TEXT NOT NULL DEFAULT 'password'")?;
+
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS oauth_tokens (
+            account_id INTEGER PRIMARY KEY,
+            access_token TEXT NOT NULL,
+            refresh_token TEXT,
+            expires_at TEXT,
+            updated_at TEXT NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES email_accounts (id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS encryption_config (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            key_source TEXT NOT NULL,
+            salt TEXT,
+            verifier TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );",
+    )?;
+    Ok(())
+}
+
+/// 版本 5：附件元数据和每封邮件的结构化地址
+///
+/// 已有邮件的地址由版本 9 补齐。
+fn attachments_and_addresses(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS attachments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            email_id TEXT NOT NULL,
+            filename TEXT NOT NULL,
+            mime_type TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            content_id TEXT,
+            is_inline BOOLEAN NOT NULL DEFAULT 0,
+            sha256 TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (email_id) REFERENCES emails (id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS email_addresses (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            email_id TEXT NOT NULL,
+            role TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            name TEXT,
+            address TEXT NOT NULL,
+            FOREIGN KEY (email_id) REFERENCES emails (id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_attachments_email ON attachments(email_id);
+        CREATE INDEX IF NOT EXISTS idx_email_addresses_email ON email_addresses(email_id);
+        CREATE INDEX IF NOT EXISTS idx_email_addresses_address ON email_addresses(address);",
+    )?;
+    Ok(())
+}
+
+/// 版本 6：回复关系头和会话表
+///
+/// 已有邮件的会话由启动时的 `rethread_unthreaded` 补齐。
+fn threading(conn: &Connection) -> Result<()> {
+    add_column(conn, "emails", "in_reply_to", "TEXT")?;
+    add_column(conn, "emails", "message_references", "TEXT")?;
+    add_column(conn, "emails", "thread_id", "TEXT")?;
+    add_column(conn, "emails", "gmail_thread_id", "TEXT")?;
+
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS threads (
+            id TEXT PRIMARY KEY,
+            account_id INTEGER,
+            subject TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_emails_thread ON emails(thread_id);",
+    )?;
+    Ok(())
+}
+
+/// 版本 7：本地草稿、发件箱和应用设置
+fn drafts_and_outbox(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS drafts (
+            id TEXT PRIMARY KEY,
+            account_id INTEGER,
+            source_email_id TEXT,
+            message TEXT NOT NULL,
+            version INTEGER NOT NULL DEFAULT 1,
+            message_id TEXT NOT NULL,
+            server_folder TEXT,
+            server_version INTEGER,
+            server_error TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES email_accounts (id) ON DELETE SET NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS outbox (
+            id TEXT PRIMARY KEY,
+            account_id INTEGER NOT NULL,
+            message TEXT NOT NULL,
+            message_id TEXT NOT NULL,
+            source_email_id TEXT,
+            status TEXT NOT NULL DEFAULT 'queued',
+            send_at TEXT NOT NULL,
+            next_attempt_at TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES email_accounts (id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS app_settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );",
+    )?;
+    Ok(())
+}
+
+/// 版本 8：LIST 中暂时缺失的文件夹先标记隐藏，之后仍缺失时才删除
+fn folder_missing_since(conn: &Connection) -> Result<()> {
+    add_column(conn, "folders", "missing_since", "TEXT")
+}
+
+/// 版本 9：为引入地址表之前的邮件根据 `sender` 和 `recipient` 生成地址记录，
+/// 之后保存的邮件都会同时写入地址
+fn backfill_addresses(conn: &Connection) -> Result<()> {
+    let emails = {
+        let mut stmt = conn.prepare(
+            "SELECT id, sender, recipient FROM emails
+             WHERE NOT EXISTS (SELECT 1 FROM email_addresses WHERE email_id = emails.id)",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+
+    let mut insert = conn.prepare(
+        "INSERT INTO email_addresses (email_id, role, position, name, address)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for (id, sender, recipient) in emails {
+        let from: Vec<EmailAddress> = EmailAddress::parse_list(&sender).into_iter().take(1).collect();
+        let to = EmailAddress::parse_list(&recipient);
+        for (role, addresses) in [("from", from), ("to", to)] {
+            for (position, address) in addresses.iter().enumerate() {
+                insert.execute(params![id, role, position as i64, address.name, address.address])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 版本 10：新邮件增量计算会话时按 In-Reply-To 和 Gmail 会话 ID 查找相关邮件
+fn thread_lookup_indexes(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_emails_in_reply_to ON emails(in_reply_to);
+        CREATE INDEX IF NOT EXISTS idx_emails_gmail_thread ON emails(gmail_thread_id);",
+    )?;
+    Ok(())
+}
+
+/// 版本 11：发件箱和草稿中指向附件存储文件的附件改为按 SHA-256 引用
+///
+/// 之前转发的附件保存的是 `attachments/<前两位>/<sha256>` 形式的路径，
+/// 附件目录移动到应用数据目录后这些路径已经失效。
+fn outgoing_attachment_blobs(conn: &Connection) -> Result<()> {
+    for table in ["outbox", "drafts"] {
+        let rows = {
+            let mut stmt = conn.prepare(&format!("SELECT id, message FROM {}", table))?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+
+        for (id, message) in rows {
+            let mut message: serde_json::Value = serde_json::from_str(&message)?;
+            let mut changed = false;
+            if let Some(attachments) = message.get_mut("attachments").and_then(|a| a.as_array_mut()) {
+                for attachment in attachments {
+                    let sha256 = attachment.get("path").and_then(|p| p.as_str()).and_then(blob_path_sha256);
+                    if let Some(sha256) = sha256 {
+                        attachment["sha256"] = sha256.into();
+                        attachment["path"] = "".into();
+                        changed = true;
+                    }
+                }
+            }
+            if changed {
+                conn.execute(
+                    &format!("UPDATE {} SET message = ?1 WHERE id = ?2", table),
+                    [serde_json::to_string(&message)?, id],
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 版本 12：按 Message-ID 查找引用它的邮件，按主题键查找会话
+///
+/// 已有会话没有主题键，全部清空后由启动时的 `rethread_unthreaded` 重新计算。
+fn references_and_thread_subjects(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS email_references (
+            email_id TEXT NOT NULL,
+            message_id TEXT NOT NULL,
+            PRIMARY KEY (email_id, message_id),
+            FOREIGN KEY (email_id) REFERENCES emails (id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_email_references_message_id ON email_references(message_id);
+
+        CREATE TABLE IF NOT EXISTS thread_subjects (
+            thread_id TEXT NOT NULL,
+            subject_key TEXT NOT NULL,
+            PRIMARY KEY (thread_id, subject_key),
+            FOREIGN KEY (thread_id) REFERENCES threads (id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_thread_subjects_key ON thread_subjects(subject_key);",
+    )?;
+
+    let emails = {
+        let mut stmt = conn.prepare(
+            "SELECT id, in_reply_to, message_references FROM emails
+             WHERE in_reply_to IS NOT NULL OR message_references IS NOT NULL",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    let mut insert = conn.prepare(
+        "INSERT OR IGNORE INTO email_references (email_id, message_id) VALUES (?1, ?2)",
+    )?;
+    for (id, in_reply_to, references) in emails {
+        let references = references.unwrap_or_default();
+        for message_id in references.split_whitespace().chain(in_reply_to.as_deref()) {
+            insert.execute([&id, message_id])?;
+        }
+    }
+
+    conn.execute_batch(
+        "DELETE FROM threads;
+        UPDATE emails SET thread_id = NULL;",
+    )?;
+    Ok(())
+}
+
+/// 版本 13：从草稿发送时保留草稿，发出后才删除，撤回时恢复原草稿
+fn outbox_draft_id(conn: &Connection) -> Result<()> {
+    add_column(conn, "outbox", "draft_id", "TEXT")
+}
+
+/// 路径形如 `.../attachments/<前两位>/<sha256>` 时返回其中的 SHA-256
+fn blob_path_sha256(path: &str) -> Option<String> {
+    let path = std::path::Path::new(path);
+    let sha256 = path.file_name()?.to_str()?;
+    let prefix = path.parent()?;
+    let root = prefix.parent()?;
+    let is_blob = sha256.len() == 64
+        && sha256.chars().all(|c| c.is_ascii_hexdigit())
+        && prefix.file_name()?.to_str()? == &sha256[..2]
+        && root.file_name()?.to_str()? == "attachments";
+    is_blob.then(|| sha256.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 引入版本号之前创建的数据库，`user_version` 为 0
+    const V1_FIXTURE: &str = include_str!("fixtures/v1.sql");
+
+    fn temp_db_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.db", name, uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_upgrade_v1_fixture_to_head() {
+        let path = temp_db_path("xmail-v1");
+        Connection::open(&path).unwrap().execute_batch(V1_FIXTURE).unwrap();
+
+        let db = Database::new(&path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+
+        // 原有邮件保留，新增的列使用默认值，地址由版本 9 补齐
+        let email = db.get_email_by_id("email-1").unwrap().unwrap();
+        assert_eq!(email.subject, "季度报告");
+        assert!(!email.has_attachment);
+        assert_eq!(email.to[0].address, "bob@example.com");
+
+        // 服务商的 SSL 设置转换为安全模式，旧列被删除
+        let security = |id: i32| -> (String, String) {
+            db.conn.query_row(
+                "SELECT imap_security, smtp_security FROM email_providers WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).unwrap()
+        };
+        assert_eq!(security(1), ("implicit_tls".to_string(), "starttls".to_string()));
+        assert_eq!(security(3), ("implicit_tls".to_string(), "implicit_tls".to_string()));
+        // 旧版本忽略 use_ssl = 0，25 端口也使用 TLS，不能转换成明文
+        assert_eq!(security(6), ("implicit_tls".to_string(), "implicit_tls".to_string()));
+        assert!(!has_column(&db.conn, "email_providers", "use_ssl").unwrap());
+        assert!(db.get_drafts(None).unwrap().is_empty());
+
+        // 升级前的备份仍是旧版本
+        let backup = format!("{}.v0.bak", path.display());
+        let old = Connection::open(&backup).unwrap();
+        let version: i32 = old.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, 0);
+        assert!(has_column(&old, "email_providers", "use_ssl").unwrap());
+
+        drop(db);
+        drop(old);
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(&backup).ok();
+    }
+
+    #[test]
+    fn test_failed_step_rolls_back() {
+        fn failing(conn: &Connection) -> Result<()> {
+            conn.execute("CREATE TABLE half_done (id INTEGER)", [])?;
+            bail!("模拟失败")
+        }
+        let migrations = [
+            Migration { description: "初始结构", up: initial_schema },
+            Migration { description: "失败", up: failing },
+        ];
+
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(apply(&conn, &migrations, 0).is_err());
+
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, 1);
+        assert!(has_column(&conn, "emails", "subject").unwrap());
+        assert!(!has_column(&conn, "half_done", "id").unwrap());
+    }
+
+    #[test]
+    fn test_outgoing_attachment_paths_become_blob_references() {
+        let db = Database::new(":memory:").unwrap();
+        let conn = &db.conn;
+
+        let sha256 = "ab".repeat(32);
+        let message = serde_json::json!({
+            "subject": "转发",
+            "attachments": [
+                { "path": format!("attachments/ab/{}", sha256), "filename": "report.pdf" },
+                { "path": "/home/me/notes.txt" }
+            ]
+        });
+        conn.execute(
+            "INSERT INTO email_accounts (id, provider_id, email_address, display_name, username, password, created_at)
+             VALUES (1, 1, 'me@example.com', '', 'me', '', '')",
+            [],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO outbox (id, account_id, message, message_id, send_at, next_attempt_at, created_at, updated_at)
+             VALUES ('o1', 1, ?1, 'm@example.com', '', '', '', '')",
+            [message.to_string()],
+        ).unwrap();
+
+        // 重新执行版本 11
+        apply(conn, MIGRATIONS, 10).unwrap();
+        let message: String = conn.query_row("SELECT message FROM outbox", [], |row| row.get(0)).unwrap();
+        let message: serde_json::Value = serde_json::from_str(&message).unwrap();
+        assert_eq!(message["attachments"][0]["sha256"], sha256.as_str());
+        assert_eq!(message["attachments"][0]["path"], "");
+        assert_eq!(message["attachments"][1]["path"], "/home/me/notes.txt");
+        assert!(message["attachments"][1].get("sha256").is_none());
+    }
+}
@@ -1,3 +1,15 @@
 pub mod connection;
+mod sync_state;
+mod pending_operations;
+mod folders;
+mod oauth_tokens;
+mod encryption;
+mod attachments;
+mod addresses;
+mod threads;
+mod drafts;
+mod outbox;
+mod settings;
+mod migrations;
 
-pub use connection::*;
\ No newline at end of file
+pub use connection::*;
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension};
+use crate::database::connection::Database;
+use crate::models::oauth_token::OAuthToken;
+use crate::services::crypto_service::CryptoService;
+
+impl Database {
+    /// 获取账户的 OAuth2 令牌（已解密）
+    pub fn get_oauth_token(&self, account_id: i32) -> Result<Option<OAuthToken>> {
+        let row = self.conn.query_row(
+            "SELECT account_id, access_token, refresh_token, expires_at, updated_at
+             FROM oauth_tokens WHERE account_id = ?1",
+            [account_id],
+            |row| {
+                Ok((
+                    row.get::<_, i32>("account_id")?,
+                    row.get::<_, String>("access_token")?,
+                    row.get::<_, Option<String>>("refresh_token")?,
+                    row.get::<_, Option<String>>("expires_at")?,
+                    row.get::<_, String>("updated_at")?,
+                ))
+            },
+        ).optional()?;
+
+        let (account_id, access_token, refresh_token, expires_at, updated_at) = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        Ok(Some(OAuthToken {
+            account_id,
+            access_token: CryptoService::decrypt_password(&access_token)?,
+            refresh_token: refresh_token
+                .map(|token| CryptoService::decrypt_password(&token))
+                .transpose()?,
+            expires_at: expires_at
+                .map(|value| chrono::DateTime::parse_from_rfc3339(&value))
+                .transpose()?
+                .map(|value| value.with_timezone(&chrono::Utc)),
+            updated_at: chrono::DateTime::parse_from_rfc3339(&updated_at)?.with_timezone(&chrono::Utc),
+        }))
+    }
+
+    /// 保存账户的 OAuth2 令牌，令牌加密后存储
+    pub fn save_oauth_token(&self, token: &OAuthToken) -> Result<()> {
+        let refresh_token = token
+            .refresh_token
+            .as_deref()
+            .map(CryptoService::encrypt_password)
+            .transpose()?;
+
+        self.conn.execute(
+            "INSERT INTO oauth_tokens (account_id, access_token, refresh_token, expires_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(account_id) DO UPDATE SET
+                access_token = excluded.access_token,
+                refresh_token = excluded.refresh_token,
+                expires_at = excluded.expires_at,
+                updated_at = excluded.updated_at",
+            params![
+                token.account_id,
+                CryptoService::encrypt_password(&token.access_token)?,
+                refresh_token,
+                token.expires_at.map(|value| value.to_rfc3339()),
+                token.updated_at.to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension, Row};
+use crate::database::connection::Database;
+use crate::models::outbox::{outbox_time, OutboxMessage, OutboxStatus};
+
+const OUTBOX_COLUMNS: &str =
+    "id, account_id, message, message_id, source_email_id, draft_id, status, send_at, \
+     next_attempt_at, attempts, last_error, created_at, updated_at";
+
+impl Database {
+    pub fn insert_outbox_message(&self, message: &OutboxMessage) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO outbox (id, account_id, message, message_id, source_email_id, draft_id, status, send_at,
+                                 next_attempt_at, attempts, last_error, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                message.id,
+                message.account_id,
+                serde_json::to_string(&message.message)?,
+                message.message_id,
+                message.source_email_id,
+                message.draft_id,
+                message.status.as_str(),
+                message.send_at,
+                message.next_attempt_at,
+                message.attempts,
+                message.last_error,
+                message.created_at,
+                message.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_outbox_message(&self, id: &str) -> Result<Option<OutboxMessage>> {
+        let mut stmt = self.conn.prepare(&format!("SELECT {} FROM outbox WHERE id = ?1", OUTBOX_COLUMNS))?;
+        let mut rows = stmt.query([id])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row_to_outbox_message(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 发件箱中的邮件，按计划发送时间排列，`account_id` 为 None 时返回全部账户
+    pub fn get_outbox_messages(&self, account_id: Option<i32>) -> Result<Vec<OutboxMessage>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM outbox WHERE (?1 IS NULL OR account_id = ?1) AND status != 'sent'
+             ORDER BY send_at, created_at",
+            OUTBOX_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query([account_id])?;
+        let mut messages = Vec::new();
+        while let Some(row) = rows.next()? {
+            messages.push(row_to_outbox_message(row)?);
+        }
+        Ok(messages)
+    }
+
+    /// 取出一封已到发送时间的邮件并标记为发送中，之后无法撤回
+    pub fn claim_due_outbox_message(&self, now: &str) -> Result<Option<OutboxMessage>> {
+        let id: Option<String> = self.conn.query_row(
+            "SELECT id FROM outbox WHERE status = 'queued' AND next_attempt_at <= ?1
+             ORDER BY next_attempt_at LIMIT 1",
+            [now],
+            |row| row.get(0),
+        ).optional()?;
+
+        let id = match id {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let claimed = self.conn.execute(
+            "UPDATE outbox SET status = 'sending', updated_at = ?2 WHERE id = ?1 AND status = 'queued'",
+            params![id, now],
+        )?;
+        if claimed == 0 {
+            return Ok(None);
+        }
+        self.get_outbox_message(&id)
+    }
+
+    /// 最早的待发送时间，发件箱中没有等待发送的邮件时为 None
+    pub fn next_outbox_attempt(&self) -> Result<Option<String>> {
+        let next = self.conn.query_row(
+            "SELECT MIN(next_attempt_at) FROM outbox WHERE status = 'queued'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(next)
+    }
+
+    /// 临时失败，等待下次重试
+    pub fn reschedule_outbox_message(&self, id: &str, next_attempt_at: &str, error: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE outbox SET status = 'queued', next_attempt_at = ?2, attempts = attempts + 1,
+                               last_error = ?3, updated_at = ?4
+             WHERE id = ?1",
+            params![id, next_attempt_at, error, outbox_time(chrono::Utc::now())],
+        )?;
+        Ok(())
+    }
+
+    /// 永久失败，保留在发件箱中等待手动重试或撤回
+    pub fn fail_outbox_message(&self, id: &str, error: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE outbox SET status = 'failed', attempts = attempts + 1, last_error = ?2, updated_at = ?3
+             WHERE id = ?1",
+            params![id, error, outbox_time(chrono::Utc::now())],
+        )?;
+        Ok(())
+    }
+
+    /// 重新发送失败的邮件，返回是否存在失败的该邮件
+    pub fn retry_outbox_message(&self, id: &str) -> Result<bool> {
+        let now = outbox_time(chrono::Utc::now());
+        let changed = self.conn.execute(
+            "UPDATE outbox SET status = 'queued', next_attempt_at = ?2, attempts = 0, updated_at = ?2
+             WHERE id = ?1 AND status = 'failed'",
+            params![id, now],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// 从发件箱中取回还没有开始发送的邮件，正在发送时返回 None
+    pub fn recall_outbox_message(&self, id: &str) -> Result<Option<OutboxMessage>> {
+        let message = match self.get_outbox_message(id)? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        let deleted = self.conn.execute(
+            "DELETE FROM outbox WHERE id = ?1 AND status IN ('queued', 'failed')",
+            [id],
+        )?;
+        Ok(if deleted > 0 { Some(message) } else { None })
+    }
+
+    pub fn delete_outbox_message(&self, id: &str) -> Result<()> {
+        self.conn.execute("DELETE FROM outbox WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// 删除已发送邮件失败时改为标记已发送，之后不会再被取出或放回队列
+    pub fn mark_outbox_sent(&self, id: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE outbox SET status = 'sent', updated_at = ?2 WHERE id = ?1",
+            params![id, outbox_time(chrono::Utc::now())],
+        )?;
+        Ok(())
+    }
+
+    /// 启动时删除已发送的邮件，并把上次退出前正在发送的邮件放回队列
+    ///
+    /// 退出时 SMTP 可能已经接受了邮件，重新发送使用相同的 Message-ID。
+    pub fn requeue_interrupted_outbox(&self) -> Result<usize> {
+        self.conn.execute("DELETE FROM outbox WHERE status = 'sent'", [])?;
+        let changed = self.conn.execute(
+            "UPDATE outbox SET status = 'queued' WHERE status = 'sending'",
+            [],
+        )?;
+        Ok(changed)
+    }
+}
+
+fn row_to_outbox_message(row: &Row) -> Result<OutboxMessage> {
+    let message: String = row.get("message")?;
+    let status: String = row.get("status")?;
+    Ok(OutboxMessage {
+        id: row.get("id")?,
+        account_id: row.get("account_id")?,
+        message: serde_json::from_str(&message)?,
+        message_id: row.get("message_id")?,
+        source_email_id: row.get("source_email_id")?,
+        draft_id: row.get("draft_id")?,
+        status: OutboxStatus::parse(&status).unwrap_or(OutboxStatus::Queued),
+        send_at: row.get("send_at")?,
+        next_attempt_at: row.get("next_attempt_at")?,
+        attempts: row.get("attempts")?,
+        last_error: row.get("last_error")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::outgoing::OutgoingMessage;
+
+    fn queued(id: &str, account_id: i32, send_at: &str) -> OutboxMessage {
+        OutboxMessage {
+            id: id.to_string(),
+            account_id,
+            message: OutgoingMessage { subject: id.to_string(), ..Default::default() },
+            message_id: format!("{}@example.com", id),
+            source_email_id: None,
+            draft_id: None,
+            status: OutboxStatus::Queued,
+            send_at: send_at.to_string(),
+            next_attempt_at: send_at.to_string(),
+            attempts: 0,
+            last_error: None,
+            created_at: send_at.to_string(),
+            updated_at: send_at.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_claim_and_recall() {
+        let db = Database::new(":memory:").unwrap();
+        db.conn.execute(
+            "INSERT INTO email_accounts (id, provider_id, email_address, display_name, username, password, created_at)
+             VALUES (1, 1, 'me@example.com', '', 'me', '', '')",
+            [],
+        ).unwrap();
+
+        db.insert_outbox_message(&queued("later", 1, "2030-01-01T00:00:10Z")).unwrap();
+        db.insert_outbox_message(&queued("now", 1, "2030-01-01T00:00:00Z")).unwrap();
+
+        // 撤回窗口内还没到发送时间
+        assert!(db.claim_due_outbox_message("2029-12-31T23:59:59Z").unwrap().is_none());
+        assert_eq!(db.next_outbox_attempt().unwrap().as_deref(), Some("2030-01-01T00:00:00Z"));
+
+        let claimed = db.claim_due_outbox_message("2030-01-01T00:00:05Z").unwrap().unwrap();
+        assert_eq!(claimed.id, "now");
+        assert_eq!(claimed.status, OutboxStatus::Sending);
+
+        // 正在发送的邮件无法撤回，等待中的可以
+        assert!(db.recall_outbox_message("now").unwrap().is_none());
+        assert_eq!(db.recall_outbox_message("later").unwrap().unwrap().message.subject, "later");
+
+        db.fail_outbox_message("now", "550 mailbox unavailable").unwrap();
+        assert!(db.retry_outbox_message("now").unwrap());
+        assert_eq!(db.get_outbox_messages(Some(1)).unwrap()[0].status, OutboxStatus::Queued);
+    }
+
+    #[test]
+    fn test_sent_message_is_never_requeued() {
+        let db = Database::new(":memory:").unwrap();
+        db.conn.execute(
+            "INSERT INTO email_accounts (id, provider_id, email_address, display_name, username, password, created_at)
+             VALUES (1, 1, 'me@example.com', '', 'me', '', '')",
+            [],
+        ).unwrap();
+
+        db.insert_outbox_message(&queued("sent", 1, "2030-01-01T00:00:00Z")).unwrap();
+        db.insert_outbox_message(&queued("interrupted", 1, "2030-01-01T00:00:00Z")).unwrap();
+        db.claim_due_outbox_message("2030-01-01T00:00:05Z").unwrap().unwrap();
+        db.claim_due_outbox_message("2030-01-01T00:00:05Z").unwrap().unwrap();
+        db.mark_outbox_sent("sent").unwrap();
+        assert_eq!(db.get_outbox_messages(None).unwrap().len(), 1);
+        assert!(!db.retry_outbox_message("sent").unwrap());
+
+        // 重新启动后只有未发出的邮件回到队列
+        assert_eq!(db.requeue_interrupted_outbox().unwrap(), 1);
+        assert!(db.get_outbox_message("sent").unwrap().is_none());
+        assert_eq!(db.claim_due_outbox_message("2030-01-01T00:00:05Z").unwrap().unwrap().id, "interrupted");
+    }
+}
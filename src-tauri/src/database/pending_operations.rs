@@ -0,0 +1,88 @@
+use anyhow::Result;
+use rusqlite::params;
+use crate::database::connection::Database;
+use crate::models::email::Email;
+use crate::models::pending_operation::PendingOperation;
+
+impl Database {
+    /// 记录一次标记修改，同一封邮件同一标记只保留最新的值
+    ///
+    /// 本地创建的邮件没有对应的服务器位置，直接忽略。
+    pub fn queue_flag_change(&self, email: &Email, flag: &str, value: bool) -> Result<()> {
+        let (account_id, folder, uid) = match (email.account_id, &email.folder, email.uid) {
+            (Some(account_id), Some(folder), Some(uid)) => (account_id, folder, uid),
+            _ => return Ok(()),
+        };
+
+        self.conn.execute(
+            "INSERT INTO pending_operations (account_id, folder, uid, flag, value, attempts, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6)
+             ON CONFLICT(account_id, folder, uid, flag) DO UPDATE SET
+                value = excluded.value,
+                attempts = 0,
+                last_error = NULL,
+                created_at = excluded.created_at",
+            params![
+                account_id,
+                folder,
+                uid,
+                flag,
+                value,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_pending_operations(&self, account_id: i32) -> Result<Vec<PendingOperation>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, account_id, folder, uid, flag, value, attempts, last_error, created_at
+             FROM pending_operations WHERE account_id = ?1 ORDER BY id"
+        )?;
+
+        let operation_iter = stmt.query_map([account_id], |row| {
+            Ok(PendingOperation {
+                id: row.get(0)?,
+                account_id: row.get(1)?,
+                folder: row.get(2)?,
+                uid: row.get(3)?,
+                flag: row.get(4)?,
+                value: row.get(5)?,
+                attempts: row.get(6)?,
+                last_error: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })?;
+
+        let mut operations = Vec::new();
+        for operation in operation_iter {
+            operations.push(operation?);
+        }
+        Ok(operations)
+    }
+
+    /// 删除已推送的操作；推送期间又被修改过的操作（created_at 变化）会保留
+    pub fn complete_pending_operations(&self, operations: &[PendingOperation]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        for operation in operations {
+            self.conn.execute(
+                "DELETE FROM pending_operations WHERE id = ?1 AND created_at = ?2",
+                params![operation.id, operation.created_at],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 记录推送失败，操作保留在队列中等待下次重放
+    pub fn record_pending_failure(&self, account_id: i32, error: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE pending_operations SET attempts = attempts + 1, last_error = ?2
+             WHERE account_id = ?1",
+            params![account_id, error],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension};
+use crate::database::connection::Database;
+
+impl Database {
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        let value = self.conn.query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            [key],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(value)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO app_settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            params![key, value, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+}
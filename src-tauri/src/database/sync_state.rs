@@ -0,0 +1,106 @@
+use anyhow::Result;
+use rusqlite::params;
+use std::collections::HashMap;
+use crate::database::connection::Database;
+use crate::models::sync_state::FolderSyncState;
+use crate::services::imap_response::FlagUpdate;
+
+impl Database {
+    /// 获取账户下所有文件夹的同步进度，以文件夹名为键
+    pub fn get_sync_states(&self, account_id: i32) -> Result<HashMap<String, FolderSyncState>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT account_id, folder, uid_validity, last_uid, highest_modseq, updated_at
+             FROM sync_states WHERE account_id = ?1"
+        )?;
+
+        let state_iter = stmt.query_map([account_id], |row| {
+            Ok(FolderSyncState {
+                account_id: row.get(0)?,
+                folder: row.get(1)?,
+                uid_validity: row.get(2)?,
+                last_uid: row.get(3)?,
+                highest_modseq: row.get::<_, i64>(4)? as u64,
+                updated_at: row.get(5)?,
+            })
+        })?;
+
+        let mut states = HashMap::new();
+        for state in state_iter {
+            let state = state?;
+            states.insert(state.folder.clone(), state);
+        }
+        Ok(states)
+    }
+
+    pub fn save_sync_state(&self, state: &FolderSyncState) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO sync_states (account_id, folder, uid_validity, last_uid, highest_modseq, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(account_id, folder) DO UPDATE SET
+                uid_validity = excluded.uid_validity,
+                last_uid = excluded.last_uid,
+                highest_modseq = excluded.highest_modseq,
+                updated_at = excluded.updated_at",
+            params![
+                state.account_id,
+                state.folder,
+                state.uid_validity,
+                state.last_uid,
+                state.highest_modseq as i64,
+                state.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 清空某个文件夹的本地缓存（UIDVALIDITY 变化时调用）
+    pub fn clear_folder_emails(&self, account_id: i32, folder: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM emails WHERE account_id = ?1 AND folder = ?2",
+            params![account_id, folder],
+        )?;
+        // 旧的 UID 已失效，对应的离线标记修改也无法再推送
+        self.conn.execute(
+            "DELETE FROM pending_operations WHERE account_id = ?1 AND folder = ?2",
+            params![account_id, folder],
+        )?;
+        Ok(())
+    }
+
+    /// 用服务器上的标记更新本地已读、重要状态，尚未推送的本地修改优先
+    pub fn apply_flag_updates(&self, account_id: i32, folder: &str, updates: &[FlagUpdate]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let now = chrono::Utc::now().to_rfc3339();
+
+        for update in updates {
+            self.conn.execute(
+                "UPDATE emails SET is_read = ?1, is_important = ?2, updated_at = ?3
+                 WHERE account_id = ?4 AND folder = ?5 AND uid = ?6
+                   AND (is_read != ?1 OR is_important != ?2)
+                   AND NOT EXISTS (
+                       SELECT 1 FROM pending_operations p
+                       WHERE p.account_id = ?4 AND p.folder = ?5 AND p.uid = ?6
+                   )",
+                params![update.is_read, update.is_important, now, account_id, folder, update.uid],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 删除服务器上已经不存在的邮件，`ranges` 为 UID 闭区间
+    pub fn delete_vanished_emails(&self, account_id: i32, folder: &str, ranges: &[(u32, u32)]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        for (start, end) in ranges {
+            self.conn.execute(
+                "DELETE FROM emails WHERE account_id = ?1 AND folder = ?2 AND uid BETWEEN ?3 AND ?4",
+                params![account_id, folder, start, end],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::Result;
+use rusqlite::{params, params_from_iter, Connection, Row, ToSql};
+use crate::database::connection::{Database, EMAIL_COLUMNS};
+use crate::models::email::Email;
+use crate::models::thread::{EmailThread, ThreadAssignment, ThreadingHeaders};
+
+/// 计算会话使用的列，与 `row_to_headers` 对应
+const HEADER_COLUMNS: &str = "id, message_id, in_reply_to, message_references, subject,
+    COALESCE(sent_at, created_at) AS date, gmail_thread_id, thread_id";
+
+impl Database {
+    /// 读取账户全部邮件的会话相关头部，`account_id` 为 None 时读取本地创建的邮件
+    pub fn get_threading_headers(&self, account_id: Option<i32>) -> Result<Vec<ThreadingHeaders>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM emails WHERE account_id IS ?1",
+            HEADER_COLUMNS
+        ))?;
+
+        let headers = stmt
+            .query_map([account_id], row_to_headers)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(headers)
+    }
+
+    /// 读取指定邮件的会话相关头部
+    pub fn get_threading_headers_by_ids(&self, account_id: Option<i32>, email_ids: &[String]) -> Result<Vec<ThreadingHeaders>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM emails WHERE account_id IS ? AND id IN ({})",
+            HEADER_COLUMNS,
+            placeholders(email_ids.len())
+        ))?;
+
+        let params = std::iter::once(&account_id as &dyn ToSql)
+            .chain(email_ids.iter().map(|id| id as &dyn ToSql));
+        let headers = stmt
+            .query_map(params_from_iter(params), row_to_headers)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(headers)
+    }
+
+    /// 读取可能与 `messages` 属于同一会话的已有会话中的全部邮件（不含 `messages` 本身）
+    ///
+    /// 相关的会话包括：含有被引用邮件的、含有引用这些邮件（References 或 In-Reply-To）的、
+    /// Gmail 会话 ID 相同的，以及含有主题键（见 `threading::normalize_subject`）在 `subjects` 中的邮件的。
+    pub fn get_related_threading_headers(
+        &self,
+        account_id: Option<i32>,
+        messages: &[ThreadingHeaders],
+        subjects: &[String],
+    ) -> Result<Vec<ThreadingHeaders>> {
+        let referenced: Vec<&String> = messages
+            .iter()
+            .flat_map(|m| m.references.iter().chain(&m.in_reply_to))
+            .collect();
+        let message_ids: Vec<&String> = messages.iter().filter_map(|m| m.message_id.as_ref()).collect();
+        let gmail_thread_ids: Vec<&String> = messages.iter().filter_map(|m| m.gmail_thread_id.as_ref()).collect();
+        let email_ids: Vec<&String> = messages.iter().map(|m| &m.email_id).collect();
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM emails
+             WHERE account_id IS ?1 AND id NOT IN ({}) AND thread_id IN (
+                 SELECT thread_id FROM emails
+                 WHERE account_id IS ?1 AND thread_id IS NOT NULL
+                   AND (message_id IN ({}) OR gmail_thread_id IN ({})
+                        OR id IN (SELECT email_id FROM email_references WHERE message_id IN ({})))
+                 UNION
+                 SELECT s.thread_id FROM thread_subjects s JOIN threads t ON t.id = s.thread_id
+                 WHERE t.account_id IS ?1 AND s.subject_key IN ({})
+             )",
+            HEADER_COLUMNS,
+            placeholders(email_ids.len()),
+            placeholders(referenced.len()),
+            placeholders(gmail_thread_ids.len()),
+            placeholders(message_ids.len()),
+            placeholders(subjects.len())
+        ))?;
+
+        let params = std::iter::once(&account_id as &dyn ToSql)
+            .chain(email_ids.into_iter().map(|v| v as &dyn ToSql))
+            .chain(referenced.into_iter().map(|v| v as &dyn ToSql))
+            .chain(gmail_thread_ids.into_iter().map(|v| v as &dyn ToSql))
+            .chain(message_ids.into_iter().map(|v| v as &dyn ToSql))
+            .chain(subjects.iter().map(|v| v as &dyn ToSql));
+        let headers = stmt
+            .query_map(params_from_iter(params), row_to_headers)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(headers)
+    }
+
+    /// 保存邮件引用的 Message-ID（References 和 In-Reply-To），用于查找先于原邮件到达的回复
+    pub fn save_references(&self, email: &Email) -> Result<()> {
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT OR IGNORE INTO email_references (email_id, message_id) VALUES (?1, ?2)",
+        )?;
+        for message_id in email.references.iter().chain(&email.in_reply_to) {
+            stmt.execute(params![email.id, message_id])?;
+        }
+        Ok(())
+    }
+
+    /// 替换账户的全部会话，并更新邮件所属的会话
+    pub fn replace_threads(&self, account_id: Option<i32>, threads: &[ThreadAssignment]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM threads WHERE account_id IS ?1", [account_id])?;
+        insert_threads(&tx, account_id, threads)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 删除 `replaced` 中的会话，保存重新分组后的会话并更新邮件所属的会话
+    pub fn update_threads(&self, account_id: Option<i32>, replaced: &[String], threads: &[ThreadAssignment]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached("DELETE FROM threads WHERE id = ?1")?;
+            for thread_id in replaced {
+                stmt.execute([thread_id])?;
+            }
+        }
+        insert_threads(&tx, account_id, threads)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 有邮件尚未计算会话的账户
+    pub fn get_unthreaded_accounts(&self) -> Result<Vec<Option<i32>>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT account_id FROM emails WHERE thread_id IS NULL",
+        )?;
+        let accounts = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(accounts)
+    }
+
+    /// 会话列表，按最新邮件的时间倒序排列
+    pub fn get_threads(&self, account_id: Option<i32>) -> Result<Vec<EmailThread>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.id, t.account_id, t.subject,
+                    COUNT(e.id) AS message_count,
+                    SUM(CASE WHEN e.is_read = 0 THEN 1 ELSE 0 END) AS unread_count,
+                    MAX(e.has_attachment) AS has_attachment,
+                    MAX(COALESCE(e.sent_at, e.created_at)) AS last_message_at,
+                    (SELECT l.sender FROM emails l WHERE l.thread_id = t.id
+                     ORDER BY COALESCE(l.sent_at, l.created_at) DESC LIMIT 1) AS last_sender
+             FROM threads t
+             JOIN emails e ON e.thread_id = t.id
+             WHERE ?1 IS NULL OR t.account_id = ?1
+             GROUP BY t.id
+             ORDER BY last_message_at DESC",
+        )?;
+
+        let threads = stmt
+            .query_map([account_id], |row| {
+                Ok(EmailThread {
+                    id: row.get("id")?,
+                    account_id: row.get("account_id")?,
+                    subject: row.get("subject")?,
+                    message_count: row.get("message_count")?,
+                    unread_count: row.get("unread_count")?,
+                    has_attachment: row.get("has_attachment")?,
+                    last_sender: row.get("last_sender")?,
+                    last_message_at: row.get("last_message_at")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(threads)
+    }
+
+    /// 会话中的全部邮件，按时间顺序排列
+    pub fn get_thread_emails(&self, thread_id: &str) -> Result<Vec<Email>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM emails WHERE thread_id = ?1 ORDER BY COALESCE(sent_at, created_at)",
+            EMAIL_COLUMNS
+        ))?;
+
+        let mut emails = stmt
+            .query_map([thread_id], Self::row_to_email)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        self.load_addresses_batch(&mut emails)?;
+        Ok(emails)
+    }
+}
+
+fn insert_threads(conn: &Connection, account_id: Option<i32>, threads: &[ThreadAssignment]) -> Result<()> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut insert = conn.prepare_cached(
+        "INSERT INTO threads (id, account_id, subject, updated_at) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    let mut insert_subject = conn.prepare_cached(
+        "INSERT INTO thread_subjects (thread_id, subject_key) VALUES (?1, ?2)",
+    )?;
+    let mut update = conn.prepare_cached(
+        "UPDATE emails SET thread_id = ?1 WHERE id = ?2 AND thread_id IS NOT ?1",
+    )?;
+    for thread in threads {
+        insert.execute(params![thread.id, account_id, thread.subject, now])?;
+        for subject_key in &thread.subject_keys {
+            insert_subject.execute(params![thread.id, subject_key])?;
+        }
+        for email_id in &thread.email_ids {
+            update.execute(params![thread.id, email_id])?;
+        }
+    }
+    Ok(())
+}
+
+fn row_to_headers(row: &Row) -> rusqlite::Result<ThreadingHeaders> {
+    let references: Option<String> = row.get("message_references")?;
+    Ok(ThreadingHeaders {
+        email_id: row.get("id")?,
+        message_id: row.get("message_id")?,
+        in_reply_to: row.get("in_reply_to")?,
+        references: references
+            .map(|r| r.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default(),
+        subject: row.get("subject")?,
+        date: row.get("date")?,
+        gmail_thread_id: row.get("gmail_thread_id")?,
+        thread_id: row.get("thread_id")?,
+    })
+}
+
+/// `IN (...)` 的参数占位符，空列表时使用 NULL（不匹配任何值）
+fn placeholders(count: usize) -> String {
+    if count == 0 {
+        return "NULL".to_string();
+    }
+    vec!["?"; count].join(", ")
+}
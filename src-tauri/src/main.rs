@@ -6,18 +6,77 @@ mod models;
 mod services;
 mod database;
 
+use commands::draft::*;
 use commands::email::*;
+use commands::outbox::*;
 use commands::provider::*;
+use commands::security::*;
 use database::connection::Database;
+use services::app_paths::{self, AppPaths};
+use services::blob_store::BlobStore;
+use services::crypto_service::CryptoService;
+use services::idle_service::IdleManager;
+use services::outbox_service::OutboxManager;
+use services::threading;
 use std::sync::Mutex;
+use tauri::Manager;
 
 fn main() {
-    // 初始化数据库
-    let database = Database::new("emails.db")
-        .expect("Failed to initialize database");
+    // 通过 --profile 或 XMAIL_PROFILE 选择配置文件，不同配置文件的数据互相独立
+    let profile = app_paths::selected_profile()
+        .expect("Invalid profile");
 
     tauri::Builder::default()
-        .manage(Mutex::new(database))
+        .manage(IdleManager::new())
+        .manage(OutboxManager::new())
+        .setup(move |app| {
+            let paths = AppPaths::resolve(&app.path().app_data_dir()?, profile)?;
+
+            // 旧版本把数据库和附件放在启动目录中，移动到数据目录
+            if let Ok(cwd) = std::env::current_dir() {
+                if paths.migrate_legacy_files(&cwd)? {
+                    eprintln!("已将 {} 中的数据库移动到 {}", cwd.display(), paths.data_dir().display());
+                }
+            }
+
+            // 初始化数据库
+            let database = Database::new(paths.database())?;
+
+            // 为升级前同步的邮件计算会话
+            if let Err(e) = threading::rethread_unthreaded(&database) {
+                eprintln!("计算邮件会话失败: {}", e);
+            }
+
+            // 加载账户凭据的加密密钥，设置了主密码时需要等待用户解锁
+            let unlocked = CryptoService::initialize(&database, paths.data_dir())?;
+
+            // 附件内容按 SHA-256 保存在数据库旁的 attachments 目录中，
+            // 启动时（开始同步前）删除邮件删除后不再被引用的内容
+            let blobs = BlobStore::new(paths.attachments());
+            if let Err(e) = database.referenced_blobs().and_then(|referenced| blobs.remove_unreferenced(&referenced)) {
+                eprintln!("清理附件存储失败: {}", e);
+            }
+            app.manage(blobs);
+            app.manage(Mutex::new(database));
+
+            // 非默认配置文件在窗口标题中显示名称
+            if let Some(profile) = paths.profile() {
+                if let Some(window) = app.get_webview_window("main") {
+                    window.set_title(&format!("邮件管理系统 - {}", profile))?;
+                }
+            }
+            app.manage(paths);
+
+            // 为已启用的账户启动新邮件监听
+            if unlocked {
+                let handle = app.handle().clone();
+                app.state::<IdleManager>().start_active_accounts(&handle)?;
+            }
+
+            // 发件箱后台发送，凭据解锁前不会发送
+            app.state::<OutboxManager>().start(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             // 邮件相关命令
             get_all_emails,
@@ -27,19 +86,50 @@ fn main() {
             mark_email_as_read,
             mark_email_as_important,
             delete_email,
+            get_email_attachments,
+            save_attachment,
+            open_attachment,
+            create_reply_draft,
+            create_forward_draft,
+            get_threads,
+            get_thread_emails,
+            // 草稿相关命令
+            save_draft,
+            update_draft,
+            discard_draft,
+            get_drafts,
+            get_draft,
+            // 发件箱相关命令
+            queue_email,
+            get_outbox,
+            undo_send,
+            retry_outbox_email,
+            get_undo_send_delay,
+            set_undo_send_delay,
             get_categories,
             get_statistics,
             // 邮件服务商和账户相关命令
             get_email_providers,
             add_email_account,
+            authorize_oauth_account,
             get_email_accounts,
+            update_account_credentials,
             test_email_connection,
+            get_imap_client_id,
+            set_imap_client_id,
             sync_account_emails,
+            send_email,
+            get_email_folders,
             toggle_account_status,
             delete_email_account,
             get_email_categories,
             add_email_category,
-            delete_email_category
+            delete_email_category,
+            // 账户凭据加密相关命令
+            get_encryption_status,
+            unlock_master_password,
+            change_master_password,
+            reset_credential_store
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 地址在邮件中的角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AddressRole {
+    #[serde(rename = "from")]
+    From,
+    #[serde(rename = "to")]
+    To,
+    #[serde(rename = "cc")]
+    Cc,
+    #[serde(rename = "bcc")]
+    Bcc,
+    #[serde(rename = "reply_to")]
+    ReplyTo,
+}
+
+impl AddressRole {
+    /// 收件人过滤匹配的角色
+    pub const RECIPIENTS: [AddressRole; 4] = [
+        AddressRole::To,
+        AddressRole::Cc,
+        AddressRole::Bcc,
+        AddressRole::ReplyTo,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AddressRole::From => "from",
+            AddressRole::To => "to",
+            AddressRole::Cc => "cc",
+            AddressRole::Bcc => "bcc",
+            AddressRole::ReplyTo => "reply_to",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "from" => Some(AddressRole::From),
+            "to" => Some(AddressRole::To),
+            "cc" => Some(AddressRole::Cc),
+            "bcc" => Some(AddressRole::Bcc),
+            "reply_to" => Some(AddressRole::ReplyTo),
+            _ => None,
+        }
+    }
+}
+
+/// 邮件地址，包含可选的显示名称
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailAddress {
+    pub name: Option<String>,
+    pub address: String,
+}
+
+impl EmailAddress {
+    pub fn new(name: Option<String>, address: String) -> Self {
+        Self {
+            name: name.filter(|name| !name.is_empty()),
+            address,
+        }
+    }
+
+    /// 解析 RFC 5322 地址列表，例如 `"Doe, John" <john@example.com>, jane@example.com`
+    ///
+    /// 引号和尖括号内的逗号不作为分隔符，地址组（`team: a@x.com, b@x.com;`）展开为其中的地址，
+    /// 没有 `@` 的项会被忽略。显示名称中的编码字需要由调用方解码。
+    pub fn parse_list(value: &str) -> Vec<Self> {
+        split_list(value)
+            .into_iter()
+            .filter_map(|item| Self::parse_one(&item))
+            .collect()
+    }
+
+    fn parse_one(item: &str) -> Option<Self> {
+        let item = strip_group_prefix(item.trim()).trim().trim_end_matches(';').trim();
+
+        let (name, address) = match (item.rfind('<'), item.rfind('>')) {
+            (Some(start), Some(end)) if start < end => {
+                (unquote(item[..start].trim()), item[start + 1..end].trim().to_string())
+            }
+            _ => {
+                // `john@example.com (John Doe)` 形式，注释作为显示名称
+                match (item.find('('), item.rfind(')')) {
+                    (Some(start), Some(end)) if start < end => (
+                        item[start + 1..end].trim().to_string(),
+                        format!("{}{}", &item[..start], &item[end + 1..]).trim().to_string(),
+                    ),
+                    _ => (String::new(), item.to_string()),
+                }
+            }
+        };
+
+        if !address.contains('@') || address.contains(char::is_whitespace) {
+            return None;
+        }
+        Some(Self::new(Some(name), address))
+    }
+}
+
+impl fmt::Display for EmailAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) if name.contains(|c: char| ",;:<>@\"".contains(c)) => {
+                write!(f, "\"{}\" <{}>", name.replace('\\', "\\\\").replace('"', "\\\""), self.address)
+            }
+            Some(name) => write!(f, "{} <{}>", name, self.address),
+            None => write!(f, "{}", self.address),
+        }
+    }
+}
+
+/// 将地址列表格式化为以逗号分隔的字符串
+pub fn format_list(addresses: &[EmailAddress]) -> String {
+    addresses.iter().map(|address| address.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// 按逗号拆分地址列表，跳过引号、尖括号和注释中的逗号
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut angle = 0;
+    let mut comment = 0;
+
+    for c in value.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => angle += 1,
+            '>' if !in_quotes && angle > 0 => angle -= 1,
+            '(' if !in_quotes => comment += 1,
+            ')' if !in_quotes && comment > 0 => comment -= 1,
+            ',' if !in_quotes && angle == 0 && comment == 0 => {
+                items.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    items.push(current);
+
+    items
+}
+
+/// 去掉地址组的组名部分（引号和尖括号外的第一个冒号之前）
+fn strip_group_prefix(item: &str) -> &str {
+    let mut in_quotes = false;
+    for (index, c) in item.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => return item,
+            ':' if !in_quotes => return &item[index + 1..],
+            _ => {}
+        }
+    }
+    item
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => inner.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address_list() {
+        let addresses = EmailAddress::parse_list(
+            "\"Doe, John\" <john@example.com>, jane@example.com, 王五 <wangwu@example.cn>, \
+             bob@example.com (Bob), team: a@example.com, b@example.com;, undisclosed-recipients:;",
+        );
+
+        let expected = [
+            (Some("Doe, John"), "john@example.com"),
+            (None, "jane@example.com"),
+            (Some("王五"), "wangwu@example.cn"),
+            (Some("Bob"), "bob@example.com"),
+            (None, "a@example.com"),
+            (None, "b@example.com"),
+        ];
+        assert_eq!(addresses.len(), expected.len());
+        for (address, (name, email)) in addresses.iter().zip(expected) {
+            assert_eq!(address.name.as_deref(), name);
+            assert_eq!(address.address, email);
+        }
+
+        assert_eq!(addresses[0].to_string(), "\"Doe, John\" <john@example.com>");
+        assert_eq!(format_list(&addresses[1..3]), "jane@example.com, 王五 <wangwu@example.cn>");
+    }
+}
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// 邮件附件的元数据，内容按 SHA-256 保存在本地附件存储中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: i64,
+    pub email_id: String,
+    pub filename: String,
+    pub mime_type: String,
+    /// 解码后的字节数
+    pub size: i64,
+    /// Content-ID（不含尖括号），HTML 正文中通过 `cid:` 引用
+    pub content_id: Option<String>,
+    /// 内嵌资源（例如签名图片），不计入 `has_attachment`
+    pub is_inline: bool,
+    /// 内容的 SHA-256（十六进制）
+    pub sha256: String,
+    pub created_at: String,
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+use crate::models::outgoing::OutgoingMessage;
+
+/// 本地保存的草稿
+///
+/// 每次保存 `version` 加一；账户有草稿箱文件夹时，草稿同时上传到服务器，
+/// 服务器上的副本通过 `message_id` 识别，重新保存时替换旧副本。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Draft {
+    pub id: String,
+    /// 发件账户，为 None 时只保存在本地
+    pub account_id: Option<i32>,
+    /// 回复或转发的原邮件
+    pub source_email_id: Option<String>,
+    pub message: OutgoingMessage,
+    /// 保存次数，更新时需要提供编辑所基于的版本
+    pub version: i64,
+    /// 草稿的 Message-ID（不含尖括号），各版本保持不变
+    pub message_id: String,
+    /// 服务器副本所在的文件夹
+    pub server_folder: Option<String>,
+    /// 已上传到服务器的版本，从未上传时为 None
+    pub server_version: Option<i64>,
+    /// 最近一次上传失败的原因
+    pub server_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Draft {
+    pub fn new(account_id: Option<i32>, source_email_id: Option<String>, message: OutgoingMessage, message_id: String) -> Self {
+        let now = chrono::Utc::now().to_rfc3339();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            account_id,
+            source_email_id,
+            message,
+            version: 1,
+            message_id,
+            server_folder: None,
+            server_version: None,
+            server_error: None,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    /// 服务器上的副本是否为最新版本
+    pub fn is_uploaded(&self) -> bool {
+        self.server_version == Some(self.version)
+    }
+}
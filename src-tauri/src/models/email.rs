@@ -1,16 +1,62 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use crate::models::address::{format_list, EmailAddress};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Email {
     pub id: String,
+    /// 发件人，格式为 `名称 <地址>`，用于列表显示
     pub sender: String,
+    /// 收件人列表，以逗号分隔，用于列表显示
     pub recipient: String,
     pub subject: String,
     pub body: String,
+    /// HTML 正文（如果邮件包含 text/html 部分）
+    pub html_body: Option<String>,
     pub category: String,
     pub is_read: bool,
     pub is_important: bool,
+    /// 所属邮件账户，本地创建的邮件为空
+    pub account_id: Option<i32>,
+    /// 服务器上的文件夹名称
+    pub folder: Option<String>,
+    /// 服务器文件夹中的 UID
+    pub uid: Option<u32>,
+    /// Message-ID 头部（不含尖括号）
+    pub message_id: Option<String>,
+    /// In-Reply-To 头部中的 Message-ID
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+    /// References 头部中的 Message-ID 列表，从最早的祖先开始
+    #[serde(default)]
+    pub references: Vec<String>,
+    /// 所属会话
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    /// Gmail 的会话 ID（X-GM-THRID）
+    #[serde(default)]
+    pub gmail_thread_id: Option<String>,
+    /// 是否包含附件（不计内嵌图片）
+    #[serde(default)]
+    pub has_attachment: bool,
+    #[serde(default)]
+    pub from: Option<EmailAddress>,
+    #[serde(default)]
+    pub to: Vec<EmailAddress>,
+    #[serde(default)]
+    pub cc: Vec<EmailAddress>,
+    /// 只有本地发送的邮件才有密送地址
+    #[serde(default)]
+    pub bcc: Vec<EmailAddress>,
+    #[serde(default)]
+    pub reply_to: Vec<EmailAddress>,
+    /// 邮件头部的 Date，缺失或无法解析时使用服务器收到的时间
+    #[serde(default)]
+    pub sent_at: Option<DateTime<Utc>>,
+    /// 服务器收到邮件的时间（IMAP INTERNALDATE）
+    #[serde(default)]
+    pub received_at: Option<DateTime<Utc>>,
+    /// 本地记录创建时间
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -30,14 +76,60 @@ impl Email {
             recipient,
             subject,
             body,
+            html_body: None,
             category,
             is_read: false,
             is_important: false,
+            account_id: None,
+            folder: None,
+            uid: None,
+            message_id: None,
+            in_reply_to: None,
+            references: Vec::new(),
+            thread_id: None,
+            gmail_thread_id: None,
+            has_attachment: false,
+            from: None,
+            to: Vec::new(),
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            reply_to: Vec::new(),
+            sent_at: None,
+            received_at: None,
             created_at: now,
             updated_at: now,
         }
     }
 
+    /// 设置结构化地址，并据此更新用于显示的 `sender` 和 `recipient`
+    pub fn set_addresses(
+        &mut self,
+        from: Option<EmailAddress>,
+        to: Vec<EmailAddress>,
+        cc: Vec<EmailAddress>,
+        bcc: Vec<EmailAddress>,
+        reply_to: Vec<EmailAddress>,
+    ) {
+        if let Some(from) = &from {
+            self.sender = from.to_string();
+        }
+        // 只有抄送时显示抄送地址
+        let shown = if to.is_empty() { &cc } else { &to };
+        if !shown.is_empty() {
+            self.recipient = format_list(shown);
+        }
+        self.from = from;
+        self.to = to;
+        self.cc = cc;
+        self.bcc = bcc;
+        self.reply_to = reply_to;
+    }
+
+    /// 所有收件人角色（收件人、抄送、密送、回复地址）的地址
+    pub fn recipient_addresses(&self) -> impl Iterator<Item = &EmailAddress> {
+        self.to.iter().chain(&self.cc).chain(&self.bcc).chain(&self.reply_to)
+    }
+
     pub fn mark_as_read(&mut self) {
         self.is_read = true;
         self.updated_at = Utc::now();
@@ -55,13 +147,41 @@ impl Email {
     }
 }
 
+/// 邮件摘要，用于新邮件通知等不需要正文的场景
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailSummary {
+    pub id: String,
+    pub account_id: Option<i32>,
+    pub folder: Option<String>,
+    pub sender: String,
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<&Email> for EmailSummary {
+    fn from(email: &Email) -> Self {
+        Self {
+            id: email.id.clone(),
+            account_id: email.account_id,
+            folder: email.folder.clone(),
+            sender: email.sender.clone(),
+            subject: email.subject.clone(),
+            created_at: email.created_at,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailFilter {
     pub sender: Option<String>,
     pub recipient: Option<String>,
     pub category: Option<String>,
+    pub account_id: Option<i32>,
+    /// 服务器上的文件夹名称，需与 `account_id` 一起使用
+    pub folder: Option<String>,
     pub is_read: Option<bool>,
     pub is_important: Option<bool>,
+    pub has_attachment: Option<bool>,
     pub keyword: Option<String>,
 }
 
@@ -71,8 +191,11 @@ impl Default for EmailFilter {
             sender: None,
             recipient: None,
             category: None,
+            account_id: None,
+            folder: None,
             is_read: None,
             is_important: None,
+            has_attachment: None,
             keyword: None,
         }
     }
@@ -88,6 +211,12 @@ impl EmailFilter {
         self
     }
 
+    pub fn folder(mut self, account_id: i32, folder: String) -> Self {
+        self.account_id = Some(account_id);
+        self.folder = Some(folder);
+        self
+    }
+
     pub fn keyword(mut self, keyword: String) -> Self {
         self.keyword = Some(keyword);
         self
@@ -103,6 +232,11 @@ impl EmailFilter {
         self
     }
 
+    pub fn with_attachments(mut self) -> Self {
+        self.has_attachment = Some(true);
+        self
+    }
+
     #[allow(dead_code)]
     pub fn matches(&self, email: &Email) -> bool {
         // 发件人过滤
@@ -112,9 +246,14 @@ impl EmailFilter {
             }
         }
 
-        // 收件人过滤
+        // 收件人过滤，匹配收件人、抄送、密送和回复地址中的任意一个
         if let Some(recipient) = &self.recipient {
-            if !email.recipient.contains(recipient) {
+            let matched = email.recipient.contains(recipient)
+                || email.recipient_addresses().any(|address| {
+                    address.address.contains(recipient)
+                        || address.name.as_deref().is_some_and(|name| name.contains(recipient))
+                });
+            if !matched {
                 return false;
             }
         }
@@ -126,6 +265,14 @@ impl EmailFilter {
             }
         }
 
+        // 账户和文件夹过滤
+        if self.account_id.is_some() && email.account_id != self.account_id {
+            return false;
+        }
+        if self.folder.is_some() && email.folder != self.folder {
+            return false;
+        }
+
         // 已读状态过滤
         if let Some(is_read) = self.is_read {
             if email.is_read != is_read {
@@ -140,6 +287,13 @@ impl EmailFilter {
             }
         }
 
+        // 附件过滤
+        if let Some(has_attachment) = self.has_attachment {
+            if email.has_attachment != has_attachment {
+                return false;
+            }
+        }
+
         // 关键词过滤
         if let Some(keyword) = &self.keyword {
             let keyword_lower = keyword.to_lowercase();
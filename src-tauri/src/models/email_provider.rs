@@ -1,5 +1,67 @@
 use serde::{Deserialize, Serialize};
 
+/// 连接加密方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SecurityMode {
+    /// 建立连接后立即进行 TLS 握手（IMAP 993，SMTP 465/994）
+    #[serde(rename = "implicit_tls")]
+    ImplicitTls,
+    /// 先建立明文连接，再通过 STARTTLS 升级（IMAP 143，SMTP 587）
+    #[serde(rename = "starttls")]
+    StartTls,
+    /// 不加密，仅用于本地测试服务器
+    #[serde(rename = "none")]
+    None,
+}
+
+impl SecurityMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecurityMode::ImplicitTls => "implicit_tls",
+            SecurityMode::StartTls => "starttls",
+            SecurityMode::None => "none",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "implicit_tls" => Some(SecurityMode::ImplicitTls),
+            "starttls" => Some(SecurityMode::StartTls),
+            "none" => Some(SecurityMode::None),
+            _ => None,
+        }
+    }
+}
+
+/// 账户登录方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AuthMethod {
+    /// 密码或授权码
+    #[default]
+    #[serde(rename = "password")]
+    Password,
+    /// OAuth2 访问令牌，通过 XOAUTH2 SASL 登录
+    #[serde(rename = "oauth2")]
+    OAuth2,
+}
+
+impl AuthMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMethod::Password => "password",
+            AuthMethod::OAuth2 => "oauth2",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "password" => Some(AuthMethod::Password),
+            "oauth2" => Some(AuthMethod::OAuth2),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailProvider {
     pub id: i32,
@@ -9,8 +71,11 @@ pub struct EmailProvider {
     pub imap_port: u16,
     pub smtp_server: String,
     pub smtp_port: u16,
-    pub use_ssl: bool,
-    pub use_tls: bool,
+    pub imap_security: SecurityMode,
+    pub smtp_security: SecurityMode,
+    /// 服务器要求登录后发送 IMAP ID 命令（RFC 2971），即使未在 CAPABILITY 中声明
+    #[serde(default)]
+    pub requires_imap_id: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,12 +85,46 @@ pub struct EmailAccount {
     pub email_address: String,
     pub display_name: String,
     pub username: String,
-    pub password: String, // 应该加密存储
+    /// 加密后的密码，只在连接服务器时解密，不会序列化到前端
+    #[serde(skip_serializing, default)]
+    pub password: String,
+    #[serde(default)]
+    pub auth_method: AuthMethod,
+    pub is_active: bool,
+    pub last_sync: Option<String>,
+    pub created_at: String,
+}
+
+/// 返回给前端的账户信息，不包含密码等凭据
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailAccountInfo {
+    pub id: i32,
+    pub provider_id: i32,
+    pub email_address: String,
+    pub display_name: String,
+    pub username: String,
+    pub auth_method: AuthMethod,
     pub is_active: bool,
     pub last_sync: Option<String>,
     pub created_at: String,
 }
 
+impl From<&EmailAccount> for EmailAccountInfo {
+    fn from(account: &EmailAccount) -> Self {
+        Self {
+            id: account.id,
+            provider_id: account.provider_id,
+            email_address: account.email_address.clone(),
+            display_name: account.display_name.clone(),
+            username: account.username.clone(),
+            auth_method: account.auth_method,
+            is_active: account.is_active,
+            last_sync: account.last_sync.clone(),
+            created_at: account.created_at.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailCategory {
     pub id: i32,
@@ -36,6 +135,23 @@ pub struct EmailCategory {
     pub created_at: String,
 }
 
+impl EmailProvider {
+    /// 服务器是否会自动把通过 SMTP 发出的邮件保存到已发送文件夹（Gmail、Outlook）
+    ///
+    /// 这类服务商不需要再 APPEND 副本，否则已发送文件夹中会出现重复的邮件。
+    /// 自定义服务商按 SMTP 服务器的域名判断，例如使用 Google Workspace 或 Microsoft 365 的企业邮箱。
+    pub fn saves_sent_copy(&self) -> bool {
+        if matches!(self.provider_type.as_str(), "gmail" | "outlook") {
+            return true;
+        }
+
+        let server = self.smtp_server.to_lowercase();
+        ["gmail.com", "googlemail.com", "outlook.com", "office365.com", "hotmail.com", "live.com"]
+            .iter()
+            .any(|domain| server == *domain || server.ends_with(&format!(".{}", domain)))
+    }
+}
+
 // 预设的邮件服务商配置
 impl EmailProvider {
     pub fn get_predefined_providers() -> Vec<EmailProvider> {
@@ -48,8 +164,9 @@ impl EmailProvider {
                 imap_port: 993,
                 smtp_server: "smtp.gmail.com".to_string(),
                 smtp_port: 587,
-                use_ssl: true,
-                use_tls: true,
+                imap_security: SecurityMode::ImplicitTls,
+                smtp_security: SecurityMode::StartTls,
+                requires_imap_id: false,
             },
             EmailProvider {
                 id: 2,
@@ -59,8 +176,9 @@ impl EmailProvider {
                 imap_port: 993,
                 smtp_server: "smtp.qq.com".to_string(),
                 smtp_port: 587,
-                use_ssl: true,
-                use_tls: true,
+                imap_security: SecurityMode::ImplicitTls,
+                smtp_security: SecurityMode::StartTls,
+                requires_imap_id: false,
             },
             EmailProvider {
                 id: 3,
@@ -70,8 +188,9 @@ impl EmailProvider {
                 imap_port: 993,
                 smtp_server: "smtp.163.com".to_string(),
                 smtp_port: 994,
-                use_ssl: true,
-                use_tls: false,
+                imap_security: SecurityMode::ImplicitTls,
+                smtp_security: SecurityMode::ImplicitTls,
+                requires_imap_id: true,
             },
             EmailProvider {
                 id: 4,
@@ -81,8 +200,9 @@ impl EmailProvider {
                 imap_port: 993,
                 smtp_server: "smtp.126.com".to_string(),
                 smtp_port: 994,
-                use_ssl: true,
-                use_tls: false,
+                imap_security: SecurityMode::ImplicitTls,
+                smtp_security: SecurityMode::ImplicitTls,
+                requires_imap_id: true,
             },
             EmailProvider {
                 id: 5,
@@ -92,8 +212,9 @@ impl EmailProvider {
                 imap_port: 993,
                 smtp_server: "smtp-mail.outlook.com".to_string(),
                 smtp_port: 587,
-                use_ssl: true,
-                use_tls: true,
+                imap_security: SecurityMode::ImplicitTls,
+                smtp_security: SecurityMode::StartTls,
+                requires_imap_id: false,
             },
         ]
     }
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+/// 账户密钥的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeySource {
+    /// 应用数据目录中随机生成的密钥文件，启动时自动解锁
+    #[serde(rename = "key_file")]
+    KeyFile,
+    /// 由主密码经 Argon2 派生，每次启动需要输入主密码解锁
+    #[serde(rename = "master_password")]
+    MasterPassword,
+}
+
+impl KeySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeySource::KeyFile => "key_file",
+            KeySource::MasterPassword => "master_password",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "key_file" => Some(KeySource::KeyFile),
+            "master_password" => Some(KeySource::MasterPassword),
+            _ => None,
+        }
+    }
+}
+
+/// 数据库中保存的加密配置，不包含密钥本身
+#[derive(Debug, Clone)]
+pub struct EncryptionConfig {
+    pub key_source: KeySource,
+    /// Argon2 盐值（base64），仅主密码模式使用
+    pub salt: Option<String>,
+    /// 用当前密钥加密的校验值，用于判断主密码或密钥文件是否正确
+    pub verifier: String,
+    pub updated_at: String,
+}
+
+/// 返回给前端的加密状态
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptionStatus {
+    pub key_source: KeySource,
+    /// 密钥是否已加载，主密码模式下启动后需要先解锁
+    pub unlocked: bool,
+    /// 密钥文件丢失或与数据库不匹配，需要重置凭据并重新输入账户密码
+    pub needs_reset: bool,
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+/// RFC 6154 SPECIAL-USE 属性
+pub const SPECIAL_USE_SENT: &str = "\\Sent";
+pub const SPECIAL_USE_DRAFTS: &str = "\\Drafts";
+pub const SPECIAL_USE_TRASH: &str = "\\Trash";
+pub const SPECIAL_USE_JUNK: &str = "\\Junk";
+pub const SPECIAL_USE_ARCHIVE: &str = "\\Archive";
+pub const SPECIAL_USE_ALL: &str = "\\All";
+pub const SPECIAL_USE_FLAGGED: &str = "\\Flagged";
+
+/// 服务器上的邮件文件夹
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MailFolder {
+    pub id: i32,
+    pub account_id: i32,
+    /// 服务器上的原始名称（修改版 UTF-7），IMAP 命令中使用
+    pub name: String,
+    /// 解码后的显示名称
+    pub display_name: String,
+    /// 层级分隔符，例如 `/` 或 `.`
+    pub delimiter: Option<String>,
+    /// SPECIAL-USE 属性，例如 `\Sent`
+    pub special_use: Option<String>,
+    /// 文件夹中邮件对应的分类
+    pub category: String,
+    /// 带有 `\Noselect` 的文件夹只是层级节点，无法选择
+    pub is_selectable: bool,
+    pub updated_at: String,
+}
+
+impl MailFolder {
+    /// 根据 LIST 响应构建文件夹，并确定其特殊用途和分类
+    pub fn from_list(
+        account_id: i32,
+        name: &str,
+        display_name: &str,
+        delimiter: Option<&str>,
+        attributes: &[String],
+    ) -> Self {
+        let is_selectable = !attributes
+            .iter()
+            .any(|attr| attr.eq_ignore_ascii_case("\\Noselect") || attr.eq_ignore_ascii_case("\\NonExistent"));
+
+        let special_use = special_use_from_attributes(attributes)
+            .or_else(|| special_use_from_name(display_name, delimiter))
+            .map(|special_use| special_use.to_string());
+
+        let category = if name.eq_ignore_ascii_case("INBOX") {
+            "收件箱".to_string()
+        } else {
+            special_use_category(special_use.as_deref()).to_string()
+        };
+
+        Self {
+            id: 0,
+            account_id,
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            delimiter: delimiter.map(|d| d.to_string()),
+            special_use,
+            category,
+            is_selectable,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn is_inbox(&self) -> bool {
+        self.name.eq_ignore_ascii_case("INBOX")
+    }
+
+    /// 是否需要同步。`\All`、`\Flagged` 是与其他文件夹内容重复的虚拟文件夹
+    pub fn should_sync(&self) -> bool {
+        self.is_selectable
+            && !matches!(self.special_use.as_deref(), Some(SPECIAL_USE_ALL) | Some(SPECIAL_USE_FLAGGED))
+    }
+}
+
+/// 特殊用途对应的系统分类
+pub fn special_use_category(special_use: Option<&str>) -> &'static str {
+    match special_use {
+        Some(SPECIAL_USE_SENT) => "发件箱",
+        Some(SPECIAL_USE_DRAFTS) => "草稿箱",
+        Some(SPECIAL_USE_TRASH) => "垃圾箱",
+        Some(SPECIAL_USE_JUNK) => "垃圾邮件",
+        Some(SPECIAL_USE_ARCHIVE) => "归档",
+        _ => "其他",
+    }
+}
+
+fn special_use_from_attributes(attributes: &[String]) -> Option<&'static str> {
+    let known = [
+        SPECIAL_USE_SENT,
+        SPECIAL_USE_DRAFTS,
+        SPECIAL_USE_TRASH,
+        SPECIAL_USE_JUNK,
+        SPECIAL_USE_ARCHIVE,
+        SPECIAL_USE_ALL,
+        SPECIAL_USE_FLAGGED,
+    ];
+
+    attributes
+        .iter()
+        .find_map(|attr| known.into_iter().find(|special| attr.eq_ignore_ascii_case(special)))
+}
+
+/// 服务器不支持 SPECIAL-USE 时，根据常见的文件夹名称判断
+fn special_use_from_name(display_name: &str, delimiter: Option<&str>) -> Option<&'static str> {
+    let leaf = match delimiter {
+        Some(delimiter) if !delimiter.is_empty() => display_name.rsplit(delimiter).next().unwrap_or(display_name),
+        _ => display_name,
+    };
+
+    match leaf.trim().to_lowercase().as_str() {
+        "sent" | "sent messages" | "sent items" | "sent mail" | "已发送" | "已发送邮件" => Some(SPECIAL_USE_SENT),
+        "drafts" | "draft" | "草稿箱" | "草稿" => Some(SPECIAL_USE_DRAFTS),
+        "trash" | "deleted messages" | "deleted items" | "已删除" | "已删除邮件" => Some(SPECIAL_USE_TRASH),
+        "junk" | "spam" | "junk e-mail" | "junk email" | "垃圾邮件" => Some(SPECIAL_USE_JUNK),
+        "archive" | "archives" | "归档" => Some(SPECIAL_USE_ARCHIVE),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folder_special_use() {
+        let attrs = |list: &[&str]| list.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        let gmail_sent = MailFolder::from_list(1, "[Gmail]/Sent Mail", "[Gmail]/Sent Mail", Some("/"), &attrs(&["\\HasNoChildren", "\\Sent"]));
+        assert_eq!(gmail_sent.special_use.as_deref(), Some(SPECIAL_USE_SENT));
+        assert_eq!(gmail_sent.category, "发件箱");
+
+        let gmail_all = MailFolder::from_list(1, "[Gmail]/All Mail", "[Gmail]/All Mail", Some("/"), &attrs(&["\\All"]));
+        assert!(!gmail_all.should_sync());
+
+        let gmail_root = MailFolder::from_list(1, "[Gmail]", "[Gmail]", Some("/"), &attrs(&["\\Noselect"]));
+        assert!(!gmail_root.should_sync());
+
+        // 163 不返回 SPECIAL-USE 属性，按名称识别
+        let netease_junk = MailFolder::from_list(1, "&V4NXPpCuTvY-", "垃圾邮件", Some("/"), &[]);
+        assert_eq!(netease_junk.category, "垃圾邮件");
+
+        let qq_trash = MailFolder::from_list(1, "Deleted Messages", "Deleted Messages", Some("/"), &[]);
+        assert_eq!(qq_trash.category, "垃圾箱");
+
+        let inbox = MailFolder::from_list(1, "INBOX", "INBOX", Some("/"), &[]);
+        assert_eq!(inbox.category, "收件箱");
+        assert!(inbox.should_sync());
+    }
+}
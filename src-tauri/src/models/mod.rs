@@ -1,4 +1,15 @@
 pub mod email;
 pub mod email_provider;
+pub mod sync_state;
+pub mod pending_operation;
+pub mod folder;
+pub mod oauth_token;
+pub mod encryption;
+pub mod attachment;
+pub mod outgoing;
+pub mod address;
+pub mod thread;
+pub mod draft;
+pub mod outbox;
 
 pub use email::*;
\ No newline at end of file
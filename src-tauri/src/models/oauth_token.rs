@@ -0,0 +1,26 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 访问令牌在到期前多久开始刷新
+const REFRESH_MARGIN_SECS: i64 = 5 * 60;
+
+/// 账户的 OAuth2 令牌
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthToken {
+    pub account_id: i32,
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    /// 访问令牌的过期时间，服务器未返回时为 None
+    pub expires_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl OAuthToken {
+    /// 访问令牌已过期或即将过期
+    pub fn needs_refresh(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => Utc::now() + Duration::seconds(REFRESH_MARGIN_SECS) >= expires_at,
+            None => false,
+        }
+    }
+}
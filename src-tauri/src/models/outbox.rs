@@ -0,0 +1,66 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use crate::models::outgoing::OutgoingMessage;
+
+/// 发件箱中邮件的状态，发送成功后从发件箱删除
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxStatus {
+    /// 等待发送（撤回窗口内、定时发送或等待重试）
+    Queued,
+    /// 后台正在发送，无法撤回
+    Sending,
+    /// 永久失败或多次重试后仍然失败，需要手动重试或撤回
+    Failed,
+    /// 已经发出但没能从发件箱删除，不会再次发送，下次启动时删除
+    Sent,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Queued => "queued",
+            OutboxStatus::Sending => "sending",
+            OutboxStatus::Failed => "failed",
+            OutboxStatus::Sent => "sent",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "queued" => Some(OutboxStatus::Queued),
+            "sending" => Some(OutboxStatus::Sending),
+            "failed" => Some(OutboxStatus::Failed),
+            "sent" => Some(OutboxStatus::Sent),
+            _ => None,
+        }
+    }
+}
+
+/// 发件箱中等待发送的邮件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxMessage {
+    pub id: String,
+    pub account_id: i32,
+    pub message: OutgoingMessage,
+    /// 入队时生成，重试时保持不变，收件方可以据此识别重复投递
+    pub message_id: String,
+    /// 回复或转发的原邮件，撤回到草稿时保留
+    pub source_email_id: Option<String>,
+    /// 从草稿发送时的草稿，邮件发出后删除，撤回时恢复为该草稿
+    pub draft_id: Option<String>,
+    pub status: OutboxStatus,
+    /// 计划发送时间（RFC 3339），不早于入队时间加撤回窗口
+    pub send_at: String,
+    /// 下一次尝试发送的时间，失败重试时推后
+    pub next_attempt_at: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// 发件箱中的时间统一为精确到秒的 UTC 时间，保证按字符串比较的结果与时间顺序一致
+pub fn outbox_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
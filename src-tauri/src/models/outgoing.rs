@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// 待发送的邮件
+///
+/// 地址可以是 `user@example.com` 或 `名称 <user@example.com>`。
+/// 只填写 `html_body` 时会根据 HTML 自动生成纯文本版本，两者作为 multipart/alternative 发送。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutgoingMessage {
+    #[serde(default)]
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    /// 密送地址只用于 SMTP 投递，不写入邮件头部
+    #[serde(default)]
+    pub bcc: Vec<String>,
+    pub reply_to: Option<String>,
+    pub subject: String,
+    pub text_body: Option<String>,
+    pub html_body: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<OutgoingAttachment>,
+    /// 回复时原邮件的 Message-ID（不含尖括号）
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+    /// 回复时的 References 列表，从最早的祖先到原邮件
+    #[serde(default)]
+    pub references: Vec<String>,
+}
+
+impl OutgoingMessage {
+    /// 所有投递地址（收件人、抄送、密送）
+    pub fn all_recipients(&self) -> impl Iterator<Item = &String> {
+        self.to.iter().chain(&self.cc).chain(&self.bcc)
+    }
+}
+
+/// 根据已有邮件生成的回复或转发草稿
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComposeDraft {
+    /// 原邮件所属的账户，作为默认发件账户
+    pub account_id: Option<i32>,
+    /// 被回复或转发的邮件
+    pub source_email_id: String,
+    pub message: OutgoingMessage,
+}
+
+/// 待发送邮件的附件，从本地文件或附件存储读取
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutgoingAttachment {
+    /// 本地文件路径，`sha256` 有值时不使用
+    #[serde(default)]
+    pub path: String,
+    /// 附件存储中的内容，转发原邮件的附件或放入发件箱后设置
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// 邮件中显示的文件名，默认使用本地文件名
+    pub filename: Option<String>,
+    /// 默认根据扩展名推断
+    pub mime_type: Option<String>,
+    /// 设置后作为内嵌资源发送，HTML 正文中通过 `cid:<content_id>` 引用
+    pub content_id: Option<String>,
+}
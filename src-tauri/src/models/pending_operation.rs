@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// IMAP 已读标记
+pub const FLAG_SEEN: &str = "\\Seen";
+/// IMAP 星标（重要）标记
+pub const FLAG_FLAGGED: &str = "\\Flagged";
+
+/// 等待推送到服务器的标记修改（离线时保存在队列中）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingOperation {
+    pub id: i64,
+    pub account_id: i32,
+    pub folder: String,
+    pub uid: u32,
+    /// IMAP 标记，例如 `\Seen`、`\Flagged`
+    pub flag: String,
+    /// true 为添加标记，false 为移除标记
+    pub value: bool,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: String,
+}
+
+impl PendingOperation {
+    /// 对应的 UID STORE 参数，例如 `+FLAGS.SILENT (\Seen)`
+    pub fn store_query(&self) -> String {
+        let sign = if self.value { "+" } else { "-" };
+        format!("{}FLAGS.SILENT ({})", sign, self.flag)
+    }
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// 每个账户、每个文件夹的同步进度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderSyncState {
+    pub account_id: i32,
+    pub folder: String,
+    /// 服务器返回的 UIDVALIDITY，变化时本地缓存的 UID 全部失效
+    pub uid_validity: u32,
+    /// 已同步的最大 UID，下次同步从 `last_uid + 1` 开始
+    pub last_uid: u32,
+    /// CONDSTORE 的 HIGHESTMODSEQ，服务器不支持时为 0
+    pub highest_modseq: u64,
+    pub updated_at: String,
+}
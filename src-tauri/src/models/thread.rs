@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// 会话列表项，数量和时间由会话中的邮件实时统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailThread {
+    pub id: String,
+    pub account_id: Option<i32>,
+    /// 去掉 Re:/Fwd: 等前缀后的主题
+    pub subject: String,
+    pub message_count: i64,
+    pub unread_count: i64,
+    pub has_attachment: bool,
+    /// 最新一封邮件的发件人
+    pub last_sender: String,
+    pub last_message_at: String,
+}
+
+/// 计算会话时使用的邮件头部信息
+#[derive(Debug, Clone)]
+pub struct ThreadingHeaders {
+    pub email_id: String,
+    pub message_id: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    pub subject: String,
+    /// 排序用的日期（RFC 3339）
+    pub date: String,
+    /// Gmail 的 X-GM-THRID
+    pub gmail_thread_id: Option<String>,
+    /// 当前所属的会话
+    pub thread_id: Option<String>,
+}
+
+/// 一个会话及其包含的邮件
+#[derive(Debug, Clone)]
+pub struct ThreadAssignment {
+    pub id: String,
+    pub subject: String,
+    /// 会话中各邮件的主题键（见 `threading::normalize_subject`），按主题查找会话时使用
+    pub subject_keys: Vec<String>,
+    pub email_ids: Vec<String>,
+}
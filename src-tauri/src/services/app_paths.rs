@@ -0,0 +1,234 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// 选择配置文件的环境变量，命令行的 `--profile` 优先
+pub const PROFILE_ENV: &str = "XMAIL_PROFILE";
+
+const DEFAULT_PROFILE: &str = "default";
+const DATABASE_FILE: &str = "emails.db";
+const ATTACHMENTS_DIR: &str = "attachments";
+
+/// 当前配置文件的数据位置
+///
+/// 默认配置文件直接使用应用数据目录，其他配置文件使用 `profiles/<名称>` 子目录，
+/// 各自拥有独立的数据库、附件和密钥文件。
+pub struct AppPaths {
+    profile: Option<String>,
+    data_dir: PathBuf,
+}
+
+impl AppPaths {
+    pub fn resolve(app_data_dir: &Path, profile: Option<String>) -> Result<Self> {
+        let data_dir = match &profile {
+            Some(profile) => app_data_dir.join("profiles").join(profile),
+            None => app_data_dir.to_path_buf(),
+        };
+        std::fs::create_dir_all(&data_dir)
+            .with_context(|| format!("无法创建数据目录 {}", data_dir.display()))?;
+        Ok(Self { profile, data_dir })
+    }
+
+    /// 配置文件名称，默认配置文件为 None
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    pub fn database(&self) -> PathBuf {
+        self.data_dir.join(DATABASE_FILE)
+    }
+
+    pub fn attachments(&self) -> PathBuf {
+        self.data_dir.join(ATTACHMENTS_DIR)
+    }
+
+    /// 把旧版本放在启动目录中的数据库和附件移动到默认配置文件的数据目录
+    ///
+    /// 只在数据目录中还没有数据库时执行，返回是否移动了数据库。附件先于数据库移动，
+    /// 数据目录中已有附件目录时合并进去，中途失败时下次启动会重新尝试。
+    pub fn migrate_legacy_files(&self, legacy_dir: &Path) -> Result<bool> {
+        let legacy_database = legacy_dir.join(DATABASE_FILE);
+        if self.profile.is_some() || self.database().exists() || !legacy_database.is_file() {
+            return Ok(false);
+        }
+
+        let legacy_attachments = legacy_dir.join(ATTACHMENTS_DIR);
+        if legacy_attachments.is_dir() {
+            if self.attachments().exists() {
+                merge_dir(&legacy_attachments, &self.attachments())?;
+            } else {
+                move_path(&legacy_attachments, &self.attachments())?;
+            }
+        }
+
+        // 回滚日志必须和数据库一起移动，否则未完成的事务无法恢复
+        for suffix in ["-journal", "-wal", "-shm"] {
+            let from = legacy_dir.join(format!("{}{}", DATABASE_FILE, suffix));
+            if from.is_file() {
+                move_path(&from, &self.data_dir.join(format!("{}{}", DATABASE_FILE, suffix)))?;
+            }
+        }
+        move_path(&legacy_database, &self.database())?;
+        Ok(true)
+    }
+}
+
+/// 从命令行参数（`--profile 名称` 或 `--profile=名称`）和 `XMAIL_PROFILE` 中读取配置文件
+pub fn selected_profile() -> Result<Option<String>> {
+    parse_profile(std::env::args().skip(1), std::env::var(PROFILE_ENV).ok())
+}
+
+fn parse_profile(args: impl IntoIterator<Item = String>, env: Option<String>) -> Result<Option<String>> {
+    let mut selected = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            match args.next() {
+                Some(value) => selected = Some(value),
+                None => bail!("--profile 缺少配置文件名称"),
+            }
+        } else if let Some(value) = arg.strip_prefix("--profile=") {
+            selected = Some(value.to_string());
+        }
+    }
+
+    let profile = match selected.or(env) {
+        Some(profile) => profile.trim().to_string(),
+        None => return Ok(None),
+    };
+    if profile.is_empty() || profile == DEFAULT_PROFILE {
+        return Ok(None);
+    }
+    // 名称用作目录名，不允许路径分隔符和 ..
+    if profile.len() > 64 || !profile.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        bail!("配置文件名称只能包含字母、数字、- 和 _，且不超过 64 个字符: {}", profile);
+    }
+    Ok(Some(profile))
+}
+
+/// 移动文件或目录，跨文件系统时复制后删除原文件
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    copy_recursive(from, to)
+        .with_context(|| format!("无法把 {} 移动到 {}", from.display(), to.display()))?;
+    if from.is_dir() {
+        std::fs::remove_dir_all(from)?;
+    } else {
+        std::fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// 把目录中的内容移动到已有目录，同名文件已存在时跳过
+///
+/// 附件存储按内容命名，同名文件的内容相同。
+fn merge_dir(from: &Path, to: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(from)? {
+        let source = entry?.path();
+        let target = to.join(source.file_name().unwrap_or_default());
+        if !target.exists() {
+            move_path(&source, &target)?;
+        } else if source.is_dir() && target.is_dir() {
+            merge_dir(&source, &target)?;
+        } else if source.is_file() && target.is_file() {
+            std::fs::remove_file(&source)?;
+        } else {
+            bail!("无法把 {} 合并到 {}", source.display(), target.display());
+        }
+    }
+    std::fs::remove_dir(from)?;
+    Ok(())
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        std::fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_profile_selection() {
+        let env = Some("personal".to_string());
+        assert_eq!(parse_profile(args(&[]), None).unwrap(), None);
+        assert_eq!(parse_profile(args(&[]), env.clone()).unwrap().as_deref(), Some("personal"));
+        assert_eq!(parse_profile(args(&["--profile", "work"]), env.clone()).unwrap().as_deref(), Some("work"));
+        assert_eq!(parse_profile(args(&["--profile=work"]), None).unwrap().as_deref(), Some("work"));
+        assert_eq!(parse_profile(args(&["--profile", "default"]), env).unwrap(), None);
+
+        assert!(parse_profile(args(&["--profile"]), None).is_err());
+        assert!(parse_profile(args(&["--profile", "../work"]), None).is_err());
+    }
+
+    #[test]
+    fn test_legacy_files_move_into_default_profile() {
+        let root = std::env::temp_dir().join(format!("xmail-paths-{}", uuid::Uuid::new_v4()));
+        let legacy = root.join("cwd");
+        std::fs::create_dir_all(legacy.join("attachments/ab")).unwrap();
+        std::fs::write(legacy.join("emails.db"), b"db").unwrap();
+        std::fs::write(legacy.join("attachments/ab/abcd"), b"blob").unwrap();
+
+        // 其他配置文件不接收旧数据
+        let work = AppPaths::resolve(&root.join("data"), Some("work".to_string())).unwrap();
+        assert!(!work.migrate_legacy_files(&legacy).unwrap());
+        assert!(work.data_dir().ends_with("profiles/work"));
+
+        let paths = AppPaths::resolve(&root.join("data"), None).unwrap();
+        assert!(paths.migrate_legacy_files(&legacy).unwrap());
+        assert_eq!(std::fs::read(paths.database()).unwrap(), b"db");
+        assert_eq!(std::fs::read(paths.attachments().join("ab/abcd")).unwrap(), b"blob");
+        assert!(!legacy.join("emails.db").exists());
+
+        // 数据目录已有数据库时不再移动
+        std::fs::write(legacy.join("emails.db"), b"stale").unwrap();
+        assert!(!paths.migrate_legacy_files(&legacy).unwrap());
+        assert_eq!(std::fs::read(paths.database()).unwrap(), b"db");
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_legacy_attachments_merge_into_existing_store() {
+        let root = std::env::temp_dir().join(format!("xmail-paths-{}", uuid::Uuid::new_v4()));
+        let legacy = root.join("cwd");
+        std::fs::create_dir_all(legacy.join("attachments/ab")).unwrap();
+        std::fs::write(legacy.join("emails.db"), b"db").unwrap();
+        std::fs::write(legacy.join("attachments/ab/abcd"), b"blob").unwrap();
+        std::fs::write(legacy.join("attachments/ab/abce"), b"legacy").unwrap();
+
+        let paths = AppPaths::resolve(&root.join("data"), None).unwrap();
+        std::fs::create_dir_all(paths.attachments().join("ab")).unwrap();
+        std::fs::create_dir_all(paths.attachments().join("cd")).unwrap();
+        std::fs::write(paths.attachments().join("ab/abcd"), b"blob").unwrap();
+        std::fs::write(paths.attachments().join("cd/cdef"), b"new").unwrap();
+
+        assert!(paths.migrate_legacy_files(&legacy).unwrap());
+        assert_eq!(std::fs::read(paths.attachments().join("ab/abcd")).unwrap(), b"blob");
+        assert_eq!(std::fs::read(paths.attachments().join("ab/abce")).unwrap(), b"legacy");
+        assert_eq!(std::fs::read(paths.attachments().join("cd/cdef")).unwrap(), b"new");
+        assert!(!legacy.join("attachments").exists());
+        assert_eq!(std::fs::read(paths.database()).unwrap(), b"db");
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{Result, anyhow};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// 按内容寻址的本地文件存储，用于保存附件内容
+///
+/// 文件按 SHA-256 命名并放在以前两位命名的子目录中，相同内容只保存一份。
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+impl BlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// 保存内容并返回其 SHA-256
+    pub fn put(&self, data: &[u8]) -> Result<String> {
+        let sha256 = format!("{:x}", Sha256::digest(data));
+        let path = self.path(&sha256)?;
+
+        if !path.exists() {
+            let dir = path.parent().ok_or_else(|| anyhow!("附件存储路径无效"))?;
+            std::fs::create_dir_all(dir)?;
+
+            // 先写入临时文件再重命名，避免中断时留下不完整的内容
+            let temp = dir.join(format!("{}.{}.tmp", sha256, uuid::Uuid::new_v4()));
+            std::fs::write(&temp, data)?;
+            if let Err(e) = std::fs::rename(&temp, &path) {
+                std::fs::remove_file(&temp).ok();
+                if !path.exists() {
+                    return Err(e.into());
+                }
+            }
+        }
+
+        Ok(sha256)
+    }
+
+    /// 内容在磁盘上的路径
+    pub fn path(&self, sha256: &str) -> Result<PathBuf> {
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!("无效的附件摘要: {}", sha256));
+        }
+        Ok(self.root.join(&sha256[..2]).join(sha256))
+    }
+
+    pub fn read(&self, sha256: &str) -> Result<Vec<u8>> {
+        Ok(std::fs::read(self.path(sha256)?)?)
+    }
+
+    /// 删除不在 `referenced` 中的内容，返回删除的文件数
+    ///
+    /// 只处理按摘要命名的文件；应在开始同步前调用，同步时内容先于附件记录写入。
+    pub fn remove_unreferenced(&self, referenced: &HashSet<String>) -> Result<usize> {
+        if !self.root.is_dir() {
+            return Ok(0);
+        }
+
+        let mut removed = 0;
+        for dir in std::fs::read_dir(&self.root)? {
+            let dir = dir?.path();
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+                if self.path(name).is_ok_and(|expected| expected == path) && !referenced.contains(name) {
+                    std::fs::remove_file(&path)?;
+                    removed += 1;
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// 将内容复制到指定路径
+    pub fn export(&self, sha256: &str, target: &Path) -> Result<()> {
+        let source = self.path(sha256)?;
+        if !source.exists() {
+            return Err(anyhow!("附件内容不存在，请重新同步该邮件"));
+        }
+        std::fs::copy(source, target)?;
+        Ok(())
+    }
+}
+
+/// 将附件名转换为安全的本地文件名，去掉路径分隔符和系统保留字符
+pub fn sanitize_filename(filename: &str) -> String {
+    let name: String = filename
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let name = name.trim().trim_matches('.').to_string();
+    if name.is_empty() {
+        "attachment".to_string()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_addressed_storage() {
+        let root = std::env::temp_dir().join(format!("xmail-blobs-{}", uuid::Uuid::new_v4()));
+        let store = BlobStore::new(&root);
+
+        let first = store.put(b"hello").unwrap();
+        assert_eq!(first, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+        assert_eq!(store.put(b"hello").unwrap(), first);
+        assert_eq!(store.read(&first).unwrap(), b"hello");
+        assert!(root.join("2c").join(&first).exists());
+
+        assert!(store.path("../../etc/passwd").is_err());
+
+        // 只保留仍被引用的内容
+        let second = store.put(b"world").unwrap();
+        let referenced: HashSet<String> = [first.clone()].into_iter().collect();
+        assert_eq!(store.remove_unreferenced(&referenced).unwrap(), 1);
+        assert!(store.read(&first).is_ok());
+        assert!(store.read(&second).is_err());
+        assert_eq!(sanitize_filename("../报告:最终版.pdf"), "_报告_最终版.pdf");
+        assert_eq!(sanitize_filename(".."), "attachment");
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
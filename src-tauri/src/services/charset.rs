@@ -0,0 +1,132 @@
+use encoding_rs::{Encoding, GB18030, REPLACEMENT};
+use regex::Regex;
+use std::sync::OnceLock;
+use crate::services::mime_parser::{decode_base64, decode_quoted_printable};
+
+/// 查找字符集对应的编码，支持 WHATWG 标签以及常见的 Windows 代码页别名
+pub fn lookup_encoding(label: &str) -> Option<&'static Encoding> {
+    let label = label.trim().trim_matches('"').to_ascii_lowercase();
+    // RFC 2231 允许附带语言后缀，例如 `utf-8*zh-cn`
+    let label = label.split('*').next().unwrap_or_default();
+
+    let label = match label {
+        "cp936" | "ms936" | "windows-936" => "gbk",
+        "cp950" | "ms950" | "windows-950" | "big5hkscs" => "big5",
+        "cp932" | "ms932" | "windows-932" | "sjis" => "shift_jis",
+        "cp949" | "ms949" | "windows-949" | "ks_c_5601" => "euc-kr",
+        "cp874" => "windows-874",
+        "utf-8-sig" => "utf-8",
+        other => other,
+    };
+
+    Encoding::for_label(label.as_bytes()).filter(|encoding| *encoding != REPLACEMENT)
+}
+
+/// 按声明的字符集解码文本；未声明或无法识别时自动判断
+pub fn decode_bytes(bytes: &[u8], charset: Option<&str>) -> String {
+    match charset.and_then(lookup_encoding) {
+        Some(encoding) => encoding.decode(bytes).0.into_owned(),
+        None => decode_unlabeled(bytes),
+    }
+}
+
+/// 解码未声明字符集的内容：优先 UTF-8，否则按 GB18030 处理
+/// （国内邮件服务商的老客户端常直接发送 GBK 编码的头部和正文）
+pub fn decode_unlabeled(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => GB18030.decode_without_bom_handling(bytes).0.into_owned(),
+    }
+}
+
+/// 解码头部中的 RFC 2047 编码字，例如 `=?GBK?B?1tDOxA==?=`
+///
+/// 相邻且字符集相同的编码字会先拼接字节再解码，
+/// 以正确处理被拆分到两个编码字中的多字节字符。
+pub fn decode_encoded_words(value: &str) -> String {
+    static ENCODED_WORD: OnceLock<Regex> = OnceLock::new();
+    let re = ENCODED_WORD.get_or_init(|| Regex::new(r"=\?([^?\s]+)\?([BbQq])\?([^?\s]*)\?=").unwrap());
+
+    let mut output = String::new();
+    let mut pending: Option<(String, Vec<u8>)> = None;
+    let mut last_end = 0;
+
+    for caps in re.captures_iter(value) {
+        let matched = caps.get(0).unwrap();
+        let between = &value[last_end..matched.start()];
+
+        // 编码字之间的空白应被忽略
+        if pending.is_none() || !between.trim().is_empty() {
+            flush_pending(&mut pending, &mut output);
+            output.push_str(between);
+        }
+
+        let charset = caps[1].to_string();
+        let bytes = decode_word(&caps[2], &caps[3]);
+
+        match &mut pending {
+            Some((current, buffer)) if current.eq_ignore_ascii_case(&charset) => {
+                buffer.extend(bytes);
+            }
+            _ => {
+                flush_pending(&mut pending, &mut output);
+                pending = Some((charset, bytes));
+            }
+        }
+
+        last_end = matched.end();
+    }
+
+    flush_pending(&mut pending, &mut output);
+    output.push_str(&value[last_end..]);
+    output
+}
+
+fn decode_word(encoding: &str, text: &str) -> Vec<u8> {
+    if encoding.eq_ignore_ascii_case("b") {
+        decode_base64(text.as_bytes()).unwrap_or_else(|| text.as_bytes().to_vec())
+    } else {
+        // Q 编码中下划线表示空格
+        let text = text.replace('_', " ");
+        decode_quoted_printable(text.as_bytes())
+    }
+}
+
+fn flush_pending(pending: &mut Option<(String, Vec<u8>)>, output: &mut String) {
+    if let Some((charset, bytes)) = pending.take() {
+        output.push_str(&decode_bytes(&bytes, Some(&charset)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_gbk_encoded_word() {
+        assert_eq!(decode_encoded_words("=?GBK?B?1tDOxA==?="), "中文");
+        assert_eq!(decode_encoded_words("Re: =?gb2312?B?1tDOxA==?= test"), "Re: 中文 test");
+    }
+
+    #[test]
+    fn test_decode_split_multibyte_words() {
+        // “中” 被拆分在两个编码字中
+        assert_eq!(decode_encoded_words("=?GBK?B?1g==?= =?GBK?B?0M7E?="), "中文");
+    }
+
+    #[test]
+    fn test_decode_q_encoding() {
+        assert_eq!(
+            decode_encoded_words("=?utf-8?Q?caf=C3=A9_au_lait?= <a@example.com>"),
+            "café au lait <a@example.com>"
+        );
+    }
+
+    #[test]
+    fn test_decode_bytes_with_charset() {
+        assert_eq!(decode_bytes(&[0xD6, 0xD0, 0xCE, 0xC4], Some("gb2312")), "中文");
+        assert_eq!(decode_bytes(&[0xA4, 0xA4, 0xA4, 0xE5], Some("big5")), "中文");
+        assert_eq!(decode_bytes(&[0xD6, 0xD0, 0xCE, 0xC4], None), "中文");
+        assert_eq!(decode_bytes("中文".as_bytes(), Some("unknown-charset")), "中文");
+    }
+}
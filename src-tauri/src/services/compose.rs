@@ -0,0 +1,306 @@
+use anyhow::{Result, anyhow};
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::address::Envelope;
+use lettre::Message;
+use std::path::Path;
+use crate::models::outgoing::{OutgoingAttachment, OutgoingMessage};
+use crate::services::blob_store::BlobStore;
+use crate::services::mime_parser::{self, AttachmentPart};
+
+/// 读取待发送邮件的附件，已在附件存储中的从存储读取，其余读取本地文件
+pub fn load_attachments(message: &OutgoingMessage, blobs: &BlobStore) -> Result<Vec<AttachmentPart>> {
+    message.attachments.iter().map(|attachment| load_attachment(attachment, blobs)).collect()
+}
+
+/// 把本地文件附件复制到附件存储，改为按 SHA-256 引用
+///
+/// 放入发件箱时调用，之后本地文件被移动或删除也不影响发送。
+pub fn store_attachments(message: &mut OutgoingMessage, blobs: &BlobStore) -> Result<()> {
+    for attachment in &mut message.attachments {
+        if attachment.sha256.is_some() {
+            continue;
+        }
+
+        let part = load_attachment(attachment, blobs)?;
+        *attachment = OutgoingAttachment {
+            path: String::new(),
+            sha256: Some(blobs.put(&part.data)?),
+            filename: Some(part.filename),
+            mime_type: Some(part.mime_type),
+            content_id: part.content_id,
+        };
+    }
+    Ok(())
+}
+
+fn load_attachment(attachment: &OutgoingAttachment, blobs: &BlobStore) -> Result<AttachmentPart> {
+    let path = Path::new(&attachment.path);
+    let data = match &attachment.sha256 {
+        Some(sha256) => blobs.read(sha256).map_err(|e| {
+            let name = attachment.filename.as_deref().unwrap_or(sha256);
+            anyhow!("附件 {} 的内容不存在: {}", name, e)
+        })?,
+        None => std::fs::read(path).map_err(|e| anyhow!("无法读取附件 {}: {}", attachment.path, e))?,
+    };
+
+    let filename = attachment
+        .filename
+        .clone()
+        .or_else(|| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "attachment".to_string());
+    let mime_type = attachment
+        .mime_type
+        .clone()
+        .unwrap_or_else(|| mime_guess::from_path(&filename).first_or_octet_stream().to_string());
+    let content_id = attachment
+        .content_id
+        .as_deref()
+        .map(|cid| cid.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+        .filter(|cid| !cid.is_empty());
+
+    Ok(AttachmentPart {
+        filename,
+        mime_type,
+        is_inline: content_id.is_some(),
+        content_id,
+        data,
+    })
+}
+
+/// 纯文本正文：优先使用填写的文本，否则由 HTML 生成
+pub fn text_body(message: &OutgoingMessage) -> String {
+    match (&message.text_body, &message.html_body) {
+        (Some(text), _) => text.clone(),
+        (None, Some(html)) => mime_parser::html_to_text(html),
+        (None, None) => String::new(),
+    }
+}
+
+/// 解析地址列表，每项可以是 `user@example.com` 或 `名称 <user@example.com>`
+pub fn parse_mailboxes(addresses: &[String]) -> Result<Vec<Mailbox>> {
+    addresses
+        .iter()
+        .map(|address| address.trim())
+        .filter(|address| !address.is_empty())
+        .map(|address| address.parse().map_err(|e| anyhow!("地址无效 {}: {}", address, e)))
+        .collect()
+}
+
+/// 构建 MIME 邮件
+///
+/// 结构为 `mixed(alternative(text, related(html, 内嵌资源)), 附件...)`，
+/// 没有附件、HTML 或内嵌资源时省略对应的层级。
+pub fn build_message(
+    from: Mailbox,
+    message_id: &str,
+    message: &OutgoingMessage,
+    attachments: &[AttachmentPart],
+) -> Result<Message> {
+    build(from, message_id, message, attachments, false)
+}
+
+/// 构建保存到服务器草稿箱的邮件：收件人可以为空，并保留 Bcc 头部
+pub fn build_draft(
+    from: Mailbox,
+    message_id: &str,
+    message: &OutgoingMessage,
+    attachments: &[AttachmentPart],
+) -> Result<Message> {
+    build(from, message_id, message, attachments, true)
+}
+
+fn build(
+    from: Mailbox,
+    message_id: &str,
+    message: &OutgoingMessage,
+    attachments: &[AttachmentPart],
+    draft: bool,
+) -> Result<Message> {
+    let to = parse_mailboxes(&message.to)?;
+    let cc = parse_mailboxes(&message.cc)?;
+    let bcc = parse_mailboxes(&message.bcc)?;
+    if to.is_empty() && cc.is_empty() && bcc.is_empty() && !draft {
+        return Err(anyhow!("至少需要一个收件人"));
+    }
+
+    let mut builder = Message::builder()
+        .from(from.clone())
+        .subject(&message.subject)
+        .date_now()
+        .message_id(Some(format!("<{}>", message_id)));
+    for mailbox in to {
+        builder = builder.to(mailbox);
+    }
+    for mailbox in cc {
+        builder = builder.cc(mailbox);
+    }
+    // lettre 只用 Bcc 生成投递地址，构建时会去掉 Bcc 头部
+    for mailbox in bcc {
+        builder = builder.bcc(mailbox);
+    }
+    if draft {
+        // 草稿不投递，信封只是为了在没有收件人时也能构建
+        let address = from.email.clone();
+        builder = builder.keep_bcc().envelope(Envelope::new(Some(address.clone()), vec![address])?);
+    }
+    if let Some(in_reply_to) = &message.in_reply_to {
+        builder = builder.in_reply_to(format!("<{}>", in_reply_to));
+    }
+    if !message.references.is_empty() {
+        let references: Vec<String> = message.references.iter().map(|id| format!("<{}>", id)).collect();
+        builder = builder.references(references.join(" "));
+    }
+    if let Some(reply_to) = message.reply_to.as_deref().filter(|r| !r.trim().is_empty()) {
+        builder = builder.reply_to(reply_to.trim().parse().map_err(|e| anyhow!("回复地址无效: {}", e))?);
+    }
+
+    let (inline, regular): (Vec<&AttachmentPart>, Vec<&AttachmentPart>) =
+        attachments.iter().partition(|part| part.is_inline);
+
+    let text = SinglePart::plain(text_body(message));
+    let message = match &message.html_body {
+        Some(html) => {
+            let html = SinglePart::html(html.clone());
+            let alternative = if inline.is_empty() {
+                MultiPart::alternative().singlepart(text).singlepart(html)
+            } else {
+                let mut related = MultiPart::related().singlepart(html);
+                for part in &inline {
+                    related = related.singlepart(inline_part(part)?);
+                }
+                MultiPart::alternative().singlepart(text).multipart(related)
+            };
+
+            if regular.is_empty() {
+                builder.multipart(alternative)?
+            } else {
+                let mut mixed = MultiPart::mixed().multipart(alternative);
+                for part in regular {
+                    mixed = mixed.singlepart(attachment_part(part));
+                }
+                builder.multipart(mixed)?
+            }
+        }
+        None if attachments.is_empty() => builder.singlepart(text)?,
+        None => {
+            // 纯文本邮件中的内嵌资源没有地方引用，作为普通附件发送
+            let mut mixed = MultiPart::mixed().singlepart(text);
+            for part in attachments {
+                mixed = mixed.singlepart(attachment_part(part));
+            }
+            builder.multipart(mixed)?
+        }
+    };
+
+    Ok(message)
+}
+
+fn content_type(part: &AttachmentPart) -> ContentType {
+    ContentType::parse(&part.mime_type)
+        .unwrap_or_else(|_| ContentType::parse("application/octet-stream").unwrap())
+}
+
+fn attachment_part(part: &AttachmentPart) -> SinglePart {
+    Attachment::new(part.filename.clone()).body(part.data.clone(), content_type(part))
+}
+
+fn inline_part(part: &AttachmentPart) -> Result<SinglePart> {
+    let content_id = part
+        .content_id
+        .clone()
+        .ok_or_else(|| anyhow!("内嵌资源 {} 缺少 Content-ID", part.filename))?;
+    Ok(Attachment::new_inline(content_id).body(part.data.clone(), content_type(part)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(filename: &str, content_id: Option<&str>) -> AttachmentPart {
+        AttachmentPart {
+            filename: filename.to_string(),
+            mime_type: mime_guess::from_path(filename).first_or_octet_stream().to_string(),
+            content_id: content_id.map(str::to_string),
+            is_inline: content_id.is_some(),
+            data: b"data".to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_build_html_message_with_attachments() {
+        let outgoing = OutgoingMessage {
+            to: vec!["张三 <zhangsan@example.com>".to_string()],
+            cc: vec!["lisi@example.com".to_string()],
+            bcc: vec!["wangwu@example.com".to_string()],
+            reply_to: Some("reply@example.com".to_string()),
+            subject: "周报".to_string(),
+            in_reply_to: Some("parent@example.com".to_string()),
+            references: vec!["root@example.com".to_string(), "parent@example.com".to_string()],
+            html_body: Some("<p>你好</p><img src=\"cid:logo\">".to_string()),
+            ..Default::default()
+        };
+        let attachments = vec![part("report.pdf", None), part("logo.png", Some("logo"))];
+
+        let from = "me@example.com".parse().unwrap();
+        let message = build_message(from, "abc@example.com", &outgoing, &attachments).unwrap();
+        assert_eq!(message.envelope().to().len(), 3);
+
+        let raw = message.formatted();
+        let parsed = mime_parser::parse_message(&raw).unwrap();
+        assert_eq!(parsed.header("Message-ID"), Some("<abc@example.com>"));
+        assert!(parsed.header("Bcc").is_none());
+        assert!(parsed.header("Reply-To").is_some());
+        assert_eq!(parsed.header("In-Reply-To"), Some("<parent@example.com>"));
+        assert_eq!(
+            mime_parser::parse_message_id_list(parsed.header("References").unwrap()),
+            vec!["root@example.com", "parent@example.com"]
+        );
+        assert_eq!(parsed.text_body.as_deref().map(str::trim), Some("你好"));
+        assert!(parsed.html_body.as_deref().unwrap().contains("cid:logo"));
+
+        assert_eq!(parsed.attachments.len(), 2);
+        let logo = parsed.attachments.iter().find(|a| a.is_inline).unwrap();
+        assert_eq!(logo.content_id.as_deref(), Some("logo"));
+        assert_eq!(logo.data, b"data");
+        assert!(parsed.has_attachment());
+
+        let outgoing = OutgoingMessage { subject: "无收件人".to_string(), ..Default::default() };
+        let from: Mailbox = "me@example.com".parse().unwrap();
+        assert!(build_message(from.clone(), "def@example.com", &outgoing, &[]).is_err());
+
+        // 草稿可以没有收件人，并保留密送
+        let outgoing = OutgoingMessage { bcc: vec!["wangwu@example.com".to_string()], ..outgoing };
+        let raw = build_draft(from, "def@example.com", &outgoing, &[]).unwrap().formatted();
+        let parsed = mime_parser::parse_message(&raw).unwrap();
+        assert_eq!(parsed.header("Bcc"), Some("wangwu@example.com"));
+    }
+
+    #[test]
+    fn test_stored_attachments_survive_file_removal() {
+        let dir = std::env::temp_dir().join(format!("xmail-compose-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("report.pdf");
+        std::fs::write(&file, b"report").unwrap();
+
+        let blobs = BlobStore::new(dir.join("attachments"));
+        let mut message = OutgoingMessage {
+            attachments: vec![OutgoingAttachment {
+                path: file.to_string_lossy().into_owned(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        store_attachments(&mut message, &blobs).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        let attachment = &message.attachments[0];
+        assert!(attachment.path.is_empty());
+        assert_eq!(attachment.filename.as_deref(), Some("report.pdf"));
+        let parts = load_attachments(&message, &blobs).unwrap();
+        assert_eq!(parts[0].data, b"report");
+        assert_eq!(parts[0].mime_type, "application/pdf");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,22 +1,91 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use argon2::Argon2;
 use base64::{Engine as _, engine::general_purpose};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use std::path::Path;
+use std::sync::RwLock;
+use crate::database::Database;
+use crate::models::encryption::{EncryptionConfig, EncryptionStatus, KeySource};
 
-/// 密码加密服务
-/// 注意：这是一个简化的实现，生产环境应该使用更安全的加密方式
+/// 密钥长度（XChaCha20-Poly1305 使用 256 位密钥）
+pub const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+
+/// 密文前缀，用于区分旧版的 base64 混淆格式
+const CIPHERTEXT_PREFIX: &str = "v1:";
+
+/// 应用数据目录中的密钥文件名
+pub const KEY_FILE_NAME: &str = "xmail.key";
+
+/// 加密后作为校验值保存，解密成功说明密钥正确
+const KEY_CHECK: &str = "xmail-key-check";
+
+pub type SecretKey = [u8; KEY_LEN];
+
+/// 当前使用的密钥。主密码模式下解锁前为 None，此时无法加解密账户凭据
+static ACTIVE_KEY: RwLock<Option<SecretKey>> = RwLock::new(None);
+
+/// 账户凭据加密服务
+///
+/// 使用 XChaCha20-Poly1305 加密，密钥来自应用数据目录中的随机密钥文件，
+/// 或者由用户设置的主密码经 Argon2id 派生。
 pub struct CryptoService;
 
 impl CryptoService {
-    /// 加密密码（使用 base64 编码 + 简单混淆）
-    /// 生产环境应该使用系统密钥链或更强的加密算法
+    /// 使用当前密钥加密密码
     pub fn encrypt_password(password: &str) -> Result<String> {
-        // 添加简单的混淆
-        let mixed = format!("xmail_{}_secure", password);
-        let encoded = general_purpose::STANDARD.encode(mixed.as_bytes());
-        Ok(encoded)
+        Self::encrypt_with_key(&Self::active_key()?, password)
     }
 
-    /// 解密密码
+    /// 使用当前密钥解密密码
     pub fn decrypt_password(encrypted: &str) -> Result<String> {
+        Self::decrypt_with_key(&Self::active_key()?, encrypted)
+    }
+
+    /// 加密结果为 `v1:` + base64(随机 nonce + 密文)
+    pub fn encrypt_with_key(key: &SecretKey, plaintext: &str) -> Result<String> {
+        let cipher = XChaCha20Poly1305::new(key.into());
+
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| anyhow!("加密失败"))?;
+
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", CIPHERTEXT_PREFIX, general_purpose::STANDARD.encode(data)))
+    }
+
+    pub fn decrypt_with_key(key: &SecretKey, encrypted: &str) -> Result<String> {
+        let encoded = encrypted
+            .strip_prefix(CIPHERTEXT_PREFIX)
+            .ok_or_else(|| anyhow!("Invalid encrypted password format"))?;
+        let data = general_purpose::STANDARD.decode(encoded)?;
+        if data.len() < NONCE_LEN {
+            return Err(anyhow!("Invalid encrypted password format"));
+        }
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let cipher = XChaCha20Poly1305::new(key.into());
+        let plaintext = cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("解密失败：密钥不正确或数据已损坏"))?;
+
+        Ok(String::from_utf8(plaintext)?)
+    }
+
+    /// 是否为旧版本的 base64 混淆格式
+    pub fn is_legacy(encrypted: &str) -> bool {
+        !encrypted.starts_with(CIPHERTEXT_PREFIX)
+    }
+
+    /// 解码旧版本的 base64 混淆格式，只在迁移时使用
+    fn decrypt_legacy(encrypted: &str) -> Result<String> {
         let decoded_bytes = general_purpose::STANDARD.decode(encrypted)?;
         let decoded_str = String::from_utf8(decoded_bytes)?;
         
@@ -29,6 +98,288 @@ impl CryptoService {
         }
     }
 
+    /// 使用 Argon2id 从主密码派生密钥
+    pub fn derive_key(master_password: &str, salt: &[u8]) -> Result<SecretKey> {
+        let mut key = [0u8; KEY_LEN];
+        Argon2::default()
+            .hash_password_into(master_password.as_bytes(), salt, &mut key)
+            .map_err(|e| anyhow!("密钥派生失败: {}", e))?;
+        Ok(key)
+    }
+
+    pub fn generate_key() -> SecretKey {
+        let mut key = [0u8; KEY_LEN];
+        rand::thread_rng().fill_bytes(&mut key);
+        key
+    }
+
+    /// 设置当前使用的密钥，传入 None 表示锁定
+    pub fn set_active_key(key: Option<SecretKey>) {
+        *ACTIVE_KEY.write().unwrap() = key;
+    }
+
+    pub fn is_unlocked() -> bool {
+        ACTIVE_KEY.read().unwrap().is_some()
+    }
+
+    fn active_key() -> Result<SecretKey> {
+        ACTIVE_KEY
+            .read()
+            .unwrap()
+            .ok_or_else(|| anyhow!("账户凭据已锁定，请先输入主密码解锁"))
+    }
+
+    /// 启动时加载密钥，返回是否已解锁
+    ///
+    /// 密钥文件模式下自动加载（首次启动时生成），并将旧版格式的凭据迁移为新格式；
+    /// 主密码模式下保持锁定，等待调用 [`CryptoService::unlock`]。
+    ///
+    /// 密钥文件丢失或与数据库不匹配时同样保持锁定，不会生成新密钥覆盖，
+    /// 由用户通过 [`CryptoService::reset_credentials`] 重置后重新输入账户密码。
+    pub fn initialize(db: &Database, data_dir: &Path) -> Result<bool> {
+        let config = db.get_encryption_config()?;
+        if let Some(config) = &config {
+            if config.key_source == KeySource::MasterPassword {
+                Self::set_active_key(None);
+                return Ok(false);
+            }
+        }
+
+        let key_file = data_dir.join(KEY_FILE_NAME);
+        let key = match &config {
+            Some(config) => {
+                let key = Self::read_key_file(&key_file)
+                    .and_then(|key| Self::verify_key(&key, config).map(|_| key));
+                match key {
+                    Ok(key) => key,
+                    Err(e) => {
+                        eprintln!("密钥文件丢失或与数据库不匹配，需要重置账户凭据: {}", e);
+                        Self::set_active_key(None);
+                        return Ok(false);
+                    }
+                }
+            }
+            None => Self::load_or_create_key_file(&key_file)?,
+        };
+
+        Self::migrate_legacy_secrets(db, &key, KeySource::KeyFile, None)?;
+        Self::set_active_key(Some(key));
+        Ok(true)
+    }
+
+    /// 使用主密码解锁
+    pub fn unlock(db: &Database, master_password: &str) -> Result<()> {
+        let config = db
+            .get_encryption_config()?
+            .filter(|config| config.key_source == KeySource::MasterPassword)
+            .ok_or_else(|| anyhow!("尚未设置主密码"))?;
+
+        let key = Self::master_key(&config, master_password)?;
+        Self::migrate_legacy_secrets(db, &key, KeySource::MasterPassword, config.salt.clone())?;
+        Self::set_active_key(Some(key));
+        Ok(())
+    }
+
+    /// 设置、修改或移除主密码，并用新密钥重新加密所有账户凭据
+    ///
+    /// 已设置主密码时必须提供当前主密码；`new_password` 为 None 时改回使用密钥文件。
+    pub fn change_master_password(
+        db: &Database,
+        data_dir: &Path,
+        current_password: Option<&str>,
+        new_password: Option<&str>,
+    ) -> Result<()> {
+        let config = db.get_encryption_config()?;
+        let current_source = config.as_ref().map(|c| c.key_source).unwrap_or(KeySource::KeyFile);
+
+        let old_key = match (&config, current_source) {
+            (Some(config), KeySource::MasterPassword) => {
+                let password = current_password.ok_or_else(|| anyhow!("请输入当前主密码"))?;
+                Self::master_key(config, password)?
+            }
+            _ => Self::active_key()?,
+        };
+
+        let key_file = data_dir.join(KEY_FILE_NAME);
+        let (new_key, salt) = match new_password {
+            Some(password) => {
+                Self::validate_password(password)?;
+                let mut salt = [0u8; SALT_LEN];
+                rand::thread_rng().fill_bytes(&mut salt);
+                (Self::derive_key(password, &salt)?, Some(general_purpose::STANDARD.encode(salt)))
+            }
+            None => {
+                if current_source == KeySource::KeyFile {
+                    return Err(anyhow!("尚未设置主密码"));
+                }
+                // 先写入密钥文件，数据库事务失败时旧的主密码仍然有效
+                let key = Self::generate_key();
+                Self::write_key_file(&key_file, &key)?;
+                (key, None)
+            }
+        };
+
+        let new_config = EncryptionConfig {
+            key_source: if salt.is_some() { KeySource::MasterPassword } else { KeySource::KeyFile },
+            salt,
+            verifier: Self::encrypt_with_key(&new_key, KEY_CHECK)?,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        db.reencrypt_secrets(&new_config, |value| Self::reencrypt_value(value, Some(&old_key), &new_key))?;
+        Self::set_active_key(Some(new_key));
+
+        // 改用主密码后不再需要密钥文件
+        if new_config.key_source == KeySource::MasterPassword {
+            if let Err(e) = std::fs::remove_file(&key_file) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    eprintln!("删除密钥文件失败: {}", e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 生成新的密钥文件并清空所有账户凭据，用于密钥文件丢失、不匹配或忘记主密码的情况
+    ///
+    /// 账户密码需要重新输入，OAuth2 账户需要重新授权。原来的密钥文件改名为 `xmail.key.invalid` 保留。
+    pub fn reset_credentials(db: &Database, data_dir: &Path) -> Result<()> {
+        let key_file = data_dir.join(KEY_FILE_NAME);
+        if key_file.exists() {
+            std::fs::rename(&key_file, data_dir.join(format!("{}.invalid", KEY_FILE_NAME)))?;
+        }
+
+        let key = Self::generate_key();
+        Self::write_key_file(&key_file, &key)?;
+
+        let config = EncryptionConfig {
+            key_source: KeySource::KeyFile,
+            salt: None,
+            verifier: Self::encrypt_with_key(&key, KEY_CHECK)?,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        db.clear_secrets(&config, &Self::encrypt_with_key(&key, "")?)?;
+        Self::set_active_key(Some(key));
+        Ok(())
+    }
+
+    pub fn encryption_status(db: &Database) -> Result<EncryptionStatus> {
+        let key_source = db
+            .get_encryption_config()?
+            .map(|config| config.key_source)
+            .unwrap_or(KeySource::KeyFile);
+        let unlocked = Self::is_unlocked();
+
+        Ok(EncryptionStatus {
+            key_source,
+            unlocked,
+            // 密钥文件模式正常情况下启动时就会解锁
+            needs_reset: key_source == KeySource::KeyFile && !unlocked,
+        })
+    }
+
+    /// 从主密码派生密钥并校验
+    fn master_key(config: &EncryptionConfig, master_password: &str) -> Result<SecretKey> {
+        let salt = config
+            .salt
+            .as_deref()
+            .ok_or_else(|| anyhow!("加密配置缺少盐值"))?;
+        let salt = general_purpose::STANDARD.decode(salt)?;
+
+        let key = Self::derive_key(master_password, &salt)?;
+        Self::verify_key(&key, config).map_err(|_| anyhow!("主密码不正确"))?;
+        Ok(key)
+    }
+
+    fn verify_key(key: &SecretKey, config: &EncryptionConfig) -> Result<()> {
+        if Self::decrypt_with_key(key, &config.verifier)? == KEY_CHECK {
+            Ok(())
+        } else {
+            Err(anyhow!("密钥校验失败"))
+        }
+    }
+
+    /// 将旧版格式的凭据用新密钥重新加密，只在存在旧格式数据或尚无加密配置时执行
+    fn migrate_legacy_secrets(db: &Database, key: &SecretKey, key_source: KeySource, salt: Option<String>) -> Result<()> {
+        let has_config = db.get_encryption_config()?.is_some();
+        let has_legacy = db.conn.query_row(
+            "SELECT EXISTS (
+                SELECT 1 FROM email_accounts WHERE password NOT LIKE 'v1:%'
+                UNION ALL
+                SELECT 1 FROM oauth_tokens
+                WHERE access_token NOT LIKE 'v1:%' OR refresh_token NOT LIKE 'v1:%'
+            )",
+            [],
+            |row| row.get::<_, bool>(0),
+        )?;
+        if has_config && !has_legacy {
+            return Ok(());
+        }
+
+        let config = EncryptionConfig {
+            key_source,
+            salt,
+            verifier: Self::encrypt_with_key(key, KEY_CHECK)?,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        db.reencrypt_secrets(&config, |value| Self::reencrypt_value(value, None, key))
+    }
+
+    /// 用新密钥重新加密单个值。`old_key` 为 None 时只迁移旧版格式的值
+    ///
+    /// 无法识别的旧版数据保持原样，使用该账户时会提示解密失败，而不是阻止整个迁移。
+    fn reencrypt_value(value: &str, old_key: Option<&SecretKey>, new_key: &SecretKey) -> Result<String> {
+        if Self::is_legacy(value) {
+            return match Self::decrypt_legacy(value) {
+                Ok(plaintext) => Self::encrypt_with_key(new_key, &plaintext),
+                Err(e) => {
+                    eprintln!("无法迁移旧格式的账户凭据: {}", e);
+                    Ok(value.to_string())
+                }
+            };
+        }
+
+        match old_key {
+            Some(old_key) => Self::encrypt_with_key(new_key, &Self::decrypt_with_key(old_key, value)?),
+            None => Ok(value.to_string()),
+        }
+    }
+
+    /// 读取密钥文件，不存在时生成新的随机密钥
+    fn load_or_create_key_file(path: &Path) -> Result<SecretKey> {
+        if path.exists() {
+            return Self::read_key_file(path);
+        }
+
+        let key = Self::generate_key();
+        Self::write_key_file(path, &key)?;
+        Ok(key)
+    }
+
+    fn read_key_file(path: &Path) -> Result<SecretKey> {
+        let encoded = std::fs::read_to_string(path)?;
+        let bytes = general_purpose::STANDARD.decode(encoded.trim())?;
+        bytes
+            .try_into()
+            .map_err(|_| anyhow!("密钥文件格式不正确: {}", path.display()))
+    }
+
+    fn write_key_file(path: &Path, key: &SecretKey) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, general_purpose::STANDARD.encode(key))?;
+
+        // 密钥文件只允许当前用户读写
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+
+        Ok(())
+    }
+
     /// 验证密码格式
     pub fn validate_password(password: &str) -> Result<()> {
         if password.is_empty() {
@@ -58,12 +409,99 @@ impl CryptoService {
 mod tests {
     use super::*;
 
+    /// 当前密钥是全局状态，修改它的测试需要串行执行
+    static KEY_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
     #[test]
     fn test_password_encryption() {
+        let _guard = KEY_LOCK.lock().unwrap();
+        CryptoService::set_active_key(Some([7u8; KEY_LEN]));
+
         let password = "test123";
         let encrypted = CryptoService::encrypt_password(password).unwrap();
+        assert!(!encrypted.contains(password));
         let decrypted = CryptoService::decrypt_password(&encrypted).unwrap();
         assert_eq!(password, decrypted);
+
+        // 相同明文每次加密结果不同，错误的密钥和被篡改的密文都无法解密
+        assert_ne!(encrypted, CryptoService::encrypt_password(password).unwrap());
+        assert!(CryptoService::decrypt_with_key(&[8u8; KEY_LEN], &encrypted).is_err());
+        let mut tampered = encrypted.clone();
+        tampered.replace_range(10..11, if &encrypted[10..11] == "A" { "B" } else { "A" });
+        assert!(CryptoService::decrypt_password(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_legacy_migration_and_master_password() {
+        let _guard = KEY_LOCK.lock().unwrap();
+        let data_dir = std::env::temp_dir().join(format!("xmail-crypto-{}", uuid::Uuid::new_v4()));
+        let db = Database::new(":memory:").unwrap();
+
+        // 旧版本保存的 base64 混淆格式
+        let legacy = general_purpose::STANDARD.encode("xmail_secret-pass_secure");
+        db.conn.execute(
+            "INSERT INTO email_accounts (provider_id, email_address, display_name, username, password, is_active, created_at)
+             VALUES (1, 'a@example.com', 'A', 'a@example.com', ?1, 1, '2024-01-01T00:00:00Z')",
+            [&legacy],
+        ).unwrap();
+        let stored_password = || -> String {
+            db.conn.query_row("SELECT password FROM email_accounts", [], |row| row.get(0)).unwrap()
+        };
+
+        assert!(CryptoService::initialize(&db, &data_dir).unwrap());
+        assert!(data_dir.join(KEY_FILE_NAME).exists());
+        assert!(!CryptoService::is_legacy(&stored_password()));
+        assert_eq!(CryptoService::decrypt_password(&stored_password()).unwrap(), "secret-pass");
+
+        // 设置主密码后密钥文件被删除，重新启动时保持锁定
+        CryptoService::change_master_password(&db, &data_dir, None, Some("master-123")).unwrap();
+        assert!(!data_dir.join(KEY_FILE_NAME).exists());
+        assert!(!CryptoService::initialize(&db, &data_dir).unwrap());
+        assert!(CryptoService::decrypt_password(&stored_password()).is_err());
+
+        assert!(CryptoService::unlock(&db, "wrong-password").is_err());
+        CryptoService::unlock(&db, "master-123").unwrap();
+        assert_eq!(CryptoService::decrypt_password(&stored_password()).unwrap(), "secret-pass");
+
+        // 修改主密码需要提供当前主密码
+        assert!(CryptoService::change_master_password(&db, &data_dir, None, Some("master-456")).is_err());
+        CryptoService::change_master_password(&db, &data_dir, Some("master-123"), Some("master-456")).unwrap();
+        assert!(CryptoService::unlock(&db, "master-123").is_err());
+        CryptoService::unlock(&db, "master-456").unwrap();
+
+        // 移除主密码后改回密钥文件
+        CryptoService::change_master_password(&db, &data_dir, Some("master-456"), None).unwrap();
+        assert!(CryptoService::initialize(&db, &data_dir).unwrap());
+        assert_eq!(CryptoService::decrypt_password(&stored_password()).unwrap(), "secret-pass");
+
+        std::fs::remove_dir_all(&data_dir).ok();
+    }
+
+    #[test]
+    fn test_lost_key_file_locks_until_reset() {
+        let _guard = KEY_LOCK.lock().unwrap();
+        let data_dir = std::env::temp_dir().join(format!("xmail-crypto-{}", uuid::Uuid::new_v4()));
+        let db = Database::new(":memory:").unwrap();
+        assert!(CryptoService::initialize(&db, &data_dir).unwrap());
+        db.conn.execute(
+            "INSERT INTO email_accounts (provider_id, email_address, display_name, username, password, is_active, created_at)
+             VALUES (1, 'a@example.com', 'A', 'a@example.com', ?1, 1, '2024-01-01T00:00:00Z')",
+            [CryptoService::encrypt_password("secret-pass").unwrap()],
+        ).unwrap();
+
+        // 密钥文件丢失时不生成新密钥，启动后保持锁定
+        std::fs::remove_file(data_dir.join(KEY_FILE_NAME)).unwrap();
+        assert!(!CryptoService::initialize(&db, &data_dir).unwrap());
+        assert!(!data_dir.join(KEY_FILE_NAME).exists());
+        assert!(CryptoService::encryption_status(&db).unwrap().needs_reset);
+
+        CryptoService::reset_credentials(&db, &data_dir).unwrap();
+        assert!(!CryptoService::encryption_status(&db).unwrap().needs_reset);
+        let password: String = db.conn.query_row("SELECT password FROM email_accounts", [], |row| row.get(0)).unwrap();
+        assert_eq!(CryptoService::decrypt_password(&password).unwrap(), "");
+        assert!(CryptoService::initialize(&db, &data_dir).unwrap());
+
+        std::fs::remove_dir_all(&data_dir).ok();
     }
 
     #[test]
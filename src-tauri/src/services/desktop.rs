@@ -0,0 +1,29 @@
+use anyhow::Result;
+
+/// 使用系统默认程序打开网址或本地文件
+pub fn open_external(target: &str) -> Result<()> {
+    use std::process::Command;
+
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        // cmd 的 start 会把 URL 中的 & 当作命令分隔符
+        let mut command = Command::new("rundll32");
+        command.args(["url.dll,FileProtocolHandler", target]);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("open");
+        command.arg(target);
+        command
+    };
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = {
+        let mut command = Command::new("xdg-open");
+        command.arg(target);
+        command
+    };
+
+    command.spawn()?;
+    Ok(())
+}
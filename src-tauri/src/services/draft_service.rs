@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Result};
+use std::sync::Mutex;
+use crate::database::Database;
+use crate::models::draft::Draft;
+use crate::models::folder::SPECIAL_USE_DRAFTS;
+use crate::models::outgoing::OutgoingMessage;
+use crate::services::blob_store::BlobStore;
+use crate::services::compose;
+use crate::services::provider_service::ProviderService;
+use crate::services::sync_service::{generate_message_id, EmailSyncService, SyncManager};
+
+/// 新建草稿并上传到发件账户的草稿箱
+///
+/// 上传失败不影响本地保存，原因记录在返回草稿的 `server_error` 中。
+pub async fn create_draft(
+    db: &Mutex<Database>,
+    blobs: &BlobStore,
+    account_id: Option<i32>,
+    source_email_id: Option<String>,
+    message: OutgoingMessage,
+) -> Result<Draft> {
+    let draft = {
+        let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+        let address = match account_id {
+            Some(account_id) => ProviderService::new(&db.conn)
+                .get_account(account_id)?
+                .map(|account| account.email_address),
+            None => None,
+        };
+
+        let draft = Draft::new(
+            account_id,
+            source_email_id,
+            message,
+            generate_message_id(address.as_deref().unwrap_or_default()),
+        );
+        db.insert_draft(&draft)?;
+        draft
+    };
+
+    sync_draft(db, blobs, draft, None).await
+}
+
+/// 保存草稿的新版本并替换服务器上的副本
+///
+/// `version` 为编辑所基于的版本，草稿已被更新的版本覆盖时返回错误。
+pub async fn update_draft(
+    db: &Mutex<Database>,
+    blobs: &BlobStore,
+    draft_id: &str,
+    version: i64,
+    account_id: Option<i32>,
+    message: &OutgoingMessage,
+) -> Result<Draft> {
+    let (previous, draft) = {
+        let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+        let previous = db.get_draft(draft_id)?.ok_or_else(|| anyhow!("草稿不存在"))?;
+        let draft = db.update_draft(draft_id, version, account_id, message)?;
+        (previous, draft)
+    };
+
+    sync_draft(db, blobs, draft, Some(previous)).await
+}
+
+/// 删除本地草稿，并尽量删除服务器上的副本（邮件发出后或放弃草稿时调用）
+///
+/// 服务器副本删除失败只记录日志，留在服务器草稿箱中的副本可以手动删除。
+pub async fn delete_draft(db: &Mutex<Database>, draft_id: &str) -> Result<()> {
+    let draft = {
+        let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+        let draft = db.get_draft(draft_id)?.ok_or_else(|| anyhow!("草稿不存在"))?;
+        db.delete_draft(draft_id)?;
+        draft
+    };
+
+    if let Err(e) = delete_server_copy(db, &draft).await {
+        eprintln!("删除服务器上的草稿失败: {}", e);
+    }
+    Ok(())
+}
+
+/// 把草稿的当前版本上传到发件账户的草稿箱，并记录上传结果
+///
+/// 更换了发件账户时，先删除原账户中的副本。
+async fn sync_draft(db: &Mutex<Database>, blobs: &BlobStore, draft: Draft, previous: Option<Draft>) -> Result<Draft> {
+    if let Some(previous) = previous.filter(|p| p.account_id != draft.account_id && p.server_folder.is_some()) {
+        if let Err(e) = delete_server_copy(db, &previous).await {
+            eprintln!("删除原账户中的草稿失败: {}", e);
+        } else {
+            let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+            db.clear_draft_server_copy(&draft.id)?;
+        }
+    }
+
+    let result = upload(db, blobs, &draft).await;
+
+    let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+    match result {
+        Ok(Some(folder)) => db.record_draft_upload(&draft.id, draft.version, &folder)?,
+        Ok(None) => {}
+        Err(e) => db.record_draft_error(&draft.id, &e.to_string())?,
+    }
+    db.get_draft(&draft.id)?.ok_or_else(|| anyhow!("草稿不存在"))
+}
+
+/// 上传草稿，返回所在的文件夹；本地草稿或账户没有草稿箱时返回 None
+async fn upload(db: &Mutex<Database>, blobs: &BlobStore, draft: &Draft) -> Result<Option<String>> {
+    let account_id = match draft.account_id {
+        Some(account_id) => account_id,
+        None => return Ok(None),
+    };
+    let folder = match drafts_folder(db, account_id)? {
+        Some(folder) => folder,
+        None => return Ok(None),
+    };
+
+    let sync_service = EmailSyncService::for_account(db, account_id).await?;
+    let attachments = compose::load_attachments(&draft.message, blobs)?;
+    let content = sync_service.build_draft(&draft.message, &draft.message_id, &attachments)?;
+
+    SyncManager::new()
+        .upload_draft(sync_service, &folder, &draft.message_id, &content)
+        .await?;
+    Ok(Some(folder))
+}
+
+async fn delete_server_copy(db: &Mutex<Database>, draft: &Draft) -> Result<()> {
+    let (account_id, folder) = match (draft.account_id, &draft.server_folder) {
+        (Some(account_id), Some(folder)) => (account_id, folder),
+        _ => return Ok(()),
+    };
+
+    let sync_service = EmailSyncService::for_account(db, account_id).await?;
+    SyncManager::new()
+        .delete_server_draft(sync_service, folder, &draft.message_id)
+        .await
+}
+
+/// 账户同步时发现的草稿箱文件夹
+fn drafts_folder(db: &Mutex<Database>, account_id: i32) -> Result<Option<String>> {
+    let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+    Ok(db
+        .get_folders(account_id)?
+        .into_iter()
+        .find(|folder| folder.special_use.as_deref() == Some(SPECIAL_USE_DRAFTS))
+        .map(|folder| folder.name))
+}
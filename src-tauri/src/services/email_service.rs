@@ -38,6 +38,7 @@ impl EmailService {
         self.db.search_emails(&filter)
     }
 
+    /// 只修改本地状态；同步到服务器的已读标记由 `mark_email_as_read` 命令加入离线队列并推送
     pub fn mark_email_as_read(&self, id: &str) -> Result<()> {
         if let Some(mut email) = self.db.get_email_by_id(id)? {
             email.mark_as_read();
@@ -46,6 +47,7 @@ impl EmailService {
         Ok(())
     }
 
+    /// 只修改本地状态；同步到服务器的重要标记由 `mark_email_as_important` 命令加入离线队列并推送
     pub fn mark_email_as_important(&self, id: &str) -> Result<()> {
         if let Some(mut email) = self.db.get_email_by_id(id)? {
             // Toggle important status instead of forcing true
@@ -109,22 +111,6 @@ impl EmailService {
     }
 
     // 批量操作功能
-    #[allow(dead_code)]
-    pub fn batch_mark_as_read(&self, ids: &[String]) -> Result<()> {
-        for id in ids {
-            self.mark_email_as_read(id)?;
-        }
-        Ok(())
-    }
-
-    #[allow(dead_code)]
-    pub fn batch_mark_as_important(&self, ids: &[String]) -> Result<()> {
-        for id in ids {
-            self.mark_email_as_important(id)?;
-        }
-        Ok(())
-    }
-
     #[allow(dead_code)]
     pub fn batch_delete(&self, ids: &[String]) -> Result<()> {
         for id in ids {
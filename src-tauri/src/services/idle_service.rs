@@ -0,0 +1,269 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use crate::database::Database;
+use crate::models::email::EmailSummary;
+use crate::services::provider_service::ProviderService;
+use crate::models::folder::MailFolder;
+use crate::services::blob_store::BlobStore;
+use crate::services::sync_service::{EmailSyncService, ImapSession, SyncExtensions, SyncManager};
+
+/// 新邮件事件
+pub const NEW_MAIL_EVENT: &str = "mail://new";
+
+/// 邮件标记或删除状态发生变化（没有新邮件）
+pub const MAIL_UPDATED_EVENT: &str = "mail://updated";
+
+/// 服务器通常在 30 分钟无活动后断开 IDLE，需在此之前重新发送
+const IDLE_KEEPALIVE: Duration = Duration::from_secs(29 * 60);
+
+/// 服务器不支持 IDLE 时的轮询间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// NOTIFY（RFC 5465）：在 IDLE 期间同时接收其他文件夹的新邮件通知
+const NOTIFY_COMMAND: &str =
+    "NOTIFY SET (selected MessageNew MessageExpunge FlagChange) (personal MessageNew MessageExpunge)";
+
+/// `mail://new` 事件内容
+#[derive(Debug, Clone, Serialize)]
+pub struct NewMailEvent {
+    pub account_id: i32,
+    pub emails: Vec<EmailSummary>,
+}
+
+/// 单个账户的后台监听任务
+struct IdleWatcher {
+    stop: Arc<AtomicBool>,
+    /// 当前连接的 TCP 副本，停止时关闭以打断阻塞中的 IDLE
+    socket: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl IdleWatcher {
+    fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Ok(socket) = self.socket.lock() {
+            if let Some(socket) = socket.as_ref() {
+                socket.shutdown(Shutdown::Both).ok();
+            }
+        }
+    }
+}
+
+/// 管理所有账户的 IMAP IDLE 推送
+pub struct IdleManager {
+    watchers: Mutex<HashMap<i32, IdleWatcher>>,
+}
+
+impl IdleManager {
+    pub fn new() -> Self {
+        Self {
+            watchers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 为所有已启用的账户启动监听
+    pub fn start_active_accounts(&self, app: &AppHandle) -> Result<()> {
+        let account_ids: Vec<i32> = {
+            let db_state = app.state::<Mutex<Database>>();
+            let db = db_state.lock().unwrap();
+            let service = ProviderService::new(&db.conn);
+            service
+                .get_active_accounts()?
+                .into_iter()
+                .map(|account| account.id)
+                .collect()
+        };
+
+        for account_id in account_ids {
+            self.start(app.clone(), account_id);
+        }
+        Ok(())
+    }
+
+    /// 为账户启动监听，已在运行时不做任何事
+    pub fn start(&self, app: AppHandle, account_id: i32) {
+        let mut watchers = self.watchers.lock().unwrap();
+        if let Some(watcher) = watchers.get(&account_id) {
+            if !watcher.stop.load(Ordering::SeqCst) {
+                return;
+            }
+        }
+
+        let watcher = IdleWatcher {
+            stop: Arc::new(AtomicBool::new(false)),
+            socket: Arc::new(Mutex::new(None)),
+        };
+        let task = IdleTask {
+            app,
+            account_id,
+            stop: watcher.stop.clone(),
+            socket: watcher.socket.clone(),
+        };
+
+        // imap crate 是阻塞 API，每个账户使用独立线程
+        std::thread::spawn(move || task.run());
+        watchers.insert(account_id, watcher);
+    }
+
+    /// 停止账户的监听并关闭连接
+    pub fn stop(&self, account_id: i32) {
+        if let Some(watcher) = self.watchers.lock().unwrap().remove(&account_id) {
+            watcher.stop();
+        }
+    }
+}
+
+struct IdleTask {
+    app: AppHandle,
+    account_id: i32,
+    stop: Arc<AtomicBool>,
+    socket: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl IdleTask {
+    fn run(self) {
+        let mut delay = INITIAL_RECONNECT_DELAY;
+
+        while !self.stopped() {
+            if let Err(e) = self.watch(&mut delay) {
+                eprintln!("账户 {} 的 IDLE 连接中断: {}", self.account_id, e);
+            }
+            self.socket.lock().unwrap().take();
+
+            // 连接断开后按指数退避重连
+            if !self.sleep(delay) {
+                break;
+            }
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+
+    /// 分段休眠以便及时响应停止请求，被停止时返回 false
+    fn sleep(&self, duration: Duration) -> bool {
+        let mut remaining = duration;
+        while !remaining.is_zero() {
+            if self.stopped() {
+                return false;
+            }
+            let step = remaining.min(Duration::from_secs(1));
+            std::thread::sleep(step);
+            remaining -= step;
+        }
+        !self.stopped()
+    }
+
+    /// 建立连接并持续等待服务器推送，连接出错时返回
+    fn watch(&self, reconnect_delay: &mut Duration) -> Result<()> {
+        let db_state = self.app.state::<Mutex<Database>>();
+        let sync_service = tauri::async_runtime::block_on(EmailSyncService::for_account(&db_state, self.account_id))?;
+        if !sync_service.account().is_active {
+            self.stop.store(true, Ordering::SeqCst);
+            return Ok(());
+        }
+
+        let (mut session, socket) = tauri::async_runtime::block_on(sync_service.connect_imap_with_socket())?;
+        *self.socket.lock().unwrap() = Some(socket);
+        if self.stopped() {
+            session.logout().ok();
+            return Ok(());
+        }
+        *reconnect_delay = INITIAL_RECONNECT_DELAY;
+
+        let (supports_idle, supports_notify) = match session.capabilities() {
+            Ok(caps) => (caps.has_str("IDLE"), caps.has_str("NOTIFY")),
+            Err(_) => (false, false),
+        };
+        let extensions = sync_service.detect_extensions(&mut session);
+
+        // 重新连接后先刷新文件夹列表并补齐断线期间的变化
+        let folders = sync_service.discover_folders(&mut session)?;
+        self.sync(&sync_service, &mut session, &folders, true, extensions)?;
+
+        // 支持 NOTIFY 时所有文件夹的变化都会推送过来，否则只能监听收件箱
+        let notify = supports_notify && session.run_command_and_read_response(NOTIFY_COMMAND).is_ok();
+        let watched: Vec<MailFolder> = if notify {
+            folders
+        } else {
+            folders.into_iter().filter(|folder| folder.is_inbox()).collect()
+        };
+
+        while !self.stopped() {
+            session.select("INBOX")?;
+
+            if supports_idle {
+                let mut idle = session.idle()?;
+                idle.set_keepalive(IDLE_KEEPALIVE);
+                idle.wait_keepalive()?;
+            } else {
+                if !self.sleep(POLL_INTERVAL) {
+                    break;
+                }
+                session.noop()?;
+            }
+
+            if self.stopped() {
+                break;
+            }
+            self.sync(&sync_service, &mut session, &watched, false, extensions)?;
+        }
+
+        session.logout().ok();
+        Ok(())
+    }
+
+    /// 同步指定文件夹并通知前端，`discovered` 表示 `folders` 是完整的文件夹列表
+    fn sync(
+        &self,
+        sync_service: &EmailSyncService,
+        session: &mut ImapSession,
+        folders: &[MailFolder],
+        discovered: bool,
+        extensions: SyncExtensions,
+    ) -> Result<()> {
+        let db_state = self.app.state::<Mutex<Database>>();
+
+        let (states, pending) = {
+            let db = db_state.lock().unwrap();
+            (db.get_sync_states(self.account_id)?, db.get_pending_operations(self.account_id)?)
+        };
+
+        let mut result = sync_service.sync_folders(session, folders, &states, &pending, extensions);
+        if discovered {
+            result.discovered_folders = Some(folders.to_vec());
+        }
+        let changed = result.folders.iter().any(|folder| {
+            folder.uid_validity_changed || !folder.flag_updates.is_empty() || !folder.vanished.is_empty()
+        });
+
+        let saved = {
+            let db = db_state.lock().unwrap();
+            let blobs = self.app.state::<BlobStore>();
+            let saved = SyncManager::save_sync_result(&db, &blobs, self.account_id, result)?;
+            ProviderService::new(&db.conn).update_account_sync_time(self.account_id)?;
+            saved
+        };
+
+        if !saved.is_empty() {
+            let event = NewMailEvent {
+                account_id: self.account_id,
+                emails: saved.iter().map(EmailSummary::from).collect(),
+            };
+            self.app.emit(NEW_MAIL_EVENT, event).ok();
+        } else if changed {
+            self.app.emit(MAIL_UPDATED_EVENT, self.account_id).ok();
+        }
+        Ok(())
+    }
+}
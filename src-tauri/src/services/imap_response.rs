@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::services::mime_parser;
+
+/// 服务器返回的单封邮件标记
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlagUpdate {
+    pub uid: u32,
+    pub is_read: bool,
+    pub is_important: bool,
+}
+
+/// 从原始响应中收集到的未标记（untagged）数据
+#[derive(Debug, Default)]
+pub struct UntaggedData {
+    pub exists: Option<u32>,
+    pub uid_validity: Option<u32>,
+    pub highest_modseq: Option<u64>,
+    /// 服务器返回 `[NOMODSEQ]`，表示该文件夹不支持 MODSEQ
+    pub no_modseq: bool,
+    /// `VANISHED` 响应中的 UID 区间（闭区间）
+    pub vanished: Vec<(u32, u32)>,
+    pub flag_updates: Vec<FlagUpdate>,
+    /// FETCH 响应中的 `X-GM-THRID`，按 UID 索引
+    pub gmail_thread_ids: HashMap<u32, String>,
+}
+
+/// 解析 SELECT / FETCH / STATUS 等命令返回的原始字节
+pub fn parse_untagged(raw: &[u8]) -> UntaggedData {
+    let text = String::from_utf8_lossy(raw);
+    let mut data = UntaggedData::default();
+
+    let exists_re = regex::Regex::new(r"^\* (\d+) EXISTS").unwrap();
+    let uid_validity_re = regex::Regex::new(r"(?i)\[UIDVALIDITY (\d+)\]").unwrap();
+    let modseq_re = regex::Regex::new(r"(?i)\[HIGHESTMODSEQ (\d+)\]|HIGHESTMODSEQ (\d+)\)").unwrap();
+    let vanished_re = regex::Regex::new(r"(?i)^\* VANISHED (?:\(EARLIER\) )?([\d:,]+)").unwrap();
+    let fetch_re = regex::Regex::new(r"(?i)^\* \d+ FETCH \((.*)\)$").unwrap();
+    let uid_re = regex::Regex::new(r"(?i)\bUID (\d+)").unwrap();
+    let gm_thread_re = regex::Regex::new(r"(?i)\bX-GM-THRID (\d+)").unwrap();
+
+    for line in text.lines() {
+        let line = line.trim_end();
+
+        if let Some(caps) = exists_re.captures(line) {
+            data.exists = caps[1].parse().ok();
+        }
+        if let Some(caps) = uid_validity_re.captures(line) {
+            data.uid_validity = caps[1].parse().ok();
+        }
+        if let Some(caps) = modseq_re.captures(line) {
+            data.highest_modseq = caps.get(1).or(caps.get(2)).and_then(|m| m.as_str().parse().ok());
+        }
+        if line.to_uppercase().contains("[NOMODSEQ]") {
+            data.no_modseq = true;
+        }
+        if let Some(caps) = vanished_re.captures(line) {
+            data.vanished.extend(parse_uid_set(&caps[1]));
+        }
+        if let Some(caps) = fetch_re.captures(line) {
+            if let Some(update) = parse_fetch_flags(&caps[1]) {
+                data.flag_updates.push(update);
+            }
+            if let (Some(uid), Some(thread_id)) = (uid_re.captures(&caps[1]), gm_thread_re.captures(&caps[1])) {
+                if let Ok(uid) = uid[1].parse() {
+                    data.gmail_thread_ids.insert(uid, thread_id[1].to_string());
+                }
+            }
+        }
+    }
+
+    data
+}
+
+/// 解析 FETCH 响应中的 `UID n FLAGS (...)`，缺少任何一项时返回 None
+fn parse_fetch_flags(items: &str) -> Option<FlagUpdate> {
+    let uid_re = regex::Regex::new(r"(?i)\bUID (\d+)").unwrap();
+    let flags_re = regex::Regex::new(r"(?i)\bFLAGS \(([^)]*)\)").unwrap();
+
+    let uid = uid_re.captures(items)?[1].parse().ok()?;
+    let flags = flags_re.captures(items)?[1].to_lowercase();
+    let flags: Vec<&str> = flags.split_whitespace().collect();
+
+    Some(FlagUpdate {
+        uid,
+        is_read: flags.contains(&"\\seen"),
+        is_important: flags.contains(&"\\flagged"),
+    })
+}
+
+/// 解析 `1:3,5,9:*` 形式的 UID 集合（`*` 视为 u32::MAX）
+pub fn parse_uid_set(set: &str) -> Vec<(u32, u32)> {
+    let parse = |s: &str| {
+        if s == "*" {
+            Some(u32::MAX)
+        } else {
+            s.parse::<u32>().ok()
+        }
+    };
+
+    set.split(',')
+        .filter_map(|item| match item.split_once(':') {
+            Some((a, b)) => {
+                let (a, b) = (parse(a)?, parse(b)?);
+                Some((a.min(b), a.max(b)))
+            }
+            None => parse(item).map(|uid| (uid, uid)),
+        })
+        .collect()
+}
+
+/// 计算 `1..=up_to` 中不在 `existing` 里的 UID 区间
+pub fn missing_uid_ranges(existing: &[u32], up_to: u32) -> Vec<(u32, u32)> {
+    let mut uids: Vec<u32> = existing.iter().copied().filter(|uid| *uid <= up_to).collect();
+    uids.sort_unstable();
+    uids.dedup();
+
+    let mut ranges = Vec::new();
+    let mut next = 1u32;
+    for uid in uids {
+        if uid > next {
+            ranges.push((next, uid - 1));
+        }
+        next = uid.saturating_add(1);
+    }
+    if up_to >= next {
+        ranges.push((next, up_to));
+    }
+    ranges
+}
+
+/// 为 IMAP 命令引用字符串（邮箱名称、ID 参数等）
+pub fn quote_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// 通过 IMAP ID 命令（RFC 2971）上报的客户端信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImapClientId {
+    pub name: String,
+    pub version: String,
+    pub vendor: String,
+}
+
+impl Default for ImapClientId {
+    fn default() -> Self {
+        Self {
+            name: "XMail".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            vendor: "XMail".to_string(),
+        }
+    }
+}
+
+impl ImapClientId {
+    /// 生成 `ID ("name" "..." "version" "..." "vendor" "...")` 命令
+    pub fn command(&self) -> String {
+        let fields = [
+            ("name", &self.name),
+            ("version", &self.version),
+            ("vendor", &self.vendor),
+        ];
+
+        let params: Vec<String> = fields
+            .iter()
+            .map(|(key, value)| format!("{} {}", quote_string(key), quote_string(value)))
+            .collect();
+        format!("ID ({})", params.join(" "))
+    }
+}
+
+/// 解码修改版 UTF-7 编码的邮箱名称（RFC 3501 5.1.3），例如 `&XfJT0ZAB-` 解码为 `已发送`
+///
+/// 无法解码的片段原样保留。
+pub fn decode_mailbox_name(name: &str) -> String {
+    let mut output = String::new();
+    let mut rest = name;
+
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let end = match after.find('-') {
+            Some(end) => end,
+            None => {
+                output.push_str(&rest[start..]);
+                return output;
+            }
+        };
+
+        let encoded = &after[..end];
+        if encoded.is_empty() {
+            // `&-` 表示字面量 `&`
+            output.push('&');
+        } else {
+            match decode_modified_base64(encoded) {
+                Some(text) => output.push_str(&text),
+                None => output.push_str(&rest[start..start + end + 2]),
+            }
+        }
+
+        rest = &after[end + 1..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// 修改版 base64 使用 `,` 代替 `/`，内容为 UTF-16BE
+fn decode_modified_base64(encoded: &str) -> Option<String> {
+    let bytes = mime_parser::decode_base64(encoded.replace(',', "/").as_bytes())?;
+    if bytes.len() % 2 != 0 {
+        return None;
+    }
+
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_qresync_select() {
+        let raw = b"* 172 EXISTS\r\n\
+* OK [UIDVALIDITY 3857529045] UIDs valid\r\n\
+* OK [HIGHESTMODSEQ 715194045007] Highest\r\n\
+* VANISHED (EARLIER) 41,43:116,118\r\n\
+* 49 FETCH (UID 117 FLAGS (\\Seen \\Answered) MODSEQ (90060115194045001))\r\n\
+* 50 FETCH (MODSEQ (90060115194045002) FLAGS (\\Flagged) UID 119)\r\n\
+A02 OK [READ-WRITE] Sorry, UIDVALIDITY mismatch\r\n";
+        let data = parse_untagged(raw);
+        assert_eq!(data.exists, Some(172));
+        assert_eq!(data.uid_validity, Some(3857529045));
+        assert_eq!(data.highest_modseq, Some(715194045007));
+        assert_eq!(data.vanished, vec![(41, 41), (43, 116), (118, 118)]);
+        assert_eq!(
+            data.flag_updates,
+            vec![
+                FlagUpdate { uid: 117, is_read: true, is_important: false },
+                FlagUpdate { uid: 119, is_read: false, is_important: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_gmail_thread_ids() {
+        let data = parse_untagged(b"* 12 FETCH (X-GM-THRID 1278455344230334865 UID 120)\r\nA03 OK Success\r\n");
+        assert_eq!(data.gmail_thread_ids.get(&120).map(String::as_str), Some("1278455344230334865"));
+        assert!(data.flag_updates.is_empty());
+    }
+
+    #[test]
+    fn test_parse_status_modseq() {
+        let data = parse_untagged(b"* STATUS \"INBOX\" (HIGHESTMODSEQ 42)\r\n");
+        assert_eq!(data.highest_modseq, Some(42));
+    }
+
+    #[test]
+    fn test_missing_uid_ranges() {
+        assert_eq!(missing_uid_ranges(&[2, 3, 7], 9), vec![(1, 1), (4, 6), (8, 9)]);
+        assert_eq!(missing_uid_ranges(&[1, 2, 3], 3), vec![]);
+        assert_eq!(missing_uid_ranges(&[], 0), vec![]);
+    }
+
+    #[test]
+    fn test_client_id_command() {
+        let id = ImapClientId {
+            name: "XMail".to_string(),
+            version: "1.0".to_string(),
+            vendor: "Say \"hi\"".to_string(),
+        };
+        assert_eq!(
+            id.command(),
+            r#"ID ("name" "XMail" "version" "1.0" "vendor" "Say \"hi\"")"#
+        );
+    }
+
+    #[test]
+    fn test_decode_mailbox_name() {
+        assert_eq!(decode_mailbox_name("&XfJT0ZAB-"), "已发送");
+        assert_eq!(decode_mailbox_name("&g0l6P3ux-"), "草稿箱");
+        assert_eq!(decode_mailbox_name("INBOX/&V4NXPpCuTvY-"), "INBOX/垃圾邮件");
+        assert_eq!(decode_mailbox_name("[Gmail]/Sent Mail"), "[Gmail]/Sent Mail");
+        assert_eq!(decode_mailbox_name("Tom &- Jerry"), "Tom & Jerry");
+        assert_eq!(decode_mailbox_name("broken &XfJ"), "broken &XfJ");
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{Result, anyhow};
+use imap::extensions::idle::SetReadTimeout;
+use native_tls::TlsStream;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// IMAP 连接的底层流，根据服务商配置的加密方式可能是 TLS 或明文
+#[derive(Debug)]
+pub enum ImapStream {
+    Tls(TlsStream<TcpStream>),
+    /// 未加密的连接，仅用于本地测试服务器
+    Plain(TcpStream),
+}
+
+impl Read for ImapStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            ImapStream::Tls(stream) => stream.read(buf),
+            ImapStream::Plain(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for ImapStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            ImapStream::Tls(stream) => stream.write(buf),
+            ImapStream::Plain(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            ImapStream::Tls(stream) => stream.flush(),
+            ImapStream::Plain(stream) => stream.flush(),
+        }
+    }
+}
+
+/// IDLE 需要设置读超时才能定期发送保活
+impl SetReadTimeout for ImapStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> imap::error::Result<()> {
+        let socket = match self {
+            ImapStream::Tls(stream) => stream.get_ref(),
+            ImapStream::Plain(stream) => stream,
+        };
+        socket.set_read_timeout(timeout).map_err(imap::error::Error::Io)
+    }
+}
+
+/// 在明文连接上读取问候并执行 STARTTLS（RFC 3501 6.2.1）
+///
+/// 升级为 TLS 后服务器不会再发送问候，调用方不能再读取一次。
+pub fn imap_starttls(stream: &TcpStream) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut writer = stream;
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("* OK") {
+        return Err(anyhow!("IMAP服务器问候异常: {}", line.trim_end()));
+    }
+
+    writer.write_all(b"x0 STARTTLS\r\n")?;
+    writer.flush()?;
+
+    // 跳过 STARTTLS 之前可能出现的未标记响应，直到收到带标签的结果
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(anyhow!("IMAP服务器在 STARTTLS 时关闭了连接"));
+        }
+        if let Some(status) = line.strip_prefix("x0 ") {
+            if status.to_ascii_uppercase().starts_with("OK") {
+                return Ok(());
+            }
+            return Err(anyhow!("IMAP服务器拒绝 STARTTLS: {}", status.trim_end()));
+        }
+    }
+}
@@ -0,0 +1,788 @@
+use anyhow::{Result, anyhow};
+use base64::{Engine as _, alphabet};
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::models::address::EmailAddress;
+use crate::services::charset;
+
+/// multipart 嵌套的最大深度，防止恶意邮件造成过深递归
+const MAX_DEPTH: usize = 16;
+
+/// 宽松的 base64 解码器：容忍缺失或多余的填充以及末尾多余的比特
+const LENIENT_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+        .with_decode_allow_trailing_bits(true),
+);
+
+/// Content-Type 头部
+#[derive(Debug, Clone)]
+pub struct ContentType {
+    /// 小写的 MIME 类型，例如 `text/plain`
+    pub mime_type: String,
+    /// 参数表，键为小写
+    pub params: HashMap<String, String>,
+}
+
+impl ContentType {
+    pub fn parse(value: &str) -> Self {
+        let (value, params) = parse_header_params(value);
+        let mime_type = if value.contains('/') {
+            value.to_lowercase()
+        } else {
+            // 无法识别的类型按 RFC 2045 视为 text/plain
+            "text/plain".to_string()
+        };
+        Self { mime_type, params }
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(&name.to_lowercase()).map(|s| s.as_str())
+    }
+
+    pub fn is_multipart(&self) -> bool {
+        self.mime_type.starts_with("multipart/")
+    }
+}
+
+impl Default for ContentType {
+    fn default() -> Self {
+        Self {
+            mime_type: "text/plain".to_string(),
+            params: HashMap::new(),
+        }
+    }
+}
+
+/// 邮件中的一个 MIME 部分
+#[derive(Debug, Clone)]
+pub struct MimePart {
+    /// 已展开折叠行的头部，保持原始顺序
+    pub headers: Vec<(String, String)>,
+    pub content_type: ContentType,
+    /// 已按 Content-Transfer-Encoding 解码的内容（multipart 部分为空）
+    pub body: Vec<u8>,
+    pub children: Vec<MimePart>,
+}
+
+impl MimePart {
+    /// 按名称查找头部（不区分大小写），返回第一个匹配值
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Content-Disposition 为 attachment 的部分不作为正文显示
+    pub fn is_attachment(&self) -> bool {
+        self.header("Content-Disposition")
+            .map(|v| parse_header_params(v).0.eq_ignore_ascii_case("attachment"))
+            .unwrap_or(false)
+    }
+
+    /// 按 charset 参数将内容解码为文本
+    pub fn decoded_text(&self) -> String {
+        charset::decode_bytes(&self.body, self.content_type.param("charset"))
+    }
+
+    /// 附件文件名，依次查找 Content-Disposition 的 filename 和 Content-Type 的 name 参数
+    pub fn filename(&self) -> Option<String> {
+        let disposition = self.header("Content-Disposition").map(|v| parse_header_params(v).1);
+
+        disposition
+            .as_ref()
+            .and_then(|params| decode_param(params, "filename"))
+            .or_else(|| decode_param(&self.content_type.params, "name"))
+            .filter(|name| !name.trim().is_empty())
+    }
+}
+
+/// 邮件中的附件，包括 HTML 正文通过 `cid:` 引用的内嵌资源
+#[derive(Debug, Clone)]
+pub struct AttachmentPart {
+    pub filename: String,
+    pub mime_type: String,
+    /// Content-ID（不含尖括号）
+    pub content_id: Option<String>,
+    /// 带有 Content-ID 且不是 `attachment` 的内嵌资源，例如签名中的图片
+    pub is_inline: bool,
+    pub data: Vec<u8>,
+}
+
+impl AttachmentPart {
+    fn from_part(part: &MimePart) -> Self {
+        let content_id = part.header("Content-ID").and_then(parse_message_id);
+        let is_inline = content_id.is_some() && !part.is_attachment();
+        let mime_type = part.content_type.mime_type.clone();
+
+        Self {
+            filename: part.filename().unwrap_or_else(|| default_filename(&mime_type)),
+            mime_type,
+            content_id,
+            is_inline,
+            data: part.body.clone(),
+        }
+    }
+}
+
+/// 解析后的完整邮件
+#[derive(Debug, Clone)]
+pub struct ParsedMessage {
+    pub root: MimePart,
+    pub text_body: Option<String>,
+    pub html_body: Option<String>,
+    pub attachments: Vec<AttachmentPart>,
+}
+
+impl ParsedMessage {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.root.header(name)
+    }
+
+    /// 解码 RFC 2047 编码字后的头部值
+    pub fn decoded_header(&self, name: &str) -> Option<String> {
+        self.header(name).map(charset::decode_encoded_words)
+    }
+
+    /// 解析地址列表头部（From、To、Cc 等），显示名称中的编码字会被解码
+    pub fn addresses(&self, name: &str) -> Vec<EmailAddress> {
+        self.header(name)
+            .map(EmailAddress::parse_list)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|address| {
+                let name = address.name.as_deref().map(charset::decode_encoded_words);
+                EmailAddress::new(name, address.address)
+            })
+            .collect()
+    }
+
+    /// Date 头部表示的发送时间
+    pub fn date(&self) -> Option<DateTime<Utc>> {
+        self.header("Date").and_then(parse_date)
+    }
+
+    /// 纯文本正文；只有 HTML 时由 HTML 转换得到
+    pub fn plain_text(&self) -> String {
+        match (&self.text_body, &self.html_body) {
+            (Some(text), _) => text.clone(),
+            (None, Some(html)) => html_to_text(html),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// 是否包含普通附件（不计内嵌资源）
+    pub fn has_attachment(&self) -> bool {
+        self.attachments.iter().any(|attachment| !attachment.is_inline)
+    }
+}
+
+/// 解析原始 RFC 822 邮件
+pub fn parse_message(raw: &[u8]) -> Result<ParsedMessage> {
+    if raw.iter().all(|b| b.is_ascii_whitespace()) {
+        return Err(anyhow!("邮件内容为空"));
+    }
+
+    let root = parse_part(raw, &ContentType::default(), 0);
+
+    let mut text_body = None;
+    let mut html_body = None;
+    let mut attachments = Vec::new();
+    collect_parts(&root, &mut text_body, &mut html_body, &mut attachments);
+
+    Ok(ParsedMessage {
+        root,
+        text_body,
+        html_body,
+        attachments,
+    })
+}
+
+fn parse_part(raw: &[u8], default_type: &ContentType, depth: usize) -> MimePart {
+    let (header_bytes, body) = split_header_body(raw);
+    let headers = parse_headers(header_bytes);
+
+    let content_type = find_header(&headers, "Content-Type")
+        .map(ContentType::parse)
+        .unwrap_or_else(|| default_type.clone());
+
+    if content_type.is_multipart() && depth < MAX_DEPTH {
+        if let Some(boundary) = content_type.param("boundary") {
+            // multipart/digest 的子部分默认类型为 message/rfc822
+            let child_default = if content_type.mime_type == "multipart/digest" {
+                ContentType::parse("message/rfc822")
+            } else {
+                ContentType::default()
+            };
+
+            let children = split_multipart(body, boundary)
+                .into_iter()
+                .map(|part| parse_part(part, &child_default, depth + 1))
+                .collect();
+
+            return MimePart {
+                headers,
+                content_type,
+                body: Vec::new(),
+                children,
+            };
+        }
+    }
+
+    let body = decode_transfer_encoding(body, find_header(&headers, "Content-Transfer-Encoding"));
+
+    MimePart {
+        headers,
+        content_type,
+        body,
+        children: Vec::new(),
+    }
+}
+
+/// 深度优先查找第一个非附件的 text/plain 与 text/html 部分作为正文，其余叶子部分作为附件
+///
+/// 没有文件名的多余文本部分（例如邮件列表追加的页脚）既不是正文也不作为附件。
+fn collect_parts(
+    part: &MimePart,
+    text: &mut Option<String>,
+    html: &mut Option<String>,
+    attachments: &mut Vec<AttachmentPart>,
+) {
+    if part.content_type.is_multipart() {
+        for child in &part.children {
+            collect_parts(child, text, html, attachments);
+        }
+        return;
+    }
+
+    let mime_type = part.content_type.mime_type.as_str();
+    if !part.is_attachment() && (mime_type == "text/plain" || mime_type == "text/html") {
+        match mime_type {
+            "text/plain" if text.is_none() => {
+                *text = Some(part.decoded_text());
+                return;
+            }
+            "text/html" if html.is_none() => {
+                *html = Some(part.decoded_text());
+                return;
+            }
+            _ => {}
+        }
+        if part.filename().is_none() {
+            return;
+        }
+    }
+
+    attachments.push(AttachmentPart::from_part(part));
+}
+
+/// 解码头部参数，支持 RFC 2231（`name*=utf-8''%E4%B8%AD`、`name*0*=` 续行）
+/// 以及常见但不规范的 RFC 2047 编码字写法
+fn decode_param(params: &HashMap<String, String>, name: &str) -> Option<String> {
+    if let Some(value) = params.get(&format!("{}*", name)) {
+        let (charset, encoded) = split_extended_value(value);
+        return Some(charset::decode_bytes(&percent_decode(encoded), charset));
+    }
+
+    let mut charset = None;
+    let mut bytes = Vec::new();
+    for index in 0.. {
+        if let Some(value) = params.get(&format!("{}*{}*", name, index)) {
+            let encoded = if index == 0 {
+                let (declared, encoded) = split_extended_value(value);
+                charset = declared;
+                encoded
+            } else {
+                value.as_str()
+            };
+            bytes.extend(percent_decode(encoded));
+        } else if let Some(value) = params.get(&format!("{}*{}", name, index)) {
+            bytes.extend_from_slice(value.as_bytes());
+        } else {
+            break;
+        }
+    }
+    if !bytes.is_empty() {
+        return Some(charset::decode_bytes(&bytes, charset));
+    }
+
+    params.get(name).map(|value| charset::decode_encoded_words(value))
+}
+
+/// 拆分 RFC 2231 扩展值 `charset'language'value`
+fn split_extended_value(value: &str) -> (Option<&str>, &str) {
+    let mut parts = value.splitn(3, '\'');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(charset), Some(_), Some(encoded)) => (Some(charset).filter(|c| !c.is_empty()), encoded),
+        _ => (None, value),
+    }
+}
+
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                output.push((hi << 4) | lo);
+                i += 3;
+                continue;
+            }
+        }
+        output.push(bytes[i]);
+        i += 1;
+    }
+
+    output
+}
+
+/// 没有文件名的附件按 MIME 类型生成默认文件名
+fn default_filename(mime_type: &str) -> String {
+    if mime_type == "message/rfc822" {
+        return "message.eml".to_string();
+    }
+
+    let extension = match mime_type.split_once('/').map(|(_, subtype)| subtype) {
+        Some(subtype) if subtype.len() <= 8 && subtype.chars().all(|c| c.is_ascii_alphanumeric()) => subtype,
+        _ => "bin",
+    };
+    format!("attachment.{}", extension)
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// 下一行（含换行符）的结束位置
+fn next_line_end(data: &[u8], start: usize) -> usize {
+    data[start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|i| start + i + 1)
+        .unwrap_or(data.len())
+}
+
+/// 在第一个空行处拆分头部与正文
+fn split_header_body(raw: &[u8]) -> (&[u8], &[u8]) {
+    let mut pos = 0;
+    while pos < raw.len() {
+        let end = next_line_end(raw, pos);
+        let line = &raw[pos..end];
+        if line == b"\n" || line == b"\r\n" {
+            return (&raw[..pos], &raw[end..]);
+        }
+        pos = end;
+    }
+    (raw, &[])
+}
+
+/// 解析头部并展开以空白开头的折叠行
+fn parse_headers(data: &[u8]) -> Vec<(String, String)> {
+    let text = charset::decode_unlabeled(data);
+    let mut headers: Vec<(String, String)> = Vec::new();
+
+    for line in text.lines() {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, value)) = headers.last_mut() {
+                let continuation = line.trim();
+                if !continuation.is_empty() {
+                    if !value.is_empty() {
+                        value.push(' ');
+                    }
+                    value.push_str(continuation);
+                }
+            }
+            continue;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim();
+            if !name.is_empty() && !name.contains(' ') {
+                headers.push((name.to_string(), value.trim().to_string()));
+            }
+        }
+    }
+
+    headers
+}
+
+/// 解析 `value; key=val; key="quoted val"` 形式的头部
+pub fn parse_header_params(value: &str) -> (String, HashMap<String, String>) {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => segments.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    segments.push(current);
+
+    let mut iter = segments.into_iter();
+    let main = iter.next().unwrap_or_default().trim().to_string();
+
+    let mut params = HashMap::new();
+    for segment in iter {
+        if let Some((key, val)) = segment.split_once('=') {
+            let key = key.trim().to_lowercase();
+            if !key.is_empty() {
+                params.insert(key, val.trim().to_string());
+            }
+        }
+    }
+
+    (main, params)
+}
+
+/// 按分隔符拆分 multipart 正文，忽略前导与结尾部分
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+
+    let mut parts = Vec::new();
+    let mut part_start: Option<usize> = None;
+    let mut pos = 0;
+
+    while pos < body.len() {
+        let line_end = next_line_end(body, pos);
+        let line = trim_line(&body[pos..line_end]);
+
+        if let Some(rest) = line.strip_prefix(delimiter) {
+            let is_close = rest == b"--";
+            if rest.is_empty() || is_close {
+                if let Some(start) = part_start {
+                    parts.push(strip_trailing_newline(&body[start..pos]));
+                }
+                if is_close {
+                    return parts;
+                }
+                part_start = Some(line_end);
+            }
+        }
+
+        pos = line_end;
+    }
+
+    // 缺少结束分隔符时保留最后一个部分
+    if let Some(start) = part_start {
+        if start < body.len() {
+            parts.push(&body[start..]);
+        }
+    }
+
+    parts
+}
+
+/// 去掉行尾的换行符以及分隔符后允许出现的空白
+fn trim_line(line: &[u8]) -> &[u8] {
+    let mut end = line.len();
+    while end > 0 && matches!(line[end - 1], b'\r' | b'\n' | b' ' | b'\t') {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// 分隔符前的换行属于分隔符本身
+fn strip_trailing_newline(data: &[u8]) -> &[u8] {
+    data.strip_suffix(b"\r\n")
+        .or_else(|| data.strip_suffix(b"\n"))
+        .unwrap_or(data)
+}
+
+fn decode_transfer_encoding(body: &[u8], encoding: Option<&str>) -> Vec<u8> {
+    let encoding = encoding.unwrap_or("7bit").trim().to_lowercase();
+
+    match encoding.as_str() {
+        "base64" => decode_base64(body).unwrap_or_else(|| body.to_vec()),
+        "quoted-printable" => decode_quoted_printable(body),
+        _ => body.to_vec(),
+    }
+}
+
+/// 解码 base64，忽略其中的换行和空白
+pub fn decode_base64(data: &[u8]) -> Option<Vec<u8>> {
+    let cleaned: Vec<u8> = data
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    LENIENT_BASE64.decode(cleaned).ok()
+}
+
+/// 解码 quoted-printable，处理 `=XX` 转义和软换行
+pub fn decode_quoted_printable(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+
+    while i < input.len() {
+        let b = input[i];
+        if b != b'=' {
+            out.push(b);
+            i += 1;
+            continue;
+        }
+
+        // 软换行：`=` 后面（可能带空白）紧跟换行
+        let mut j = i + 1;
+        while j < input.len() && matches!(input[j], b' ' | b'\t') {
+            j += 1;
+        }
+        if input.get(j) == Some(&b'\n') {
+            i = j + 1;
+            continue;
+        }
+        if input.get(j) == Some(&b'\r') && input.get(j + 1) == Some(&b'\n') {
+            i = j + 2;
+            continue;
+        }
+
+        let high = input.get(i + 1).and_then(|c| hex_value(*c));
+        let low = input.get(i + 2).and_then(|c| hex_value(*c));
+        match (high, low) {
+            (Some(h), Some(l)) => {
+                out.push((h << 4) | l);
+                i += 3;
+            }
+            _ => {
+                // 非法转义按原样保留
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    out
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// 解析 Message-ID 头部，去掉尖括号
+pub fn parse_message_id(value: &str) -> Option<String> {
+    let id = value
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim();
+
+    if id.is_empty() {
+        None
+    } else {
+        Some(id.to_string())
+    }
+}
+
+/// 解析 References / In-Reply-To 中的 Message-ID 列表，忽略尖括号外的注释和文字
+pub fn parse_message_id_list(value: &str) -> Vec<String> {
+    static MESSAGE_ID: OnceLock<Regex> = OnceLock::new();
+    let ids = MESSAGE_ID.get_or_init(|| Regex::new(r"<([^<>\s]+)>").unwrap());
+    let list: Vec<String> = ids.captures_iter(value).map(|caps| caps[1].to_string()).collect();
+
+    // 不带尖括号的不规范写法
+    if list.is_empty() {
+        return value
+            .split_whitespace()
+            .filter(|id| id.contains('@'))
+            .map(str::to_string)
+            .collect();
+    }
+    list
+}
+
+/// 解析 RFC 5322 的 Date 头部
+///
+/// 兼容常见的不规范写法：省略星期、时区后带注释（`+0800 (CST)`）、多余的空白和两位数年份。
+pub fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    static COMMENT: OnceLock<Regex> = OnceLock::new();
+    let comments = COMMENT.get_or_init(|| Regex::new(r"\([^)]*\)").unwrap());
+    let value = comments.replace_all(value, " ");
+    let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    DateTime::parse_from_rfc2822(&value)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// 将 HTML 转换为用于预览和搜索的纯文本
+pub fn html_to_text(html: &str) -> String {
+    static HIDDEN: OnceLock<Regex> = OnceLock::new();
+    static LINE_BREAKS: OnceLock<Regex> = OnceLock::new();
+    static TAGS: OnceLock<Regex> = OnceLock::new();
+    static BLANK_LINES: OnceLock<Regex> = OnceLock::new();
+    let hidden = HIDDEN.get_or_init(|| Regex::new(r"(?is)<(script|style|head)[^>]*>.*?</(script|style|head)>").unwrap());
+    let line_breaks = LINE_BREAKS.get_or_init(|| Regex::new(r"(?i)<br\s*/?>|</(p|div|tr|li|h[1-6])>").unwrap());
+    let tags = TAGS.get_or_init(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+    let blank_lines = BLANK_LINES.get_or_init(|| Regex::new(r"\n[ \t]*\n([ \t]*\n)+").unwrap());
+
+    let text = hidden.replace_all(html, "");
+    let text = line_breaks.replace_all(&text, "\n");
+    let text = tags.replace_all(&text, "");
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    blank_lines.replace_all(&text, "\n\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message_id_list() {
+        assert_eq!(
+            parse_message_id_list("<a@example.com>\r\n <b@example.com> (comment)"),
+            vec!["a@example.com", "b@example.com"]
+        );
+        assert_eq!(parse_message_id_list("c@example.com"), vec!["c@example.com"]);
+        assert!(parse_message_id_list("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let expected = "2024-03-05T02:04:05+00:00";
+        for value in [
+            "Tue, 5 Mar 2024 10:04:05 +0800",
+            "Tue, 05 Mar 2024 10:04:05 +0800 (CST)",
+            "5 Mar 2024 10:04:05 +0800",
+            "Tue, 5 Mar 24 10:04:05 +0800",
+            "Tue,  5 Mar 2024\r\n 02:04:05 GMT",
+        ] {
+            assert_eq!(parse_date(value).map(|d| d.to_rfc3339()).as_deref(), Some(expected), "{}", value);
+        }
+        assert!(parse_date("not a date").is_none());
+    }
+
+    #[test]
+    fn test_unfolds_headers() {
+        let raw = b"Subject: a very\r\n long subject\r\nFrom: a@example.com\r\n\r\nbody";
+        let parsed = parse_message(raw).unwrap();
+        assert_eq!(parsed.header("subject"), Some("a very long subject"));
+        assert_eq!(parsed.text_body.as_deref(), Some("body"));
+    }
+
+    #[test]
+    fn test_multipart_alternative() {
+        let raw = b"Content-Type: multipart/alternative; boundary=\"b1\"\r\n\r\n\
+preamble\r\n\
+--b1\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\r\n\
+caf=C3=A9 =\r\nau lait\r\n\
+--b1\r\n\
+Content-Type: text/html\r\n\
+Content-Transfer-Encoding: base64\r\n\r\n\
+PHA+aGVsbG88L3A+\r\n\
+--b1--\r\n\
+epilogue\r\n";
+        let parsed = parse_message(raw).unwrap();
+        assert_eq!(parsed.text_body.as_deref(), Some("café au lait"));
+        assert_eq!(parsed.html_body.as_deref(), Some("<p>hello</p>"));
+        assert_eq!(parsed.root.children.len(), 2);
+    }
+
+    #[test]
+    fn test_nested_multipart_skips_attachments() {
+        let raw = b"Content-Type: multipart/mixed; boundary=outer\n\n\
+--outer\n\
+Content-Type: multipart/alternative; boundary=inner\n\n\
+--inner\n\
+Content-Type: text/html\n\n\
+<b>hi</b>\n\
+--inner--\n\
+--outer\n\
+Content-Type: text/plain; name=notes.txt\n\
+Content-Disposition: attachment; filename=\"notes.txt\"\n\n\
+not the body\n\
+--outer--\n";
+        let parsed = parse_message(raw).unwrap();
+        assert_eq!(parsed.text_body, None);
+        assert_eq!(parsed.html_body.as_deref(), Some("<b>hi</b>"));
+        assert_eq!(parsed.plain_text(), "hi");
+    }
+
+    #[test]
+    fn test_attachments() {
+        let raw = b"Content-Type: multipart/mixed; boundary=outer\n\n\
+--outer\n\
+Content-Type: multipart/related; boundary=rel\n\n\
+--rel\n\
+Content-Type: text/html\n\n\
+<img src=\"cid:logo@x\">\n\
+--rel\n\
+Content-Type: image/png\n\
+Content-ID: <logo@x>\n\
+Content-Transfer-Encoding: base64\n\n\
+iVBORw==\n\
+--rel--\n\
+--outer\n\
+Content-Type: application/pdf; name=\"=?utf-8?B?5oql5ZGKLnBkZg==?=\"\n\
+Content-Disposition: attachment; filename*=utf-8''%E6%8A%A5%E5%91%8A.pdf\n\n\
+%PDF\n\
+--outer\n\
+Content-Type: text/plain; charset=gbk\n\
+Content-Disposition: attachment;\n\x20filename*0*=gbk''%D6%D0; filename*1=.txt\n\n\
+notes\n\
+--outer--\n";
+        let parsed = parse_message(raw).unwrap();
+        assert_eq!(parsed.html_body.as_deref(), Some("<img src=\"cid:logo@x\">"));
+        assert_eq!(parsed.attachments.len(), 3);
+        assert!(parsed.has_attachment());
+
+        let logo = &parsed.attachments[0];
+        assert!(logo.is_inline);
+        assert_eq!(logo.content_id.as_deref(), Some("logo@x"));
+        assert_eq!(logo.filename, "attachment.png");
+        assert_eq!(logo.data, vec![0x89, b'P', b'N', b'G']);
+
+        let report = &parsed.attachments[1];
+        assert!(!report.is_inline);
+        assert_eq!(report.filename, "报告.pdf");
+        assert_eq!(report.mime_type, "application/pdf");
+        assert_eq!(report.data, b"%PDF");
+
+        assert_eq!(parsed.attachments[2].filename, "中.txt");
+    }
+
+    #[test]
+    fn test_header_params() {
+        let (value, params) = parse_header_params("attachment; filename=\"a;b.txt\"; size=10");
+        assert_eq!(value, "attachment");
+        assert_eq!(params.get("filename").map(|s| s.as_str()), Some("a;b.txt"));
+        assert_eq!(params.get("size").map(|s| s.as_str()), Some("10"));
+    }
+
+    #[test]
+    fn test_message_id() {
+        assert_eq!(parse_message_id(" <abc@host> ").as_deref(), Some("abc@host"));
+        assert_eq!(parse_message_id("<>"), None);
+    }
+}
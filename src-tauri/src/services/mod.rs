@@ -1,7 +1,20 @@
 pub mod email_service;
 pub mod provider_service;
 pub mod sync_service;
+pub mod idle_service;
 pub mod crypto_service;
-pub mod crypto_service;
+pub mod mime_parser;
+pub mod charset;
+pub mod imap_response;
+pub mod imap_stream;
+pub mod oauth_service;
+pub mod desktop;
+pub mod blob_store;
+pub mod compose;
+pub mod threading;
+pub mod reply;
+pub mod draft_service;
+pub mod outbox_service;
+pub mod app_paths;
 
-pub use email_service::*;
\ No newline at end of file
+pub use email_service::*;
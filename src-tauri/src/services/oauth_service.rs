@@ -0,0 +1,515 @@
+use anyhow::{Result, anyhow};
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::database::Database;
+use crate::models::email_provider::{AuthMethod, EmailAccount, EmailProvider};
+use crate::models::oauth_token::OAuthToken;
+use crate::services::desktop;
+
+/// 等待用户在浏览器中完成授权的最长时间
+const AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// OAuth2 授权服务器配置
+#[derive(Debug, Clone)]
+pub struct OAuthConfig {
+    pub auth_url: String,
+    pub token_url: String,
+    pub client_id: String,
+    /// 桌面应用的 client secret 并不保密，Google 仍要求在换取令牌时提供
+    pub client_secret: Option<String>,
+    pub scopes: Vec<String>,
+}
+
+impl OAuthConfig {
+    /// 预设服务商的 OAuth2 配置
+    ///
+    /// 客户端 ID 从环境变量 `XMAIL_GMAIL_CLIENT_ID`、`XMAIL_OUTLOOK_CLIENT_ID` 读取，
+    /// 也可以在编译时通过同名环境变量写入。
+    pub fn for_provider(provider_type: &str) -> Result<Self> {
+        let (auth_url, token_url, scopes, id_var, secret_var, builtin_id, builtin_secret) = match provider_type {
+            "gmail" => (
+                "https://accounts.google.com/o/oauth2/v2/auth",
+                "https://oauth2.googleapis.com/token",
+                vec!["https://mail.google.com/"],
+                "XMAIL_GMAIL_CLIENT_ID",
+                "XMAIL_GMAIL_CLIENT_SECRET",
+                option_env!("XMAIL_GMAIL_CLIENT_ID"),
+                option_env!("XMAIL_GMAIL_CLIENT_SECRET"),
+            ),
+            "outlook" => (
+                "https://login.microsoftonline.com/common/oauth2/v2.0/authorize",
+                "https://login.microsoftonline.com/common/oauth2/v2.0/token",
+                vec![
+                    "https://outlook.office.com/IMAP.AccessAsUser.All",
+                    "https://outlook.office.com/SMTP.Send",
+                    "offline_access",
+                ],
+                "XMAIL_OUTLOOK_CLIENT_ID",
+                "XMAIL_OUTLOOK_CLIENT_SECRET",
+                option_env!("XMAIL_OUTLOOK_CLIENT_ID"),
+                option_env!("XMAIL_OUTLOOK_CLIENT_SECRET"),
+            ),
+            other => return Err(anyhow!("邮件服务商 {} 不支持 OAuth2 登录", other)),
+        };
+
+        let client_id = std::env::var(id_var)
+            .ok()
+            .or_else(|| builtin_id.map(|id| id.to_string()))
+            .ok_or_else(|| anyhow!("未配置 OAuth2 客户端 ID，请设置环境变量 {}", id_var))?;
+        let client_secret = std::env::var(secret_var)
+            .ok()
+            .or_else(|| builtin_secret.map(|secret| secret.to_string()));
+
+        Ok(Self {
+            auth_url: auth_url.to_string(),
+            token_url: token_url.to_string(),
+            client_id,
+            client_secret,
+            scopes: scopes.into_iter().map(|scope| scope.to_string()).collect(),
+        })
+    }
+}
+
+/// PKCE（RFC 7636）参数
+#[derive(Debug, Clone)]
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    pub fn generate() -> Self {
+        Self::from_verifier(&random_token())
+    }
+
+    /// 使用 S256 方法计算 code_challenge
+    pub fn from_verifier(verifier: &str) -> Self {
+        let digest = Sha256::digest(verifier.as_bytes());
+        Self {
+            verifier: verifier.to_string(),
+            challenge: general_purpose::URL_SAFE_NO_PAD.encode(digest),
+        }
+    }
+}
+
+/// 32 字节随机数的 base64url 编码，用作 code_verifier 和 state
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// 令牌端点的成功响应
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_in: Option<i64>,
+}
+
+impl TokenResponse {
+    /// 转换为需要保存的令牌；刷新时服务器可能不返回新的 refresh_token，沿用旧的
+    pub fn into_token(self, account_id: i32, previous_refresh_token: Option<String>) -> OAuthToken {
+        let now = chrono::Utc::now();
+        OAuthToken {
+            account_id,
+            access_token: self.access_token,
+            refresh_token: self.refresh_token.or(previous_refresh_token),
+            expires_at: self.expires_in.map(|secs| now + chrono::Duration::seconds(secs)),
+            updated_at: now,
+        }
+    }
+}
+
+/// 令牌端点的错误响应
+#[derive(Debug, Deserialize)]
+struct TokenError {
+    error: String,
+    error_description: Option<String>,
+}
+
+pub struct OAuthClient {
+    config: OAuthConfig,
+    http: reqwest::Client,
+}
+
+impl OAuthClient {
+    pub fn new(config: OAuthConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// 生成浏览器中打开的授权地址
+    pub fn authorization_url(&self, redirect_uri: &str, state: &str, pkce: &Pkce, login_hint: &str) -> Result<String> {
+        let scope = self.config.scopes.join(" ");
+        let mut params = vec![
+            ("response_type", "code"),
+            ("client_id", self.config.client_id.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("scope", scope.as_str()),
+            ("state", state),
+            ("code_challenge", pkce.challenge.as_str()),
+            ("code_challenge_method", "S256"),
+            // Google 只有在 access_type=offline 且重新同意时才返回 refresh_token
+            ("access_type", "offline"),
+            ("prompt", "consent"),
+        ];
+        if !login_hint.is_empty() {
+            params.push(("login_hint", login_hint));
+        }
+
+        Ok(reqwest::Url::parse_with_params(&self.config.auth_url, &params)?.to_string())
+    }
+
+    /// 用授权码换取令牌
+    pub async fn exchange_code(&self, code: &str, redirect_uri: &str, pkce: &Pkce) -> Result<TokenResponse> {
+        self.request_token(vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("code_verifier", pkce.verifier.as_str()),
+        ])
+        .await
+    }
+
+    /// 使用 refresh_token 获取新的访问令牌
+    pub async fn refresh(&self, refresh_token: &str) -> Result<TokenResponse> {
+        self.request_token(vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ])
+        .await
+    }
+
+    async fn request_token(&self, mut params: Vec<(&str, &str)>) -> Result<TokenResponse> {
+        params.push(("client_id", self.config.client_id.as_str()));
+        if let Some(secret) = &self.config.client_secret {
+            params.push(("client_secret", secret.as_str()));
+        }
+
+        let response = self.http.post(&self.config.token_url).form(&params).send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            return Err(match serde_json::from_str::<TokenError>(&body) {
+                Ok(error) => anyhow!(
+                    "OAuth2 令牌请求失败: {} {}",
+                    error.error,
+                    error.error_description.unwrap_or_default()
+                ),
+                Err(_) => anyhow!("OAuth2 令牌请求失败: HTTP {}", status),
+            });
+        }
+
+        Ok(serde_json::from_str(&body)?)
+    }
+}
+
+/// 接收授权回调的本地回环服务器（RFC 8252）
+pub struct LoopbackServer {
+    listener: TcpListener,
+}
+
+impl LoopbackServer {
+    /// 在 127.0.0.1 的随机端口上监听
+    pub fn bind() -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(("127.0.0.1", 0))?,
+        })
+    }
+
+    pub fn redirect_uri(&self) -> Result<String> {
+        Ok(format!("http://127.0.0.1:{}/callback", self.listener.local_addr()?.port()))
+    }
+
+    /// 等待浏览器重定向回来，校验 state 后返回授权码
+    pub fn wait_for_code(&self, state: &str, timeout: Duration) -> Result<String> {
+        let deadline = Instant::now() + timeout;
+        self.listener.set_nonblocking(true)?;
+
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline {
+                        return Err(anyhow!("等待 OAuth2 授权超时"));
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            if let Some(result) = Self::handle_callback(stream, state)? {
+                return result;
+            }
+        }
+    }
+
+    /// 处理一次请求；不是回调地址（例如 favicon）时返回 None 继续等待
+    fn handle_callback(mut stream: TcpStream, state: &str) -> Result<Option<Result<String>>> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+        let head = read_request_head(&mut stream)?;
+        let target = head
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .unwrap_or("/");
+
+        if !target.starts_with("/callback") {
+            stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")?;
+            return Ok(None);
+        }
+
+        let url = reqwest::Url::parse(&format!("http://127.0.0.1{}", target))?;
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+
+        let result = if let Some(error) = param("error") {
+            Err(anyhow!("授权被拒绝: {}", param("error_description").unwrap_or(error)))
+        } else if param("state").as_deref() != Some(state) {
+            Err(anyhow!("OAuth2 回调的 state 不匹配"))
+        } else {
+            param("code").ok_or_else(|| anyhow!("OAuth2 回调缺少授权码"))
+        };
+
+        let message = match &result {
+            Ok(_) => "授权完成，可以关闭此页面并返回 XMail。".to_string(),
+            Err(e) => format!("授权失败：{}", e),
+        };
+        let body = format!("<!DOCTYPE html><html><head><meta charset=\"utf-8\"></head><body><p>{}</p></body></html>", message);
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+
+        Ok(Some(result))
+    }
+}
+
+/// 读取 HTTP 请求头（到空行为止）
+fn read_request_head(stream: &mut TcpStream) -> Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+        if head.len() > 16 * 1024 {
+            return Err(anyhow!("OAuth2 回调请求过大"));
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// XOAUTH2 SASL 初始响应（未经 base64 编码）
+pub fn xoauth2_string(user: &str, access_token: &str) -> String {
+    format!("user={}\x01auth=Bearer {}\x01\x01", user, access_token)
+}
+
+pub struct OAuthService;
+
+impl OAuthService {
+    /// 在浏览器中完成授权码 + PKCE 流程，返回获得的令牌（account_id 为 0）
+    pub async fn authorize(provider: &EmailProvider, login_hint: &str) -> Result<OAuthToken> {
+        let client = OAuthClient::new(OAuthConfig::for_provider(&provider.provider_type)?);
+        let pkce = Pkce::generate();
+        let state = random_token();
+
+        let server = LoopbackServer::bind()?;
+        let redirect_uri = server.redirect_uri()?;
+        let url = client.authorization_url(&redirect_uri, &state, &pkce, login_hint)?;
+
+        if let Err(e) = desktop::open_external(&url) {
+            eprintln!("无法打开浏览器 ({})，请手动访问: {}", e, url);
+        }
+
+        let code = tokio::task::spawn_blocking(move || {
+            server.wait_for_code(&state, AUTHORIZATION_TIMEOUT)
+        })
+        .await??;
+
+        let response = client.exchange_code(&code, &redirect_uri, &pkce).await?;
+        if response.refresh_token.is_none() {
+            return Err(anyhow!("授权服务器没有返回 refresh_token，无法保持登录"));
+        }
+
+        Ok(response.into_token(0, None))
+    }
+
+    /// 获取账户登录用的访问令牌，即将过期时先刷新并保存
+    ///
+    /// 密码登录的账户返回 None。
+    pub async fn access_token(db: &Mutex<Database>, provider: &EmailProvider, account: &EmailAccount) -> Result<Option<String>> {
+        if account.auth_method != AuthMethod::OAuth2 {
+            return Ok(None);
+        }
+
+        let token = {
+            let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+            db.get_oauth_token(account.id)?
+        }
+        .ok_or_else(|| anyhow!("账户 {} 尚未完成 OAuth2 授权", account.email_address))?;
+
+        if !token.needs_refresh() {
+            return Ok(Some(token.access_token));
+        }
+
+        let refresh_token = token
+            .refresh_token
+            .ok_or_else(|| anyhow!("账户 {} 的授权已过期，请重新授权", account.email_address))?;
+
+        let client = OAuthClient::new(OAuthConfig::for_provider(&provider.provider_type)?);
+        let token = client
+            .refresh(&refresh_token)
+            .await?
+            .into_token(account.id, Some(refresh_token));
+
+        let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+        db.save_oauth_token(&token)?;
+
+        Ok(Some(token.access_token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 只响应一次请求的模拟令牌端点，返回请求体供断言
+    fn mock_token_endpoint(status: &'static str, body: &'static str) -> (OAuthConfig, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let config = OAuthConfig {
+            auth_url: "https://auth.example.com/authorize".to_string(),
+            token_url: format!("http://{}/token", listener.local_addr().unwrap()),
+            client_id: "client".to_string(),
+            client_secret: None,
+            scopes: vec!["mail".to_string()],
+        };
+
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let head = read_request_head(&mut stream).unwrap();
+            let (headers, mut request_body) = head.split_once("\r\n\r\n").map(|(h, b)| (h.to_string(), b.to_string())).unwrap();
+            let length: usize = headers
+                .lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                .unwrap_or(0);
+            while request_body.len() < length {
+                let mut buf = [0u8; 1024];
+                let n = stream.read(&mut buf).unwrap();
+                request_body.push_str(&String::from_utf8_lossy(&buf[..n]));
+            }
+
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+            request_body
+        });
+
+        (config, handle)
+    }
+
+    #[test]
+    fn test_pkce_challenge() {
+        // RFC 7636 附录 B 的示例
+        let pkce = Pkce::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk");
+        assert_eq!(pkce.challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+        assert_eq!(Pkce::generate().verifier.len(), 43);
+    }
+
+    #[test]
+    fn test_xoauth2_string() {
+        assert_eq!(
+            xoauth2_string("someuser@example.com", "ya29.vF9dft4qmTc2Nvb3RlckBhdHRhdmlzdGEuY29tCg"),
+            "user=someuser@example.com\x01auth=Bearer ya29.vF9dft4qmTc2Nvb3RlckBhdHRhdmlzdGEuY29tCg\x01\x01"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exchange_and_refresh_with_mock_endpoint() {
+        let (config, server) = mock_token_endpoint(
+            "200 OK",
+            r#"{"access_token":"at-1","refresh_token":"rt-1","expires_in":3600,"token_type":"Bearer"}"#,
+        );
+        let pkce = Pkce::from_verifier("verifier");
+        let token = OAuthClient::new(config)
+            .exchange_code("code-123", "http://127.0.0.1:1/callback", &pkce)
+            .await
+            .unwrap()
+            .into_token(7, None);
+        let request = server.join().unwrap();
+
+        assert!(request.contains("grant_type=authorization_code"));
+        assert!(request.contains("code=code-123"));
+        assert!(request.contains("code_verifier=verifier"));
+        assert_eq!(token.access_token, "at-1");
+        assert_eq!(token.refresh_token.as_deref(), Some("rt-1"));
+        assert!(!token.needs_refresh());
+
+        // 刷新响应不带 refresh_token 时沿用旧值
+        let (config, server) = mock_token_endpoint("200 OK", r#"{"access_token":"at-2","expires_in":60}"#);
+        let token = OAuthClient::new(config)
+            .refresh("rt-1")
+            .await
+            .unwrap()
+            .into_token(7, Some("rt-1".to_string()));
+        assert!(server.join().unwrap().contains("grant_type=refresh_token"));
+        assert_eq!(token.access_token, "at-2");
+        assert_eq!(token.refresh_token.as_deref(), Some("rt-1"));
+        assert!(token.needs_refresh());
+
+        let (config, server) = mock_token_endpoint(
+            "400 Bad Request",
+            r#"{"error":"invalid_grant","error_description":"Token has been expired or revoked."}"#,
+        );
+        let error = OAuthClient::new(config).refresh("rt-1").await.unwrap_err();
+        server.join().unwrap();
+        assert!(error.to_string().contains("invalid_grant"));
+    }
+
+    #[test]
+    fn test_loopback_callback() {
+        let server = LoopbackServer::bind().unwrap();
+        let redirect_uri = server.redirect_uri().unwrap();
+        let addr = redirect_uri.trim_start_matches("http://").trim_end_matches("/callback").to_string();
+
+        let browser = std::thread::spawn(move || {
+            for target in ["/favicon.ico", "/callback?code=abc%2F123&state=xyz"] {
+                let mut stream = TcpStream::connect(&addr).unwrap();
+                write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", target, addr).unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).unwrap();
+            }
+        });
+
+        let code = server.wait_for_code("xyz", Duration::from_secs(5)).unwrap();
+        browser.join().unwrap();
+        assert_eq!(code, "abc/123");
+    }
+}
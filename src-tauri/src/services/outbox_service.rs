@@ -0,0 +1,405 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use crate::database::Database;
+use crate::models::address::EmailAddress;
+use crate::models::email::Email;
+use crate::models::folder::SPECIAL_USE_SENT;
+use crate::models::outbox::{outbox_time, OutboxMessage, OutboxStatus};
+use crate::models::outgoing::OutgoingMessage;
+use crate::services::blob_store::BlobStore;
+use crate::services::compose;
+use crate::services::crypto_service::CryptoService;
+use crate::services::draft_service;
+use crate::services::provider_service::ProviderService;
+use crate::services::sync_service::{generate_message_id, DeliveryResult, EmailSyncService, SyncManager};
+
+/// 发件箱中的邮件发送成功
+pub const OUTBOX_SENT_EVENT: &str = "outbox://sent";
+
+/// 发件箱中的邮件永久失败（或多次重试后仍然失败）
+pub const OUTBOX_FAILED_EVENT: &str = "outbox://failed";
+
+/// 撤回窗口（秒）的设置项
+pub const UNDO_SEND_SETTING: &str = "undo_send_seconds";
+pub const DEFAULT_UNDO_SEND_SECONDS: u32 = 10;
+pub const MAX_UNDO_SEND_SECONDS: u32 = 120;
+
+/// 临时失败最多尝试的次数，超过后按永久失败处理
+const MAX_ATTEMPTS: i32 = 8;
+
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(60);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// 没有待发送邮件时检查发件箱的间隔
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// `outbox://sent` 事件内容
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxSentEvent {
+    pub outbox_id: String,
+    pub account_id: i32,
+    pub result: DeliveryResult,
+}
+
+/// `outbox://failed` 事件内容
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxFailedEvent {
+    pub outbox_id: String,
+    pub account_id: i32,
+    pub subject: String,
+    pub error: String,
+    pub attempts: i32,
+}
+
+/// 通过账户的 SMTP 服务器发送邮件，在本地保存已发送邮件，
+/// 服务商不会自动保存时同时追加到服务器的已发送文件夹
+///
+/// `message_id` 为 None 时生成新的 Message-ID。邮件发出后的保存失败只记录日志，
+/// 不作为发送失败返回，避免重试时重复投递。
+pub async fn deliver(
+    db: &Mutex<Database>,
+    blobs: &BlobStore,
+    account_id: i32,
+    message: &OutgoingMessage,
+    message_id: Option<String>,
+) -> Result<DeliveryResult> {
+    let sync_service = EmailSyncService::for_account(db, account_id).await?;
+    let account = sync_service.account();
+    let attachments = compose::load_attachments(message, blobs)?;
+
+    let mut email = Email::new(
+        String::new(),
+        String::new(),
+        message.subject.clone(),
+        compose::text_body(message),
+        "发件箱".to_string(),
+    );
+    let parse = |addresses: &[String]| -> Vec<EmailAddress> {
+        addresses.iter().flat_map(|address| EmailAddress::parse_list(address)).collect()
+    };
+    email.set_addresses(
+        Some(EmailAddress::new(Some(account.display_name.clone()), account.email_address.clone())),
+        parse(&message.to),
+        parse(&message.cc),
+        parse(&message.bcc),
+        message.reply_to.as_deref().map(EmailAddress::parse_list).unwrap_or_default(),
+    );
+    email.html_body = message.html_body.clone();
+    email.account_id = Some(account_id);
+    email.is_read = true;
+    email.sent_at = Some(email.created_at);
+    email.message_id = Some(message_id.unwrap_or_else(|| generate_message_id(&account.email_address)));
+    email.in_reply_to = message.in_reply_to.clone();
+    email.references = message.references.clone();
+    email.has_attachment = attachments.iter().any(|part| !part.is_inline);
+
+    // 放在服务器的已发送文件夹下，同步时按 Message-ID 识别为同一封邮件
+    email.folder = {
+        let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+        db.get_folders(account_id)?
+            .into_iter()
+            .find(|folder| folder.special_use.as_deref() == Some(SPECIAL_USE_SENT))
+            .map(|folder| folder.name)
+    };
+
+    let message_id = email.message_id.clone().unwrap_or_default();
+    let (response, raw) = sync_service.send_email(message, &message_id, &attachments).await?;
+
+    {
+        let db = db.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(e) = SyncManager::save_sent_email(&db, blobs, &email, attachments) {
+            eprintln!("保存已发送邮件失败: {}", e);
+        }
+    }
+
+    let mut result = DeliveryResult::new(&email, message, &response);
+
+    // 163 和自定义服务器不会自动保存 SMTP 发出的邮件，原样追加到已发送文件夹，
+    // 其他客户端才能看到；Gmail、Outlook 会自动保存，再追加会出现重复
+    result.saved_to_sent_folder = if !sync_service.needs_sent_copy() {
+        true
+    } else if let Some(folder) = &email.folder {
+        match SyncManager::new().save_to_sent(sync_service, folder, &raw).await {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("保存到已发送文件夹失败: {}", e);
+                false
+            }
+        }
+    } else {
+        false
+    };
+
+    Ok(result)
+}
+
+/// 把邮件放入发件箱，在 `send_at`（默认立即）和撤回窗口结束两者中较晚的时间发送
+///
+/// 附件在入队时复制到附件存储，发送时不再读取原来的本地文件。
+/// 从草稿发送时草稿保留到邮件发出后才删除。
+pub fn enqueue(
+    db: &Database,
+    blobs: &BlobStore,
+    account_id: i32,
+    mut message: OutgoingMessage,
+    source_email_id: Option<String>,
+    draft_id: Option<String>,
+    send_at: Option<DateTime<Utc>>,
+) -> Result<OutboxMessage> {
+    // 地址错误重试也不会成功，入队前先检查
+    let recipients = [&message.to, &message.cc, &message.bcc]
+        .into_iter()
+        .map(|addresses| compose::parse_mailboxes(addresses).map(|mailboxes| mailboxes.len()))
+        .sum::<Result<usize>>()?;
+    if recipients == 0 {
+        return Err(anyhow!("至少需要一个收件人"));
+    }
+
+    let account = ProviderService::new(&db.conn)
+        .get_account(account_id)?
+        .ok_or_else(|| anyhow!("账户不存在"))?;
+    compose::store_attachments(&mut message, blobs)?;
+
+    let now = Utc::now();
+    let earliest = now + chrono::Duration::seconds(undo_send_delay(db)? as i64);
+    let send_at = outbox_time(send_at.map_or(earliest, |send_at| send_at.max(earliest)));
+
+    let message = OutboxMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        account_id,
+        message,
+        message_id: generate_message_id(&account.email_address),
+        source_email_id,
+        draft_id,
+        status: OutboxStatus::Queued,
+        send_at: send_at.clone(),
+        next_attempt_at: send_at,
+        attempts: 0,
+        last_error: None,
+        created_at: outbox_time(now),
+        updated_at: outbox_time(now),
+    };
+    db.insert_outbox_message(&message)?;
+    Ok(message)
+}
+
+/// 撤回窗口（秒），未设置时使用默认值
+pub fn undo_send_delay(db: &Database) -> Result<u32> {
+    Ok(db
+        .get_setting(UNDO_SEND_SETTING)?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_UNDO_SEND_SECONDS))
+}
+
+pub fn set_undo_send_delay(db: &Database, seconds: u32) -> Result<()> {
+    if seconds > MAX_UNDO_SEND_SECONDS {
+        return Err(anyhow!("撤回时间不能超过 {} 秒", MAX_UNDO_SEND_SECONDS));
+    }
+    db.set_setting(UNDO_SEND_SETTING, &seconds.to_string())
+}
+
+/// 发送失败是否可以稍后重试
+///
+/// 4xx 响应、网络和 TLS 错误可以重试；5xx 响应、地址无效等邮件本身的问题重试也不会成功。
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    if let Some(e) = error.downcast_ref::<lettre::transport::smtp::Error>() {
+        return !(e.is_permanent() || e.is_client() || e.is_response());
+    }
+    error
+        .chain()
+        .any(|cause| cause.is::<std::io::Error>() || cause.is::<reqwest::Error>())
+}
+
+/// 第 `attempts` 次失败后的等待时间：从 1 分钟开始翻倍，最长 1 小时
+fn retry_delay(attempts: i32) -> Duration {
+    INITIAL_RETRY_DELAY
+        .saturating_mul(2u32.pow(attempts.clamp(0, 10) as u32))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// 管理发件箱的后台发送线程
+pub struct OutboxManager {
+    wake: Mutex<Option<Sender<()>>>,
+}
+
+impl OutboxManager {
+    pub fn new() -> Self {
+        Self {
+            wake: Mutex::new(None),
+        }
+    }
+
+    /// 启动后台发送线程，已启动时不做任何事
+    pub fn start(&self, app: AppHandle) {
+        let mut wake = self.wake.lock().unwrap();
+        if wake.is_some() {
+            return;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let worker = OutboxWorker { app, wake: receiver };
+
+        // SMTP 发送是阻塞的，使用独立线程
+        std::thread::spawn(move || worker.run());
+        *wake = Some(sender);
+    }
+
+    /// 发件箱有变化或凭据解锁后唤醒后台线程
+    pub fn wake(&self) {
+        if let Some(sender) = self.wake.lock().unwrap().as_ref() {
+            sender.send(()).ok();
+        }
+    }
+}
+
+struct OutboxWorker {
+    app: AppHandle,
+    wake: Receiver<()>,
+}
+
+impl OutboxWorker {
+    fn run(self) {
+        {
+            let db = self.app.state::<Mutex<Database>>();
+            let db = db.lock().unwrap();
+            if let Err(e) = db.requeue_interrupted_outbox() {
+                eprintln!("恢复发件箱失败: {}", e);
+            }
+        }
+
+        loop {
+            let wait = match self.process_due() {
+                Ok(wait) => wait,
+                Err(e) => {
+                    eprintln!("处理发件箱失败: {}", e);
+                    IDLE_CHECK_INTERVAL
+                }
+            };
+
+            match self.wake.recv_timeout(wait) {
+                Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
+
+    /// 发送所有已到时间的邮件，返回需要等待多久再检查
+    fn process_due(&self) -> Result<Duration> {
+        // 设置了主密码时，解锁前无法登录 SMTP 服务器，解锁后会被唤醒
+        if !CryptoService::is_unlocked() {
+            return Ok(IDLE_CHECK_INTERVAL);
+        }
+
+        let db = self.app.state::<Mutex<Database>>();
+        loop {
+            let message = {
+                let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+                db.claim_due_outbox_message(&outbox_time(Utc::now()))?
+            };
+            match message {
+                Some(message) => self.send(message),
+                None => break,
+            }
+        }
+
+        let next = {
+            let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+            db.next_outbox_attempt()?
+        };
+        let wait = next
+            .and_then(|next| DateTime::parse_from_rfc3339(&next).ok())
+            .map(|next| (next.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+            .unwrap_or(IDLE_CHECK_INTERVAL);
+        Ok(wait.min(IDLE_CHECK_INTERVAL))
+    }
+
+    /// 发送一封邮件并记录结果
+    ///
+    /// 发送后的数据库更新失败只记录日志：邮件已经发出时必须从发件箱删除或标记为已发送，
+    /// 不能停留在发送中状态，否则下次启动会被放回队列再次发送。
+    fn send(&self, message: OutboxMessage) {
+        let db = self.app.state::<Mutex<Database>>();
+        let blobs = self.app.state::<BlobStore>();
+
+        let result = tauri::async_runtime::block_on(deliver(
+            &db,
+            &blobs,
+            message.account_id,
+            &message.message,
+            Some(message.message_id.clone()),
+        ));
+
+        let db = db.lock().unwrap_or_else(PoisonError::into_inner);
+        match result {
+            Ok(result) => {
+                if let Err(e) = db.delete_outbox_message(&message.id) {
+                    eprintln!("从发件箱删除已发送的邮件失败: {}", e);
+                    if let Err(e) = db.mark_outbox_sent(&message.id) {
+                        eprintln!("标记发件箱邮件为已发送失败: {}", e);
+                    }
+                }
+                drop(db);
+
+                if let Some(draft_id) = &message.draft_id {
+                    let db = self.app.state::<Mutex<Database>>();
+                    if let Err(e) = tauri::async_runtime::block_on(draft_service::delete_draft(&db, draft_id)) {
+                        eprintln!("删除已发送邮件的草稿失败: {}", e);
+                    }
+                }
+                let event = OutboxSentEvent {
+                    outbox_id: message.id,
+                    account_id: message.account_id,
+                    result,
+                };
+                self.app.emit(OUTBOX_SENT_EVENT, event).ok();
+            }
+            Err(e) if is_transient(&e) && message.attempts + 1 < MAX_ATTEMPTS => {
+                eprintln!("发送邮件失败，稍后重试: {}", e);
+                let next = Utc::now() + chrono::Duration::seconds(retry_delay(message.attempts).as_secs() as i64);
+                if let Err(e) = db.reschedule_outbox_message(&message.id, &outbox_time(next), &e.to_string()) {
+                    eprintln!("更新发件箱重试时间失败: {}", e);
+                }
+            }
+            Err(e) => {
+                if let Err(e) = db.fail_outbox_message(&message.id, &e.to_string()) {
+                    eprintln!("标记发件箱邮件发送失败出错: {}", e);
+                }
+                let event = OutboxFailedEvent {
+                    outbox_id: message.id,
+                    account_id: message.account_id,
+                    subject: message.message.subject,
+                    error: e.to_string(),
+                    attempts: message.attempts + 1,
+                };
+                self.app.emit(OUTBOX_FAILED_EVENT, event).ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_transient() {
+        let timeout = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        assert!(is_transient(&anyhow::Error::from(timeout).context("连接 SMTP 服务器失败")));
+        assert!(!is_transient(&anyhow!("收件人地址无效")));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(0), Duration::from_secs(60));
+        assert_eq!(retry_delay(1), Duration::from_secs(120));
+        assert_eq!(retry_delay(5), Duration::from_secs(60 * 32));
+        assert_eq!(retry_delay(6), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(100), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(-1), INITIAL_RETRY_DELAY);
+    }
+}
@@ -1,6 +1,12 @@
 use anyhow::Result;
-use rusqlite::{params, Connection};
-use crate::models::email_provider::{EmailProvider, EmailAccount, EmailCategory};
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::models::email_provider::{AuthMethod, EmailProvider, EmailAccount, EmailCategory, SecurityMode};
+use crate::services::crypto_service::CryptoService;
+
+/// 查询账户时使用的列，与 `row_to_account` 保持一致
+const ACCOUNT_COLUMNS: &str =
+    "a.id, a.provider_id, a.email_address, a.display_name, a.username, \
+     a.password, a.is_active, a.last_sync, a.created_at, a.auth_method";
 
 pub struct ProviderService<'a> {
     conn: &'a Connection,
@@ -14,23 +20,11 @@ impl<'a> ProviderService<'a> {
     // 邮件服务商管理
     pub fn get_all_providers(&self) -> Result<Vec<EmailProvider>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, provider_type, imap_server, imap_port, smtp_server, smtp_port, use_ssl, use_tls
+            "SELECT id, name, provider_type, imap_server, imap_port, smtp_server, smtp_port, imap_security, smtp_security, requires_imap_id
              FROM email_providers ORDER BY name"
         )?;
 
-        let provider_iter = stmt.query_map([], |row| {
-            Ok(EmailProvider {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                provider_type: row.get(2)?,
-                imap_server: row.get(3)?,
-                imap_port: row.get(4)?,
-                smtp_server: row.get(5)?,
-                smtp_port: row.get(6)?,
-                use_ssl: row.get(7)?,
-                use_tls: row.get(8)?,
-            })
-        })?;
+        let provider_iter = stmt.query_map([], Self::row_to_provider)?;
 
         let mut providers = Vec::new();
         for provider in provider_iter {
@@ -39,25 +33,39 @@ impl<'a> ProviderService<'a> {
         Ok(providers)
     }
 
+    fn row_to_provider(row: &rusqlite::Row) -> rusqlite::Result<EmailProvider> {
+        let security = |idx: usize| -> rusqlite::Result<SecurityMode> {
+            let value: String = row.get(idx)?;
+            SecurityMode::parse(&value).ok_or_else(|| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    idx,
+                    rusqlite::types::Type::Text,
+                    format!("未知的加密方式: {}", value).into(),
+                )
+            })
+        };
+
+        Ok(EmailProvider {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            provider_type: row.get(2)?,
+            imap_server: row.get(3)?,
+            imap_port: row.get(4)?,
+            smtp_server: row.get(5)?,
+            smtp_port: row.get(6)?,
+            imap_security: security(7)?,
+            smtp_security: security(8)?,
+            requires_imap_id: row.get(9)?,
+        })
+    }
+
     pub fn get_provider_by_type(&self, provider_type: &str) -> Result<Option<EmailProvider>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, provider_type, imap_server, imap_port, smtp_server, smtp_port, use_ssl, use_tls
+            "SELECT id, name, provider_type, imap_server, imap_port, smtp_server, smtp_port, imap_security, smtp_security, requires_imap_id
              FROM email_providers WHERE provider_type = ?1"
         )?;
 
-        let provider_iter = stmt.query_map([provider_type], |row| {
-            Ok(EmailProvider {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                provider_type: row.get(2)?,
-                imap_server: row.get(3)?,
-                imap_port: row.get(4)?,
-                smtp_server: row.get(5)?,
-                smtp_port: row.get(6)?,
-                use_ssl: row.get(7)?,
-                use_tls: row.get(8)?,
-            })
-        })?;
+        let provider_iter = stmt.query_map([provider_type], Self::row_to_provider)?;
 
         for provider in provider_iter {
             return Ok(Some(provider?));
@@ -66,22 +74,23 @@ impl<'a> ProviderService<'a> {
     }
 
     // 邮件账户管理
+
+    /// 添加账户，`account.password` 为明文密码，保存时加密
     pub fn add_email_account(&self, account: &EmailAccount) -> Result<i64> {
-        use crate::services::crypto_service::CryptoService;
-        
         // 加密密码
-        let encrypted_password = CryptoService::encrypt_password(&account.password);
+        let encrypted_password = CryptoService::encrypt_password(&account.password)?;
         
-        let result = self.conn.execute(
+        self.conn.execute(
             "INSERT INTO email_accounts 
-             (provider_id, email_address, display_name, username, password, is_active, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+             (provider_id, email_address, display_name, username, password, auth_method, is_active, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
                 account.provider_id,
                 account.email_address,
                 account.display_name,
                 account.username,
                 encrypted_password, // 存储加密后的密码
+                account.auth_method.as_str(),
                 account.is_active,
                 chrono::Utc::now().to_rfc3339()
             ],
@@ -90,33 +99,14 @@ impl<'a> ProviderService<'a> {
         Ok(self.conn.last_insert_rowid())
     }
 
+    /// 获取所有账户，密码保持加密状态，只在连接服务器时解密
     pub fn get_all_accounts(&self) -> Result<Vec<EmailAccount>> {
-        use crate::services::crypto_service::CryptoService;
-        
-        let mut stmt = self.conn.prepare(
-            "SELECT a.id, a.provider_id, a.email_address, a.display_name, a.username, 
-                    a.password, a.is_active, a.last_sync, a.created_at
-             FROM email_accounts a
-             ORDER BY a.created_at DESC"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM email_accounts a ORDER BY a.created_at DESC",
+            ACCOUNT_COLUMNS
+        ))?;
 
-        let account_iter = stmt.query_map([], |row| {
-            let encrypted_password: String = row.get(5)?;
-            let decrypted_password = CryptoService::decrypt_password(&encrypted_password)
-                .unwrap_or_else(|_| "***".to_string()); // 解密失败时显示占位符
-            
-            Ok(EmailAccount {
-                id: row.get(0)?,
-                provider_id: row.get(1)?,
-                email_address: row.get(2)?,
-                display_name: row.get(3)?,
-                username: row.get(4)?,
-                password: decrypted_password,
-                is_active: row.get(6)?,
-                last_sync: row.get(7)?,
-                created_at: row.get(8)?,
-            })
-        })?;
+        let account_iter = stmt.query_map([], Self::row_to_account)?;
 
         let mut accounts = Vec::new();
         for account in account_iter {
@@ -125,28 +115,39 @@ impl<'a> ProviderService<'a> {
         Ok(accounts)
     }
 
+    fn row_to_account(row: &rusqlite::Row) -> rusqlite::Result<EmailAccount> {
+        Ok(EmailAccount {
+            id: row.get(0)?,
+            provider_id: row.get(1)?,
+            email_address: row.get(2)?,
+            display_name: row.get(3)?,
+            username: row.get(4)?,
+            password: row.get(5)?,
+            auth_method: Self::auth_method(row, 9)?,
+            is_active: row.get(6)?,
+            last_sync: row.get(7)?,
+            created_at: row.get(8)?,
+        })
+    }
+
+    fn auth_method(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<AuthMethod> {
+        let value: String = row.get(idx)?;
+        AuthMethod::parse(&value).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                idx,
+                rusqlite::types::Type::Text,
+                format!("未知的登录方式: {}", value).into(),
+            )
+        })
+    }
+
     pub fn get_active_accounts(&self) -> Result<Vec<EmailAccount>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT a.id, a.provider_id, a.email_address, a.display_name, a.username, 
-                    a.password, a.is_active, a.last_sync, a.created_at
-             FROM email_accounts a
-             WHERE a.is_active = 1
-             ORDER BY a.created_at DESC"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM email_accounts a WHERE a.is_active = 1 ORDER BY a.created_at DESC",
+            ACCOUNT_COLUMNS
+        ))?;
 
-        let account_iter = stmt.query_map([], |row| {
-            Ok(EmailAccount {
-                id: row.get(0)?,
-                provider_id: row.get(1)?,
-                email_address: row.get(2)?,
-                display_name: row.get(3)?,
-                username: row.get(4)?,
-                password: row.get(5)?,
-                is_active: row.get(6)?,
-                last_sync: row.get(7)?,
-                created_at: row.get(8)?,
-            })
-        })?;
+        let account_iter = stmt.query_map([], Self::row_to_account)?;
 
         let mut accounts = Vec::new();
         for account in account_iter {
@@ -155,6 +156,44 @@ impl<'a> ProviderService<'a> {
         Ok(accounts)
     }
 
+    pub fn get_account(&self, account_id: i32) -> Result<Option<EmailAccount>> {
+        let account = self.conn.query_row(
+            &format!("SELECT {} FROM email_accounts a WHERE a.id = ?1", ACCOUNT_COLUMNS),
+            [account_id],
+            Self::row_to_account,
+        ).optional()?;
+
+        Ok(account)
+    }
+
+    /// 获取账户及其所属的邮件服务商
+    pub fn get_account_with_provider(&self, account_id: i32) -> Result<(EmailProvider, EmailAccount)> {
+        let account = self.get_account(account_id)?
+            .ok_or_else(|| anyhow::anyhow!("未找到邮件账户"))?;
+
+        let provider = self.get_all_providers()?
+            .into_iter()
+            .find(|p| p.id == account.provider_id)
+            .ok_or_else(|| anyhow::anyhow!("未找到邮件服务商"))?;
+
+        Ok((provider, account))
+    }
+
+    /// 修改密码登录账户的用户名和密码，密码加密后保存
+    pub fn update_account_credentials(&self, account_id: i32, username: &str, password: &str) -> Result<()> {
+        let account = self.get_account(account_id)?
+            .ok_or_else(|| anyhow::anyhow!("未找到邮件账户"))?;
+        if account.auth_method != AuthMethod::Password {
+            return Err(anyhow::anyhow!("OAuth2 账户没有密码，请重新授权"));
+        }
+
+        self.conn.execute(
+            "UPDATE email_accounts SET username = ?1, password = ?2 WHERE id = ?3",
+            params![username, CryptoService::encrypt_password(password)?, account_id],
+        )?;
+        Ok(())
+    }
+
     pub fn update_account_sync_time(&self, account_id: i32) -> Result<()> {
         self.conn.execute(
             "UPDATE email_accounts SET last_sync = ?1 WHERE id = ?2",
@@ -171,11 +210,23 @@ impl<'a> ProviderService<'a> {
         Ok(())
     }
 
+    /// 删除账户及其同步下来的邮件（邮件只是服务器上的副本，账户删除后无法再同步）
     pub fn delete_account(&self, account_id: i32) -> Result<()> {
-        self.conn.execute(
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM emails WHERE account_id = ?1",
+            params![account_id],
+        )?;
+        // 会话表没有外键，其余记录（令牌、文件夹、同步进度、发件箱等）由外键级联删除
+        tx.execute(
+            "DELETE FROM threads WHERE account_id = ?1",
+            params![account_id],
+        )?;
+        tx.execute(
             "DELETE FROM email_accounts WHERE id = ?1",
             params![account_id],
         )?;
+        tx.commit()?;
         Ok(())
     }
 
@@ -227,4 +278,34 @@ impl<'a> ProviderService<'a> {
         )?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+
+    #[test]
+    fn test_delete_account_removes_dependent_rows() {
+        let db = Database::new(":memory:").unwrap();
+        db.conn.execute(
+            "INSERT INTO email_accounts (id, provider_id, email_address, display_name, username, password, created_at)
+             VALUES (1, 1, 'me@example.com', '', 'me', '', '')",
+            [],
+        ).unwrap();
+        db.conn.execute(
+            "INSERT INTO oauth_tokens (account_id, access_token, refresh_token, updated_at)
+             VALUES (1, 'v1:access', 'v1:refresh', '2024-01-01T00:00:00Z')",
+            [],
+        ).unwrap();
+        db.conn.execute(
+            "INSERT INTO outbox (id, account_id, message, message_id, send_at, next_attempt_at, created_at, updated_at)
+             VALUES ('o1', 1, '{}', 'm@example.com', '', '', '', '')",
+            [],
+        ).unwrap();
+
+        ProviderService::new(&db.conn).delete_account(1).unwrap();
+        assert!(db.get_oauth_token(1).unwrap().is_none());
+        assert!(db.get_outbox_messages(Some(1)).unwrap().is_empty());
+    }
+}
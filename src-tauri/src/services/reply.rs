@@ -0,0 +1,198 @@
+use chrono::Local;
+use std::collections::HashSet;
+use crate::models::address::{format_list, EmailAddress};
+use crate::models::email::Email;
+use crate::models::outgoing::{OutgoingAttachment, OutgoingMessage};
+
+/// References 中最多保留的 Message-ID 数量，过长的头部会被部分服务器拒绝
+const MAX_REFERENCES: usize = 20;
+
+/// 生成回复：收件人为原邮件的回复地址（没有时为发件人）
+///
+/// 全部回复时，原邮件的其他收件人和抄送放入抄送。`own_addresses` 为所有账户的地址，
+/// 不会出现在收件人中；回复自己发出的邮件时改为回复原收件人。
+pub fn reply(original: &Email, own_addresses: &[String], reply_all: bool) -> OutgoingMessage {
+    let own: HashSet<String> = own_addresses.iter().map(|a| a.to_lowercase()).collect();
+    let is_own = |address: &EmailAddress| own.contains(&address.address.to_lowercase());
+
+    let sent_by_me = original.from.as_ref().is_some_and(is_own);
+    let primary: Vec<EmailAddress> = if sent_by_me {
+        original.to.clone()
+    } else if !original.reply_to.is_empty() {
+        original.reply_to.clone()
+    } else {
+        original.from.iter().cloned().collect()
+    };
+
+    let mut seen = HashSet::new();
+    let mut take = |addresses: &[EmailAddress]| -> Vec<String> {
+        addresses
+            .iter()
+            .filter(|address| !is_own(address))
+            .filter(|address| seen.insert(address.address.to_lowercase()))
+            .map(|address| address.to_string())
+            .collect()
+    };
+
+    let to = take(&primary);
+    let cc = if reply_all {
+        let others: Vec<EmailAddress> = original.to.iter().chain(&original.cc).cloned().collect();
+        take(&others)
+    } else {
+        Vec::new()
+    };
+
+    let attribution = format!("在 {}，{} 写道：", format_date(original), display_sender(original));
+    let text_body = format!("\n\n{}\n{}", attribution, quote_text(&original.body));
+    let html_body = original.html_body.as_ref().map(|html| {
+        format!(
+            "<p><br></p><div>{}</div><blockquote style=\"margin:0 0 0 .8ex;border-left:1px solid #ccc;padding-left:1ex\">{}</blockquote>",
+            escape_html(&attribution),
+            html
+        )
+    });
+
+    let mut references = original.references.clone();
+    if let Some(message_id) = &original.message_id {
+        references.retain(|id| id != message_id);
+        references.push(message_id.clone());
+    }
+    if references.len() > MAX_REFERENCES {
+        // 保留最早的祖先和最近的邮件
+        let skip = references.len() - MAX_REFERENCES;
+        references.drain(1..=skip);
+    }
+
+    OutgoingMessage {
+        to,
+        cc,
+        subject: prefixed_subject("Re: ", &original.subject, &REPLY_PREFIXES),
+        text_body: Some(text_body),
+        html_body,
+        in_reply_to: original.message_id.clone(),
+        references,
+        ..Default::default()
+    }
+}
+
+/// 生成转发：正文前附上原邮件的头部信息，并带上原邮件的附件
+///
+/// `attachments` 为原邮件在附件存储中的附件；纯文本转发时不包含内嵌资源。
+pub fn forward(original: &Email, attachments: Vec<OutgoingAttachment>) -> OutgoingMessage {
+    let mut header = vec![
+        "---------- 转发的邮件 ----------".to_string(),
+        format!("发件人: {}", display_sender(original)),
+        format!("日期: {}", format_date(original)),
+        format!("主题: {}", original.subject),
+        format!("收件人: {}", display_recipients(original)),
+    ];
+    if !original.cc.is_empty() {
+        header.push(format!("抄送: {}", format_list(&original.cc)));
+    }
+
+    let text_body = format!("\n\n{}\n\n{}", header.join("\n"), original.body);
+    let html_body = original.html_body.as_ref().map(|html| {
+        let header: Vec<String> = header.iter().map(|line| escape_html(line)).collect();
+        format!("<p><br></p><div>{}</div><br>{}", header.join("<br>"), html)
+    });
+
+    let attachments = if html_body.is_some() {
+        attachments
+    } else {
+        attachments.into_iter().filter(|a| a.content_id.is_none()).collect()
+    };
+
+    OutgoingMessage {
+        subject: prefixed_subject("Fwd: ", &original.subject, &FORWARD_PREFIXES),
+        text_body: Some(text_body),
+        html_body,
+        attachments,
+        ..Default::default()
+    }
+}
+
+const REPLY_PREFIXES: [&str; 5] = ["re", "aw", "sv", "回复", "答复"];
+const FORWARD_PREFIXES: [&str; 4] = ["fwd", "fw", "转发", "wg"];
+
+/// 去掉开头同类的前缀后加上统一的 `Re: ` / `Fwd: `，
+/// 例如 `回复：RE: 周报` 回复后为 `Re: 周报`
+fn prefixed_subject(prefix: &str, subject: &str, known: &[&str]) -> String {
+    let pattern = format!(
+        r"(?i)^\s*(?:{})(?:\[\d+\]|\(\d+\))?\s*[:：]\s*",
+        known.join("|")
+    );
+    let re = regex::Regex::new(&pattern).unwrap();
+
+    let mut rest = subject.trim();
+    while let Some(m) = re.find(rest) {
+        rest = &rest[m.end()..];
+    }
+    format!("{}{}", prefix, rest)
+}
+
+fn display_sender(email: &Email) -> String {
+    email.from.as_ref().map(|from| from.to_string()).unwrap_or_else(|| email.sender.clone())
+}
+
+fn display_recipients(email: &Email) -> String {
+    if email.to.is_empty() {
+        email.recipient.clone()
+    } else {
+        format_list(&email.to)
+    }
+}
+
+fn format_date(email: &Email) -> String {
+    email
+        .sent_at
+        .unwrap_or(email.created_at)
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+fn quote_text(body: &str) -> String {
+    body.lines()
+        .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_all() {
+        let mut original = Email::new(String::new(), String::new(), "回复：RE: 周报".into(), "第一行\n\n第二行".into(), "收件箱".into());
+        original.set_addresses(
+            EmailAddress::parse_list("张三 <zhangsan@example.com>").pop(),
+            EmailAddress::parse_list("me@example.com, lisi@example.com"),
+            EmailAddress::parse_list("Me@Other.com, zhangsan@example.com, wangwu@example.com"),
+            Vec::new(),
+            Vec::new(),
+        );
+        original.message_id = Some("c@example.com".into());
+        original.references = vec!["a@example.com".into(), "b@example.com".into()];
+
+        let own = ["me@example.com".to_string(), "me@other.com".to_string()];
+        let message = reply(&original, &own, true);
+        assert_eq!(message.subject, "Re: 周报");
+        assert_eq!(message.to, vec!["张三 <zhangsan@example.com>"]);
+        assert_eq!(message.cc, vec!["lisi@example.com", "wangwu@example.com"]);
+        assert_eq!(message.in_reply_to.as_deref(), Some("c@example.com"));
+        assert_eq!(message.references, vec!["a@example.com", "b@example.com", "c@example.com"]);
+        assert!(message.text_body.unwrap().ends_with("写道：\n> 第一行\n>\n> 第二行"));
+
+        assert!(reply(&original, &own, false).cc.is_empty());
+
+        let forwarded = forward(&original, Vec::new());
+        assert_eq!(forwarded.subject, "Fwd: 回复：RE: 周报");
+        assert!(forwarded.to.is_empty() && forwarded.in_reply_to.is_none());
+        assert_eq!(prefixed_subject("Fwd: ", "转发: FW: 通知", &FORWARD_PREFIXES), "Fwd: 通知");
+    }
+}
@@ -1,28 +1,212 @@
 use anyhow::{Result, anyhow};
 use imap::Session;
-use native_tls::{TlsConnector, TlsStream};
+use imap::types::{Flag, NameAttribute};
+use lettre::SmtpTransport;
+use lettre::transport::smtp::response::Response as SmtpResponse;
+use native_tls::TlsConnector;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::net::TcpStream;
-use crate::models::email_provider::{EmailProvider, EmailAccount};
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::models::address::EmailAddress;
+use crate::models::email_provider::{AuthMethod, EmailProvider, EmailAccount, SecurityMode};
+use crate::models::attachment::Attachment;
 use crate::models::email::Email;
+use crate::models::folder::MailFolder;
+use crate::models::outgoing::OutgoingMessage;
+use crate::models::pending_operation::PendingOperation;
+use crate::models::sync_state::FolderSyncState;
+use crate::database::Database;
+use crate::services::blob_store::BlobStore;
+use crate::services::imap_response::{self, FlagUpdate, ImapClientId};
+use crate::services::compose;
+use crate::services::crypto_service::CryptoService;
+use crate::services::imap_stream::{self, ImapStream};
+use crate::services::mime_parser::{self, AttachmentPart};
+use crate::services::oauth_service::{self, OAuthService};
+use crate::services::provider_service::ProviderService;
+use crate::services::threading;
+
+/// 首次同步时每个文件夹获取的邮件数量
+pub const INITIAL_SYNC_LIMIT: usize = 50;
+
+/// 建立连接和登录阶段的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub type ImapSession = Session<ImapStream>;
+
+/// 连接测试结果，记录实际连接成功的加密方式
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionTestResult {
+    pub imap_security: SecurityMode,
+    pub smtp_security: SecurityMode,
+}
+
+/// 邮件发送结果
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryResult {
+    /// 本地保存的已发送邮件 ID
+    pub email_id: String,
+    /// Message-ID 头部（不含尖括号）
+    pub message_id: String,
+    pub recipients: Vec<String>,
+    /// SMTP 服务器对邮件数据的最终响应
+    pub smtp_code: String,
+    pub smtp_message: String,
+    pub sent_at: String,
+    /// 服务器的已发送文件夹中是否有这封邮件（服务商自动保存或已追加）
+    pub saved_to_sent_folder: bool,
+}
+
+impl DeliveryResult {
+    pub fn new(email: &Email, message: &OutgoingMessage, response: &SmtpResponse) -> Self {
+        let recipients = message
+            .all_recipients()
+            .filter_map(|address| address.trim().parse::<lettre::message::Mailbox>().ok())
+            .map(|mailbox| mailbox.email.to_string())
+            .collect();
+
+        Self {
+            email_id: email.id.clone(),
+            message_id: email.message_id.clone().unwrap_or_default(),
+            recipients,
+            smtp_code: response.code().to_string(),
+            smtp_message: response.message().collect::<Vec<_>>().join("\n"),
+            sent_at: email.created_at.to_rfc3339(),
+            saved_to_sent_folder: false,
+        }
+    }
+}
+
+/// 生成新的 Message-ID（不含尖括号），域名取发件地址的域名
+pub fn generate_message_id(address: &str) -> String {
+    let domain = address.rsplit_once('@').map(|(_, domain)| domain).unwrap_or("localhost");
+    format!("{}@{}", uuid::Uuid::new_v4(), domain)
+}
+
+/// 单个文件夹的同步结果
+#[derive(Debug)]
+pub struct FolderSyncResult {
+    pub folder: String,
+    pub uid_validity: u32,
+    /// 本次同步后已知的最大 UID
+    pub last_uid: u32,
+    /// 文件夹当前的 HIGHESTMODSEQ，不支持 CONDSTORE 时为 0
+    pub highest_modseq: u64,
+    /// UIDVALIDITY 与上次不同，本地缓存需要重建
+    pub uid_validity_changed: bool,
+    pub emails: Vec<Email>,
+    /// 新邮件的附件，键为邮件 ID
+    pub attachments: HashMap<String, Vec<AttachmentPart>>,
+    /// 已同步邮件在服务器上的最新标记
+    pub flag_updates: Vec<FlagUpdate>,
+    /// 服务器上已删除的 UID 区间（闭区间）
+    pub vanished: Vec<(u32, u32)>,
+}
+
+/// 一次账户同步的结果
+#[derive(Debug)]
+pub struct AccountSyncResult {
+    /// 本次通过 LIST 发现的文件夹，未重新获取时为 None
+    pub discovered_folders: Option<Vec<MailFolder>>,
+    pub folders: Vec<FolderSyncResult>,
+    /// 已成功推送到服务器的离线标记修改
+    pub pushed_operations: Vec<PendingOperation>,
+}
+
+/// 服务器支持的同步扩展
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncExtensions {
+    pub condstore: bool,
+    pub qresync: bool,
+    /// Gmail 扩展（X-GM-EXT-1），可以获取 X-GM-THRID
+    pub gmail: bool,
+}
+
+/// SELECT 后得到的文件夹状态
+struct SelectedFolder {
+    exists: u32,
+    uid_validity: u32,
+    highest_modseq: Option<u64>,
+    /// 使用 QRESYNC 选择时，响应中附带的标记变化和已删除 UID
+    qresync: Option<(Vec<FlagUpdate>, Vec<(u32, u32)>)>,
+}
+
+/// IMAP ID 上报的客户端信息（JSON）的设置项
+pub const IMAP_CLIENT_ID_SETTING: &str = "imap_client_id";
+
+/// 通过 IMAP ID 上报的客户端信息，未设置时使用默认值
+pub fn imap_client_id(db: &Database) -> Result<ImapClientId> {
+    Ok(db
+        .get_setting(IMAP_CLIENT_ID_SETTING)?
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default())
+}
+
+pub fn set_imap_client_id(db: &Database, client_id: &ImapClientId) -> Result<()> {
+    if client_id.name.trim().is_empty() {
+        return Err(anyhow!("客户端名称不能为空"));
+    }
+    db.set_setting(IMAP_CLIENT_ID_SETTING, &serde_json::to_string(client_id)?)
+}
 
 pub struct EmailSyncService {
     provider: EmailProvider,
     account: EmailAccount,
+    client_id: ImapClientId,
+    /// OAuth2 账户的访问令牌
+    access_token: Option<String>,
 }
 
 impl EmailSyncService {
     pub fn new(provider: EmailProvider, account: EmailAccount) -> Self {
-        Self { provider, account }
+        Self {
+            provider,
+            account,
+            client_id: ImapClientId::default(),
+            access_token: None,
+        }
     }
 
-    pub async fn connect_imap(&self) -> Result<Session<TlsStream<TcpStream>>> {
-        use crate::services::crypto_service::CryptoService;
-        
-        let domain = &self.provider.imap_server;
-        let port = self.provider.imap_port;
+    /// 从数据库加载账户，OAuth2 账户会同时获取（必要时刷新）访问令牌
+    pub async fn for_account(db: &Mutex<Database>, account_id: i32) -> Result<Self> {
+        let (provider, account, client_id) = {
+            let db = db.lock().map_err(|e| anyhow!(e.to_string()))?;
+            let (provider, account) = ProviderService::new(&db.conn).get_account_with_provider(account_id)?;
+            (provider, account, imap_client_id(&db)?)
+        };
+
+        let access_token = OAuthService::access_token(db, &provider, &account).await?;
+        Ok(Self::new(provider, account)
+            .with_client_id(client_id)
+            .with_access_token(access_token))
+    }
+
+    pub fn with_access_token(mut self, access_token: Option<String>) -> Self {
+        self.access_token = access_token;
+        self
+    }
+
+    pub fn account(&self) -> &EmailAccount {
+        &self.account
+    }
 
-        // 解密密码
-        let password = CryptoService::decrypt_password(&self.account.password)?;
+    /// 设置通过 IMAP ID 命令上报的客户端信息
+    pub fn with_client_id(mut self, client_id: ImapClientId) -> Self {
+        self.client_id = client_id;
+        self
+    }
+
+    pub async fn connect_imap(&self) -> Result<ImapSession> {
+        let (session, _) = self.connect_imap_with_socket().await?;
+        Ok(session)
+    }
+
+    /// 建立 IMAP 会话，同时返回底层 TCP 连接的副本，
+    /// 用于在其他线程中关闭连接以打断阻塞中的 IDLE
+    pub async fn connect_imap_with_socket(&self) -> Result<(ImapSession, TcpStream)> {
+        let password = self.login_secret()?;
 
         // 重试连接（最多3次）
         let mut attempts = 0;
@@ -31,8 +215,8 @@ impl EmailSyncService {
         while attempts < max_attempts {
             attempts += 1;
             
-            match self.try_connect_imap(domain, port, &password).await {
-                Ok(session) => return Ok(session),
+            match self.try_connect_imap(self.provider.imap_security, &password).await {
+                Ok(connection) => return Ok(connection),
                 Err(e) => {
                     eprintln!("IMAP连接失败 (尝试 {}/{}): {}", attempts, max_attempts, e);
                     if attempts < max_attempts {
@@ -49,171 +233,672 @@ impl EmailSyncService {
         Err(anyhow!("IMAP连接失败，已重试 {} 次", max_attempts))
     }
 
-    async fn try_connect_imap(&self, domain: &str, port: u16, password: &str) -> Result<Session<TlsStream<TcpStream>>> {
+    async fn try_connect_imap(&self, security: SecurityMode, password: &str) -> Result<(ImapSession, TcpStream)> {
+        let domain = self.provider.imap_server.as_str();
+        let port = self.provider.imap_port;
+
         // 建立TCP连接（30秒超时）
         let tcp_stream = tokio::time::timeout(
-            std::time::Duration::from_secs(30),
+            CONNECT_TIMEOUT,
             tokio::net::TcpStream::connect((domain, port))
         ).await??;
         
-        // 转换为同步流
+        // 转换为同步流（into_std 得到的是非阻塞套接字，imap crate 需要阻塞读写）
         let std_stream = tcp_stream.into_std()?;
+        std_stream.set_nonblocking(false)?;
+        // 加密方式与端口不匹配时服务器可能一直不响应，握手和登录阶段需要读超时
+        std_stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+        let socket = std_stream.try_clone()?;
         
-        // 建立TLS连接
-        let tls = TlsConnector::new()?;
-        let tls_stream = tls.connect(domain, std_stream)?;
+        // 按配置的加密方式建立连接
+        let stream = match security {
+            SecurityMode::ImplicitTls => {
+                let tls = TlsConnector::new()?;
+                ImapStream::Tls(tls.connect(domain, std_stream)?)
+            }
+            SecurityMode::StartTls => {
+                imap_stream::imap_starttls(&std_stream)?;
+                let tls = TlsConnector::new()?;
+                ImapStream::Tls(tls.connect(domain, std_stream)?)
+            }
+            SecurityMode::None => ImapStream::Plain(std_stream),
+        };
 
-        // 创建IMAP会话
-        let client = imap::Client::new(tls_stream);
+        // 创建IMAP会话（STARTTLS 时问候已在升级前读取）
+        let mut client = imap::Client::new(stream);
+        if security != SecurityMode::StartTls {
+            client.read_greeting()?;
+        }
         
         // 登录
-        let session = client
-            .login(&self.account.username, password)
-            .map_err(|e| anyhow!("IMAP登录失败: {:?}", e.0))?;
+        let mut session = match self.account.auth_method {
+            AuthMethod::Password => client
+                .login(&self.account.username, password)
+                .map_err(|e| anyhow!("IMAP登录失败: {:?}", e.0))?,
+            AuthMethod::OAuth2 => {
+                let authenticator = XOAuth2 {
+                    user: self.account.username.clone(),
+                    access_token: password.to_string(),
+                };
+                client
+                    .authenticate("XOAUTH2", &authenticator)
+                    .map_err(|e| anyhow!("IMAP XOAUTH2 认证失败: {:?}", e.0))?
+            }
+        };
 
-        Ok(session)
+        self.send_client_id(&mut session);
+        socket.set_read_timeout(None)?;
+
+        Ok((session, socket))
     }
 
-    pub async fn fetch_emails(&self, folder: &str, limit: usize) -> Result<Vec<Email>> {
-        let mut session = self.connect_imap().await?;
-        
+    /// 服务器声明 ID 能力或服务商要求时发送 IMAP ID 命令
+    ///
+    /// 网易邮箱（163/126）未收到 ID 时会以 "Unsafe Login" 拒绝 SELECT。
+    fn send_client_id(&self, session: &mut ImapSession) {
+        let advertised = session
+            .capabilities()
+            .map(|caps| caps.has_str("ID"))
+            .unwrap_or(false);
+        if !advertised && !self.provider.requires_imap_id {
+            return;
+        }
+
+        if let Err(e) = session.run_command_and_read_response(&self.client_id.command()) {
+            eprintln!("发送 IMAP ID 失败: {}", e);
+        }
+    }
+
+    /// 检测 CONDSTORE/QRESYNC 支持；QRESYNC 需要先 ENABLE 才能在 SELECT 中使用
+    pub fn detect_extensions(&self, session: &mut ImapSession) -> SyncExtensions {
+        let (condstore, qresync, gmail) = match session.capabilities() {
+            Ok(caps) => (caps.has_str("CONDSTORE"), caps.has_str("QRESYNC"), caps.has_str("X-GM-EXT-1")),
+            Err(_) => (false, false, false),
+        };
+
+        let qresync = qresync && session.run_command_and_read_response("ENABLE QRESYNC").is_ok();
+
+        // QRESYNC 隐含 CONDSTORE
+        SyncExtensions {
+            condstore: condstore || qresync,
+            qresync,
+            gmail,
+        }
+    }
+
+    /// 增量同步一个文件夹：同步已有邮件的标记和删除，再获取上次同步之后的新 UID
+    ///
+    /// 首次同步或 UIDVALIDITY 变化时，只取最近的 `limit` 封邮件重建缓存。
+    pub fn sync_folder(
+        &self,
+        session: &mut ImapSession,
+        folder: &MailFolder,
+        state: Option<&FolderSyncState>,
+        extensions: SyncExtensions,
+        limit: usize,
+    ) -> Result<FolderSyncResult> {
         // 选择邮箱文件夹
-        session.select(folder)?;
+        let selected = self.select_folder(session, &folder.name, state, extensions)?;
+        let uid_validity = selected.uid_validity;
 
-        // 搜索邮件
-        let sequences = session.search("ALL")?;
-        
-        let mut emails = Vec::new();
-        let start = if sequences.len() > limit {
-            sequences.len() - limit
+        let uid_validity_changed = state
+            .map(|s| s.uid_validity != uid_validity)
+            .unwrap_or(false);
+        let last_uid = match state {
+            Some(s) if !uid_validity_changed => s.last_uid,
+            _ => 0,
+        };
+
+        let (flag_updates, vanished) = match state {
+            Some(state) if last_uid > 0 => self.resync_flags(session, state, &selected, extensions)?,
+            _ => (Vec::new(), Vec::new()),
+        };
+
+        let uid_set = if last_uid > 0 {
+            format!("{}:*", last_uid + 1)
         } else {
-            0
+            let mut uids: Vec<u32> = session.uid_search("ALL")?.into_iter().collect();
+            uids.sort_unstable();
+            let start = uids.len().saturating_sub(limit);
+            uids[start..]
+                .iter()
+                .map(|uid| uid.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
         };
 
-        for seq in &sequences[start..] {
-            if let Ok(messages) = session.fetch(format!("{}", seq), "RFC822") {
-                for message in &messages {
-                    if let Some(body) = message.body() {
-                        match self.parse_email_from_raw(body) {
-                            Ok(email) => emails.push(email),
-                            Err(e) => eprintln!("解析邮件失败: {}", e),
+        let mut emails = Vec::new();
+        let mut attachments = HashMap::new();
+        let mut max_uid = last_uid;
+
+        if selected.exists > 0 && !uid_set.is_empty() {
+            // BODY.PEEK[] 不会把邮件标记为已读
+            let messages = session.uid_fetch(&uid_set, "(UID FLAGS INTERNALDATE BODY.PEEK[])")?;
+
+            for message in messages.iter() {
+                // `n:*` 在没有新邮件时也会返回当前最大的 UID，需要过滤掉
+                let uid = match message.uid {
+                    Some(uid) if uid > last_uid => uid,
+                    _ => continue,
+                };
+                max_uid = max_uid.max(uid);
+
+                if let Some(body) = message.body() {
+                    match self.parse_email_from_raw(body) {
+                        Ok((mut email, parts)) => {
+                            email.account_id = Some(self.account.id);
+                            email.folder = Some(folder.name.clone());
+                            email.uid = Some(uid);
+                            email.category = folder.category.clone();
+                            email.is_read = message.flags().contains(&Flag::Seen);
+                            email.is_important = message.flags().contains(&Flag::Flagged);
+                            email.received_at = message.internal_date().map(|date| date.with_timezone(&chrono::Utc));
+                            if email.sent_at.is_none() {
+                                email.sent_at = email.received_at;
+                            }
+                            if !parts.is_empty() {
+                                attachments.insert(email.id.clone(), parts);
+                            }
+                            emails.push(email);
+                        }
+                        Err(e) => eprintln!("解析邮件失败: {}", e),
+                    }
+                }
+            }
+
+            // Gmail 的会话 ID 不在标准 FETCH 响应中，单独获取
+            if extensions.gmail && !emails.is_empty() {
+                match session.run_command_and_read_response(format!("UID FETCH {} (UID X-GM-THRID)", uid_set)) {
+                    Ok(raw) => {
+                        let thread_ids = imap_response::parse_untagged(&raw).gmail_thread_ids;
+                        for email in &mut emails {
+                            email.gmail_thread_id = email.uid.and_then(|uid| thread_ids.get(&uid).cloned());
                         }
                     }
+                    Err(e) => eprintln!("获取 Gmail 会话 ID 失败: {}", e),
                 }
             }
         }
 
-        // 登出
-        session.logout()?;
-        
-        Ok(emails)
+        Ok(FolderSyncResult {
+            folder: folder.name.clone(),
+            uid_validity,
+            last_uid: max_uid,
+            highest_modseq: selected.highest_modseq.unwrap_or(0),
+            uid_validity_changed,
+            emails,
+            attachments,
+            flag_updates,
+            vanished,
+        })
     }
 
-    fn parse_email_from_raw(&self, raw_email: &[u8]) -> Result<Email> {
-        let email_str = String::from_utf8_lossy(raw_email);
-        
-        // 简单的邮件解析（实际应用中应该使用专门的邮件解析库）
-        let mut sender = String::new();
-        let mut recipient = String::new();
-        let mut subject = String::new();
-        let mut body = String::new();
-        
-        let mut in_body = false;
-        let mut body_lines = Vec::new();
-        
-        for line in email_str.lines() {
-            if in_body {
-                body_lines.push(line);
-                continue;
+    fn select_folder(
+        &self,
+        session: &mut ImapSession,
+        folder: &str,
+        state: Option<&FolderSyncState>,
+        extensions: SyncExtensions,
+    ) -> Result<SelectedFolder> {
+        if !extensions.condstore {
+            let mailbox = session.select(folder)?;
+            return Ok(SelectedFolder {
+                exists: mailbox.exists,
+                uid_validity: mailbox.uid_validity.unwrap_or(0),
+                highest_modseq: None,
+                qresync: None,
+            });
+        }
+
+        // imap crate 不解析 HIGHESTMODSEQ / VANISHED，需要直接读取原始响应
+        let mailbox = imap_response::quote_string(folder);
+        let (command, use_qresync) = match state {
+            Some(s) if extensions.qresync && s.highest_modseq > 0 => (
+                format!("SELECT {} (QRESYNC ({} {}))", mailbox, s.uid_validity, s.highest_modseq),
+                true,
+            ),
+            _ => (format!("SELECT {} (CONDSTORE)", mailbox), false),
+        };
+
+        let data = imap_response::parse_untagged(&session.run_command_and_read_response(&command)?);
+
+        Ok(SelectedFolder {
+            exists: data.exists.unwrap_or(0),
+            uid_validity: data.uid_validity.unwrap_or(0),
+            highest_modseq: if data.no_modseq { None } else { data.highest_modseq },
+            qresync: if use_qresync {
+                Some((data.flag_updates, data.vanished))
+            } else {
+                None
+            },
+        })
+    }
+
+    /// 获取已同步邮件的标记变化和被删除的 UID 区间
+    fn resync_flags(
+        &self,
+        session: &mut ImapSession,
+        state: &FolderSyncState,
+        selected: &SelectedFolder,
+        extensions: SyncExtensions,
+    ) -> Result<(Vec<FlagUpdate>, Vec<(u32, u32)>)> {
+        let last_uid = state.last_uid;
+
+        // QRESYNC：SELECT 的响应中已经包含变化
+        if let Some((updates, vanished)) = &selected.qresync {
+            return Ok((updates.clone(), vanished.clone()));
+        }
+
+        if selected.exists == 0 {
+            return Ok((Vec::new(), vec![(1, last_uid)]));
+        }
+
+        match selected.highest_modseq {
+            Some(modseq) if extensions.condstore && state.highest_modseq > 0 => {
+                if modseq == state.highest_modseq {
+                    return Ok((Vec::new(), Vec::new()));
+                }
+
+                // CONDSTORE：只取 MODSEQ 变化过的邮件，删除通过 UID 列表比较得到
+                let raw = session.run_command_and_read_response(format!(
+                    "UID FETCH 1:{} (UID FLAGS) (CHANGEDSINCE {})",
+                    last_uid, state.highest_modseq
+                ))?;
+                let updates = imap_response::parse_untagged(&raw).flag_updates;
+
+                let existing: Vec<u32> = session
+                    .uid_search(format!("UID 1:{}", last_uid))?
+                    .into_iter()
+                    .collect();
+
+                Ok((updates, imap_response::missing_uid_ranges(&existing, last_uid)))
             }
-            
-            if line.is_empty() {
-                in_body = true;
-                continue;
+            _ => {
+                // 不支持 CONDSTORE 时比较全部已同步邮件的 UID 和标记
+                let raw = session.run_command_and_read_response(format!(
+                    "UID FETCH 1:{} (UID FLAGS)",
+                    last_uid
+                ))?;
+                let updates = imap_response::parse_untagged(&raw).flag_updates;
+                let existing: Vec<u32> = updates.iter().map(|u| u.uid).collect();
+
+                Ok((updates, imap_response::missing_uid_ranges(&existing, last_uid)))
             }
-            
-            if line.starts_with("From: ") {
-                sender = self.extract_email_address(&line[6..]);
-            } else if line.starts_with("To: ") {
-                recipient = self.extract_email_address(&line[4..]);
-            } else if line.starts_with("Subject: ") {
-                subject = line[9..].to_string();
+        }
+    }
+
+    /// 通过 `LIST "" "*"` 获取账户的全部文件夹
+    pub fn discover_folders(&self, session: &mut ImapSession) -> Result<Vec<MailFolder>> {
+        let names = session.list(Some(""), Some("*"))?;
+
+        let folders = names
+            .iter()
+            .map(|name| {
+                let attributes: Vec<String> = name
+                    .attributes()
+                    .iter()
+                    .map(|attr| match attr {
+                        NameAttribute::NoInferiors => "\\Noinferiors".to_string(),
+                        NameAttribute::NoSelect => "\\Noselect".to_string(),
+                        NameAttribute::Marked => "\\Marked".to_string(),
+                        NameAttribute::Unmarked => "\\Unmarked".to_string(),
+                        NameAttribute::Custom(custom) => custom.to_string(),
+                    })
+                    .collect();
+
+                MailFolder::from_list(
+                    self.account.id,
+                    name.name(),
+                    &imap_response::decode_mailbox_name(name.name()),
+                    name.delimiter(),
+                    &attributes,
+                )
+            })
+            .collect();
+
+        Ok(folders)
+    }
+
+    /// 在已登录的会话上重放离线标记修改并同步指定文件夹
+    ///
+    /// 单个文件夹失败只记录日志，不影响其他文件夹。
+    pub fn sync_folders(
+        &self,
+        session: &mut ImapSession,
+        folders: &[MailFolder],
+        states: &HashMap<String, FolderSyncState>,
+        pending: &[PendingOperation],
+        extensions: SyncExtensions,
+    ) -> AccountSyncResult {
+        // 先重放离线期间的标记修改，避免被服务器上的旧状态覆盖
+        let pushed_operations = if pending.is_empty() {
+            Vec::new()
+        } else {
+            match self.push_flag_changes(session, pending) {
+                Ok(done) => done,
+                Err(e) => {
+                    eprintln!("推送标记修改失败: {}", e);
+                    Vec::new()
+                }
+            }
+        };
+
+        let mut results = Vec::new();
+        for folder in folders.iter().filter(|folder| folder.should_sync()) {
+            match self.sync_folder(session, folder, states.get(&folder.name), extensions, INITIAL_SYNC_LIMIT) {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    eprintln!("同步文件夹 {} 失败: {}", folder.display_name, e);
+                }
             }
         }
-        
-        body = body_lines.join("\n");
-        
-        // 如果收件人为空，使用当前账户邮箱
-        if recipient.is_empty() {
-            recipient = self.account.email_address.clone();
+
+        AccountSyncResult {
+            discovered_folders: None,
+            folders: results,
+            pushed_operations,
         }
-        
-        Ok(Email {
-            id: uuid::Uuid::new_v4().to_string(),
-            sender,
-            recipient,
-            subject,
-            body,
-            category: "收件箱".to_string(),
-            is_read: false,
-            is_important: false,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        })
     }
-    
-    fn extract_email_address(&self, header_value: &str) -> String {
-        // 简单的邮箱地址提取
-        if let Some(start) = header_value.find('<') {
-            if let Some(end) = header_value.find('>') {
-                return header_value[start + 1..end].to_string();
+
+    /// 将本地的标记修改推送到服务器，返回已处理完的操作
+    ///
+    /// 服务器拒绝的操作（邮件或文件夹已不存在）同样视为已处理；
+    /// 网络错误直接返回，队列中的操作会在下次连接时重放（STORE 是幂等的）。
+    pub fn push_flag_changes(
+        &self,
+        session: &mut ImapSession,
+        operations: &[PendingOperation],
+    ) -> Result<Vec<PendingOperation>> {
+        let mut done = Vec::new();
+
+        let mut folders: Vec<&str> = operations.iter().map(|op| op.folder.as_str()).collect();
+        folders.sort_unstable();
+        folders.dedup();
+
+        for folder in folders {
+            let folder_operations = operations.iter().filter(|op| op.folder == folder);
+
+            match session.select(folder) {
+                Ok(_) => {}
+                Err(imap::error::Error::No(_)) | Err(imap::error::Error::Bad(_)) => {
+                    eprintln!("文件夹 {} 不存在，丢弃其中的标记修改", folder);
+                    done.extend(folder_operations.cloned());
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            }
+
+            for operation in folder_operations {
+                match session.uid_store(operation.uid.to_string(), operation.store_query()) {
+                    Ok(_) => {}
+                    Err(imap::error::Error::No(e)) | Err(imap::error::Error::Bad(e)) => {
+                        eprintln!("服务器拒绝标记修改 (UID {}): {}", operation.uid, e);
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+                done.push(operation.clone());
             }
         }
-        
-        // 如果没有尖括号，尝试提取邮箱格式的字符串
-        let re = regex::Regex::new(r"[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}").unwrap();
-        if let Some(mat) = re.find(header_value) {
-            return mat.as_str().to_string();
+
+        Ok(done)
+    }
+
+    fn parse_email_from_raw(&self, raw_email: &[u8]) -> Result<(Email, Vec<AttachmentPart>)> {
+        let parsed = mime_parser::parse_message(raw_email)?;
+
+        let from = parsed.addresses("From").into_iter().next();
+        let mut to = parsed.addresses("To");
+        let subject = parsed.decoded_header("Subject").unwrap_or_default();
+
+        // 如果收件人为空（例如只有密送），使用当前账户邮箱
+        if to.is_empty() && parsed.addresses("Cc").is_empty() {
+            to.push(EmailAddress::new(None, self.account.email_address.clone()));
         }
-        
-        header_value.trim().to_string()
+
+        let mut email = Email::new(
+            String::new(),
+            String::new(),
+            subject,
+            parsed.plain_text(),
+            "收件箱".to_string(),
+        );
+        email.set_addresses(
+            from,
+            to,
+            parsed.addresses("Cc"),
+            Vec::new(),
+            parsed.addresses("Reply-To"),
+        );
+        email.html_body = parsed.html_body.clone();
+        email.sent_at = parsed.date();
+        email.message_id = parsed.header("Message-ID").and_then(mime_parser::parse_message_id);
+        email.in_reply_to = parsed
+            .header("In-Reply-To")
+            .and_then(|v| mime_parser::parse_message_id_list(v).pop());
+        email.references = parsed
+            .header("References")
+            .map(mime_parser::parse_message_id_list)
+            .unwrap_or_default();
+        email.has_attachment = parsed.has_attachment();
+
+        Ok((email, parsed.attachments))
     }
 
-    pub async fn send_email(&self, email: &Email) -> Result<()> {
-        use lettre::{Message, SmtpTransport, Transport};
-        use lettre::transport::smtp::authentication::Credentials;
+    /// 以账户的显示名称和地址作为发件人发送邮件，返回 SMTP 响应和发出的 RFC 822 内容
+    ///
+    /// 投递地址包括收件人、抄送和密送，`attachments` 由 `compose::load_attachments` 读取。
+    pub async fn send_email(
+        &self,
+        message: &OutgoingMessage,
+        message_id: &str,
+        attachments: &[AttachmentPart],
+    ) -> Result<(SmtpResponse, Vec<u8>)> {
+        use lettre::Transport;
 
-        // 构建邮件
-        let message = Message::builder()
-            .from(email.sender.parse()?)
-            .to(email.recipient.parse()?)
-            .subject(&email.subject)
-            .body(email.body.clone())?;
+        let message = compose::build_message(self.from_mailbox()?, message_id, message, attachments)?;
+        let raw = message.formatted();
 
         // 配置SMTP
-        let creds = Credentials::new(
-            self.account.username.clone(),
-            self.account.password.clone(),
-        );
+        let password = self.login_secret()?;
+        let mailer = self.smtp_transport(self.provider.smtp_security, &password)?;
+
+        // 发送邮件
+        let response = mailer.send_raw(message.envelope(), &raw)?;
+        Ok((response, raw))
+    }
+
+    /// 发出的邮件需要由客户端保存到已发送文件夹
+    pub fn needs_sent_copy(&self) -> bool {
+        !self.provider.saves_sent_copy()
+    }
+
+    /// 把已发送的邮件原样追加到已发送文件夹，并标记为已读
+    pub fn append_sent(&self, session: &mut ImapSession, folder: &str, content: &[u8]) -> Result<()> {
+        session.append_with_flags(folder, content, &[Flag::Seen])?;
+        Ok(())
+    }
+
+    /// 构建草稿的 RFC 822 内容，`attachments` 由 `compose::load_attachments` 读取
+    pub fn build_draft(
+        &self,
+        message: &OutgoingMessage,
+        message_id: &str,
+        attachments: &[AttachmentPart],
+    ) -> Result<Vec<u8>> {
+        let message = compose::build_draft(self.from_mailbox()?, message_id, message, attachments)?;
+        Ok(message.formatted())
+    }
+
+    /// 账户的显示名称和地址
+    fn from_mailbox(&self) -> Result<lettre::message::Mailbox> {
+        Ok(lettre::message::Mailbox::new(
+            Some(self.account.display_name.clone()).filter(|name| !name.is_empty()),
+            self.account.email_address.parse()?,
+        ))
+    }
 
-        let mailer = SmtpTransport::relay(&self.provider.smtp_server)?
+    /// 把草稿追加到草稿箱（带 `\Draft` 标记），再删除同一 Message-ID 的旧副本
+    ///
+    /// 先追加后删除，追加失败时服务器上仍保留上一版本。
+    pub fn replace_draft(
+        &self,
+        session: &mut ImapSession,
+        folder: &str,
+        message_id: &str,
+        content: &[u8],
+    ) -> Result<()> {
+        session.select(folder)?;
+        let previous = self.search_message_id(session, message_id)?;
+
+        session.append_with_flags(folder, content, &[Flag::Seen, Flag::Draft])?;
+        self.expunge_uids(session, &previous)
+    }
+
+    /// 删除文件夹中指定 Message-ID 的全部邮件
+    pub fn delete_by_message_id(&self, session: &mut ImapSession, folder: &str, message_id: &str) -> Result<()> {
+        session.select(folder)?;
+        let uids = self.search_message_id(session, message_id)?;
+        self.expunge_uids(session, &uids)
+    }
+
+    fn search_message_id(&self, session: &mut ImapSession, message_id: &str) -> Result<Vec<u32>> {
+        let query = format!("HEADER Message-ID {}", imap_response::quote_string(&format!("<{}>", message_id)));
+        let mut uids: Vec<u32> = session.uid_search(query)?.into_iter().collect();
+        uids.sort_unstable();
+        Ok(uids)
+    }
+
+    /// 标记删除并清除指定的邮件
+    ///
+    /// 服务器不支持 UIDPLUS 时只能使用 EXPUNGE，会一并清除文件夹中其他已标记删除的邮件。
+    fn expunge_uids(&self, session: &mut ImapSession, uids: &[u32]) -> Result<()> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+
+        let uid_set = uids.iter().map(|uid| uid.to_string()).collect::<Vec<_>>().join(",");
+        session.uid_store(&uid_set, "+FLAGS.SILENT (\\Deleted)")?;
+
+        let uidplus = session
+            .capabilities()
+            .map(|caps| caps.has_str("UIDPLUS"))
+            .unwrap_or(false);
+        if uidplus {
+            session.uid_expunge(&uid_set)?;
+        } else {
+            session.expunge()?;
+        }
+        Ok(())
+    }
+
+    /// 按加密方式创建 SMTP 连接：隐式 TLS 使用 `relay`，STARTTLS 使用 `starttls_relay`
+    fn smtp_transport(&self, security: SecurityMode, password: &str) -> Result<SmtpTransport> {
+        use lettre::transport::smtp::authentication::{Credentials, Mechanism};
+
+        let server = self.provider.smtp_server.as_str();
+        let builder = match security {
+            SecurityMode::ImplicitTls => SmtpTransport::relay(server)?,
+            SecurityMode::StartTls => SmtpTransport::starttls_relay(server)?,
+            SecurityMode::None => SmtpTransport::builder_dangerous(server),
+        };
+
+        let creds = Credentials::new(self.account.username.clone(), password.to_string());
+        let mut builder = builder
             .port(self.provider.smtp_port)
             .credentials(creds)
-            .build();
+            .timeout(Some(CONNECT_TIMEOUT));
 
-        // 发送邮件
-        mailer.send(&message)?;
-        
-        Ok(())
+        if self.account.auth_method == AuthMethod::OAuth2 {
+            builder = builder.authentication(vec![Mechanism::Xoauth2]);
+        }
+
+        Ok(builder.build())
+    }
+
+    /// 登录凭据：密码账户为解密后的密码，OAuth2 账户为访问令牌
+    fn login_secret(&self) -> Result<String> {
+        match self.account.auth_method {
+            AuthMethod::Password => {
+                let password = CryptoService::decrypt_password(&self.account.password)?;
+                // 重置凭据后密码为空，需要用户重新输入
+                if password.is_empty() {
+                    return Err(anyhow!("账户 {} 的密码需要重新输入", self.account.email_address));
+                }
+                Ok(password)
+            }
+            AuthMethod::OAuth2 => self
+                .access_token
+                .clone()
+                .ok_or_else(|| anyhow!("账户 {} 缺少 OAuth2 访问令牌，请重新授权", self.account.email_address)),
+        }
     }
 
-    pub async fn test_connection(&self) -> Result<bool> {
-        match self.connect_imap().await {
-            Ok(mut session) => {
-                session.logout().ok();
-                Ok(true)
+    /// 测试 IMAP 和 SMTP 连接，返回实际成功的加密方式
+    ///
+    /// 先尝试服务商配置的方式，失败后再尝试另一种 TLS 方式，不会自动降级为明文。
+    pub async fn test_connection(&self) -> Result<ConnectionTestResult> {
+        let password = self.login_secret()?;
+
+        let mut imap_result = Err(anyhow!("IMAP连接失败"));
+        for security in Self::candidate_modes(self.provider.imap_security) {
+            match self.try_connect_imap(security, &password).await {
+                Ok((mut session, _)) => {
+                    session.logout().ok();
+                    imap_result = Ok(security);
+                    break;
+                }
+                Err(e) => imap_result = Err(anyhow!("IMAP连接失败 ({}): {}", security.as_str(), e)),
+            }
+        }
+        let imap_security = imap_result?;
+
+        let mut smtp_result = Err(anyhow!("SMTP连接失败"));
+        for security in Self::candidate_modes(self.provider.smtp_security) {
+            let connected = self
+                .smtp_transport(security, &password)
+                .and_then(|mailer| Ok(mailer.test_connection()?));
+            match connected {
+                Ok(true) => {
+                    smtp_result = Ok(security);
+                    break;
+                }
+                Ok(false) => smtp_result = Err(anyhow!("SMTP连接失败 ({})", security.as_str())),
+                Err(e) => smtp_result = Err(anyhow!("SMTP连接失败 ({}): {}", security.as_str(), e)),
             }
-            Err(_) => Ok(false)
+        }
+        let smtp_security = smtp_result?;
+
+        Ok(ConnectionTestResult {
+            imap_security,
+            smtp_security,
+        })
+    }
+
+    /// 连接测试时依次尝试的加密方式
+    fn candidate_modes(configured: SecurityMode) -> Vec<SecurityMode> {
+        match configured {
+            SecurityMode::ImplicitTls => vec![SecurityMode::ImplicitTls, SecurityMode::StartTls],
+            SecurityMode::StartTls => vec![SecurityMode::StartTls, SecurityMode::ImplicitTls],
+            SecurityMode::None => vec![SecurityMode::None],
+        }
+    }
+}
+
+/// IMAP XOAUTH2 认证器
+struct XOAuth2 {
+    user: String,
+    access_token: String,
+}
+
+impl imap::Authenticator for XOAuth2 {
+    type Response = String;
+
+    fn process(&self, challenge: &[u8]) -> Self::Response {
+        // 认证失败时服务器会返回包含错误信息的质询，回复空行结束认证
+        if challenge.is_empty() {
+            oauth_service::xoauth2_string(&self.user, &self.access_token)
+        } else {
+            String::new()
         }
     }
 }
@@ -230,37 +915,164 @@ impl SyncManager {
 
     pub async fn sync_account_emails(
         &self, 
-        provider: EmailProvider, 
-        account: EmailAccount
-    ) -> Result<Vec<Email>> {
-        let sync_service = EmailSyncService::new(provider, account);
-        
-        // 同步收件箱
-        let mut all_emails = Vec::new();
-        
-        // 尝试同步不同文件夹
-        let folders = vec!["INBOX", "Sent", "Drafts"];
+        sync_service: EmailSyncService,
+        states: &HashMap<String, FolderSyncState>,
+        pending: &[PendingOperation],
+    ) -> Result<AccountSyncResult> {
+        let mut session = sync_service.connect_imap().await?;
+
+        let extensions = sync_service.detect_extensions(&mut session);
+        let folders = sync_service.discover_folders(&mut session)?;
+
+        let mut result = sync_service.sync_folders(&mut session, &folders, states, pending, extensions);
+        result.discovered_folders = Some(folders);
+
+        // 登出
+        session.logout().ok();
         
-        for folder in folders {
-            match sync_service.fetch_emails(folder, 50).await {
-                Ok(mut emails) => {
-                    // 根据文件夹设置分类
-                    for email in &mut emails {
-                        email.category = match folder {
-                            "INBOX" => "收件箱".to_string(),
-                            "Sent" => "发件箱".to_string(),
-                            "Drafts" => "草稿箱".to_string(),
-                            _ => "其他".to_string(),
-                        };
-                    }
-                    all_emails.extend(emails);
-                }
-                Err(e) => {
-                    eprintln!("同步文件夹 {} 失败: {}", folder, e);
+        Ok(result)
+    }
+
+    /// 将同步结果写入本地数据库，附件内容写入附件存储，返回新增的邮件
+    pub fn save_sync_result(db: &Database, blobs: &BlobStore, account_id: i32, result: AccountSyncResult) -> Result<Vec<Email>> {
+        db.complete_pending_operations(&result.pushed_operations)?;
+
+        if let Some(folders) = &result.discovered_folders {
+            db.save_folders(account_id, folders)?;
+        }
+
+        let mut saved = Vec::new();
+        for folder in result.folders {
+            if folder.uid_validity_changed {
+                db.clear_folder_emails(account_id, &folder.folder)?;
+            }
+
+            // 同步已有邮件的标记变化，并删除服务器上已删除的邮件
+            db.apply_flag_updates(account_id, &folder.folder, &folder.flag_updates)?;
+            db.delete_vanished_emails(account_id, &folder.folder, &folder.vanished)?;
+
+            // 先写入附件内容，写入数据库失败时下次同步不会留下缺少内容的附件记录
+            let mut attachments = HashMap::new();
+            for (email_id, parts) in folder.attachments {
+                let stored = parts
+                    .into_iter()
+                    .map(|part| Ok((blobs.put(&part.data)?, part)))
+                    .collect::<Result<Vec<_>>>()?;
+                attachments.insert(email_id, stored);
+            }
+
+            let new_emails = db.save_synced_emails(folder.emails)?;
+            for email in &new_emails {
+                for (sha256, part) in attachments.remove(&email.id).unwrap_or_default() {
+                    Self::insert_attachment(db, &email.id, sha256, part)?;
                 }
             }
+            saved.extend(new_emails);
+
+            db.save_sync_state(&FolderSyncState {
+                account_id,
+                folder: folder.folder,
+                uid_validity: folder.uid_validity,
+                last_uid: folder.last_uid,
+                highest_modseq: folder.highest_modseq,
+                updated_at: chrono::Utc::now().to_rfc3339(),
+            })?;
         }
-        
-        Ok(all_emails)
+
+        // 只为新邮件及其相关的会话重新分组
+        if !saved.is_empty() {
+            let email_ids: Vec<String> = saved.iter().map(|email| email.id.clone()).collect();
+            threading::thread_new_messages(db, Some(account_id), &email_ids)?;
+        }
+
+        Ok(saved)
+    }
+
+    /// 保存已发送邮件的本地副本及其附件
+    pub fn save_sent_email(db: &Database, blobs: &BlobStore, email: &Email, attachments: Vec<AttachmentPart>) -> Result<()> {
+        let stored = attachments
+            .into_iter()
+            .map(|part| Ok((blobs.put(&part.data)?, part)))
+            .collect::<Result<Vec<_>>>()?;
+
+        db.insert_email(email)?;
+        for (sha256, part) in stored {
+            Self::insert_attachment(db, &email.id, sha256, part)?;
+        }
+        threading::thread_new_messages(db, email.account_id, std::slice::from_ref(&email.id))
+    }
+
+    fn insert_attachment(db: &Database, email_id: &str, sha256: String, part: AttachmentPart) -> Result<i64> {
+        db.insert_attachment(&Attachment {
+            id: 0,
+            email_id: email_id.to_string(),
+            filename: part.filename,
+            mime_type: part.mime_type,
+            size: part.data.len() as i64,
+            content_id: part.content_id,
+            is_inline: part.is_inline,
+            sha256,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// 把已发送的邮件保存到服务器的已发送文件夹
+    pub async fn save_to_sent(
+        &self,
+        sync_service: EmailSyncService,
+        folder: &str,
+        content: &[u8],
+    ) -> Result<()> {
+        let mut session = sync_service.connect_imap().await?;
+
+        let result = sync_service.append_sent(&mut session, folder, content);
+        session.logout().ok();
+
+        result
+    }
+
+    /// 上传草稿并替换服务器上的旧版本
+    pub async fn upload_draft(
+        &self,
+        sync_service: EmailSyncService,
+        folder: &str,
+        message_id: &str,
+        content: &[u8],
+    ) -> Result<()> {
+        let mut session = sync_service.connect_imap().await?;
+
+        let result = sync_service.replace_draft(&mut session, folder, message_id, content);
+        session.logout().ok();
+
+        result
+    }
+
+    /// 删除服务器上的草稿
+    pub async fn delete_server_draft(
+        &self,
+        sync_service: EmailSyncService,
+        folder: &str,
+        message_id: &str,
+    ) -> Result<()> {
+        let mut session = sync_service.connect_imap().await?;
+
+        let result = sync_service.delete_by_message_id(&mut session, folder, message_id);
+        session.logout().ok();
+
+        result
+    }
+
+    /// 只推送离线队列中的标记修改
+    pub async fn push_flag_changes(
+        &self,
+        sync_service: EmailSyncService,
+        operations: &[PendingOperation],
+    ) -> Result<Vec<PendingOperation>> {
+        let mut session = sync_service.connect_imap().await?;
+
+        let result = sync_service.push_flag_changes(&mut session, operations);
+        session.logout().ok();
+
+        result
     }
 }
\ No newline at end of file
@@ -0,0 +1,426 @@
+use anyhow::Result;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::database::Database;
+use crate::models::thread::{ThreadAssignment, ThreadingHeaders};
+
+/// 每次增量计算会话处理的新邮件数，限制查询参数的个数
+const THREAD_BATCH_SIZE: usize = 100;
+
+/// 重新计算账户的全部会话，`account_id` 为 None 时处理本地创建的邮件
+pub fn rethread(db: &Database, account_id: Option<i32>) -> Result<()> {
+    let messages = db.get_threading_headers(account_id)?;
+    db.replace_threads(account_id, &assign_threads(&messages))
+}
+
+/// 为新保存的邮件计算会话
+///
+/// 只读取与新邮件相关的已有会话（通过 Message-ID、References / In-Reply-To、Gmail 会话 ID 或主题关联），
+/// 与新邮件一起重新分组后替换这些会话，不重新计算账户的全部会话。
+pub fn thread_new_messages(db: &Database, account_id: Option<i32>, email_ids: &[String]) -> Result<()> {
+    for chunk in email_ids.chunks(THREAD_BATCH_SIZE) {
+        let new_messages = db.get_threading_headers_by_ids(account_id, chunk)?;
+        if new_messages.is_empty() {
+            continue;
+        }
+
+        let subjects: Vec<String> = new_messages
+            .iter()
+            .map(|m| normalize_subject(&m.subject))
+            .filter(|subject| !subject.is_empty())
+            .collect();
+        let related = db.get_related_threading_headers(account_id, &new_messages, &subjects)?;
+
+        let mut messages = new_messages;
+        messages.extend(related);
+        let mut replaced: Vec<String> = messages.iter().filter_map(|m| m.thread_id.clone()).collect();
+        replaced.sort_unstable();
+        replaced.dedup();
+
+        db.update_threads(account_id, &replaced, &assign_threads(&messages))?;
+    }
+    Ok(())
+}
+
+/// 按 [`group_messages`] 的分组结果生成会话
+fn assign_threads(messages: &[ThreadingHeaders]) -> Vec<ThreadAssignment> {
+    group_messages(messages)
+        .into_iter()
+        .map(|group| {
+            // 会话 ID 取最早一封邮件的 ID，新邮件加入时保持不变
+            let first = group
+                .iter()
+                .copied()
+                .min_by(|a, b| {
+                    (&messages[*a].date, &messages[*a].email_id)
+                        .cmp(&(&messages[*b].date, &messages[*b].email_id))
+                })
+                .unwrap_or(group[0]);
+
+            let mut subject_keys: Vec<String> = group
+                .iter()
+                .map(|&i| normalize_subject(&messages[i].subject))
+                .filter(|key| !key.is_empty())
+                .collect();
+            subject_keys.sort_unstable();
+            subject_keys.dedup();
+
+            ThreadAssignment {
+                id: messages[first].email_id.clone(),
+                subject: strip_subject_prefixes(&messages[first].subject).to_string(),
+                subject_keys,
+                email_ids: group.iter().map(|&i| messages[i].email_id.clone()).collect(),
+            }
+        })
+        .collect()
+}
+
+/// 为还没有会话的邮件重新计算所在账户的全部会话，用于升级后首次启动
+pub fn rethread_unthreaded(db: &Database) -> Result<()> {
+    for account_id in db.get_unthreaded_accounts()? {
+        rethread(db, account_id)?;
+    }
+    Ok(())
+}
+
+/// 去掉主题开头的 Re:、Fwd:、回复: 等前缀
+pub fn strip_subject_prefixes(subject: &str) -> &str {
+    static PREFIX: OnceLock<Regex> = OnceLock::new();
+    let prefix = PREFIX.get_or_init(|| {
+        Regex::new(r"(?i)^\s*(?:re|fwd?|aw|sv|回复|答复|转发)(?:\[\d+\]|\(\d+\))?\s*[:：]\s*").unwrap()
+    });
+
+    let mut rest = subject.trim();
+    while let Some(m) = prefix.find(rest) {
+        rest = &rest[m.end()..];
+    }
+    rest.trim()
+}
+
+/// 按主题合并会话时比较的键：去掉前缀后转为小写
+pub fn normalize_subject(subject: &str) -> String {
+    strip_subject_prefixes(subject).to_lowercase()
+}
+
+fn is_reply(subject: &str) -> bool {
+    strip_subject_prefixes(subject).len() != subject.trim().len()
+}
+
+/// 邮件容器：`message` 为空表示只在 References 中出现、本地没有的邮件
+#[derive(Default)]
+struct Container {
+    message: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// 按 JWZ 算法把邮件分组为会话，返回每个会话中邮件的下标
+///
+/// 1. 按 Message-ID 建立容器，依据 References / In-Reply-To 连接父子关系（跳过会形成环的连接）；
+/// 2. 没有父容器的容器作为会话的根；
+/// 3. 缺少引用头部的回复（主题带 Re: 等前缀）按主题并入同主题的会话，
+///    两封都不是回复时不合并，避免把主题相同的无关邮件归为一个会话；
+/// 4. Gmail 邮件的 X-GM-THRID 相同时合并为同一会话。
+pub fn group_messages(messages: &[ThreadingHeaders]) -> Vec<Vec<usize>> {
+    let mut containers: Vec<Container> = Vec::new();
+    let mut id_table: HashMap<String, usize> = HashMap::new();
+
+    let mut container_for = |containers: &mut Vec<Container>, id: &str| -> usize {
+        *id_table.entry(id.to_string()).or_insert_with(|| {
+            containers.push(Container::default());
+            containers.len() - 1
+        })
+    };
+
+    for (index, message) in messages.iter().enumerate() {
+        // Message-ID 缺失或重复时使用独立的容器
+        let this = match &message.message_id {
+            Some(id) => {
+                let existing = container_for(&mut containers, id);
+                if containers[existing].message.is_none() {
+                    existing
+                } else {
+                    containers.push(Container::default());
+                    containers.len() - 1
+                }
+            }
+            None => {
+                containers.push(Container::default());
+                containers.len() - 1
+            }
+        };
+        containers[this].message = Some(index);
+
+        let mut references: Vec<&str> = message.references.iter().map(String::as_str).collect();
+        if let Some(in_reply_to) = message.in_reply_to.as_deref() {
+            if references.last() != Some(&in_reply_to) {
+                references.push(in_reply_to);
+            }
+        }
+        references.retain(|id| Some(*id) != message.message_id.as_deref());
+
+        // References 中相邻的两项视为父子，已有父容器的不再修改
+        let mut previous: Option<usize> = None;
+        for id in references {
+            let current = container_for(&mut containers, id);
+            if let Some(parent) = previous {
+                if containers[current].parent.is_none() && !is_ancestor(&containers, current, parent) {
+                    link(&mut containers, parent, current);
+                }
+            }
+            previous = Some(current);
+        }
+
+        // 邮件自身的父容器以其 References 的最后一项为准
+        unlink(&mut containers, this);
+        if let Some(parent) = previous {
+            if !is_ancestor(&containers, this, parent) {
+                link(&mut containers, parent, this);
+            }
+        }
+    }
+
+    let roots: Vec<usize> = (0..containers.len())
+        .filter(|&c| containers[c].parent.is_none() && first_message(&containers, c).is_some())
+        .collect();
+
+    let mut groups = UnionFind::new(containers.len());
+
+    // 按主题合并：每个主题优先选择非回复的根
+    let subject_of = |root: usize| -> Option<(String, bool)> {
+        let message = &messages[first_message(&containers, root)?];
+        let base = normalize_subject(&message.subject);
+        if base.is_empty() {
+            return None;
+        }
+        let reply = containers[root].message.is_none() || is_reply(&message.subject);
+        Some((base, reply))
+    };
+
+    let mut subject_table: HashMap<String, (usize, bool)> = HashMap::new();
+    for &root in &roots {
+        if let Some((base, reply)) = subject_of(root) {
+            let entry = subject_table.entry(base).or_insert((root, reply));
+            if entry.1 && !reply {
+                *entry = (root, reply);
+            }
+        }
+    }
+    for &root in &roots {
+        if let Some((base, reply)) = subject_of(root) {
+            let (other, other_reply) = subject_table[&base];
+            if other != root && (reply || other_reply) {
+                groups.union(root, other);
+            }
+        }
+    }
+
+    // Gmail 会话 ID
+    let mut gmail_threads: HashMap<&str, usize> = HashMap::new();
+    for &root in &roots {
+        for message in descendants(&containers, root) {
+            if let Some(thread_id) = messages[message].gmail_thread_id.as_deref() {
+                let first = *gmail_threads.entry(thread_id).or_insert(root);
+                groups.union(root, first);
+            }
+        }
+    }
+
+    let mut result: HashMap<usize, Vec<usize>> = HashMap::new();
+    for &root in &roots {
+        result
+            .entry(groups.find(root))
+            .or_default()
+            .extend(descendants(&containers, root));
+    }
+
+    let mut result: Vec<Vec<usize>> = result.into_values().collect();
+    for group in &mut result {
+        group.sort_unstable();
+    }
+    result.sort_unstable();
+    result
+}
+
+fn link(containers: &mut [Container], parent: usize, child: usize) {
+    containers[child].parent = Some(parent);
+    containers[parent].children.push(child);
+}
+
+fn unlink(containers: &mut [Container], child: usize) {
+    if let Some(parent) = containers[child].parent.take() {
+        containers[parent].children.retain(|&c| c != child);
+    }
+}
+
+/// `ancestor` 是否为 `node` 本身或其祖先
+fn is_ancestor(containers: &[Container], ancestor: usize, node: usize) -> bool {
+    let mut current = Some(node);
+    while let Some(c) = current {
+        if c == ancestor {
+            return true;
+        }
+        current = containers[c].parent;
+    }
+    false
+}
+
+/// 容器及其后代中的全部邮件
+fn descendants(containers: &[Container], root: usize) -> Vec<usize> {
+    let mut result = Vec::new();
+    let mut stack = vec![root];
+    while let Some(c) = stack.pop() {
+        result.extend(containers[c].message);
+        stack.extend(containers[c].children.iter().rev());
+    }
+    result
+}
+
+/// 容器或其后代中按深度优先顺序的第一封邮件
+fn first_message(containers: &[Container], root: usize) -> Option<usize> {
+    descendants(containers, root).into_iter().next()
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        Self { parent: (0..size).collect() }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut current = x;
+        while self.parent[current] != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, references: &[&str], subject: &str) -> ThreadingHeaders {
+        ThreadingHeaders {
+            email_id: id.to_string(),
+            message_id: Some(format!("{}@example.com", id)),
+            in_reply_to: references.last().map(|r| format!("{}@example.com", r)),
+            references: references.iter().map(|r| format!("{}@example.com", r)).collect(),
+            subject: subject.to_string(),
+            date: String::new(),
+            gmail_thread_id: None,
+            thread_id: None,
+        }
+    }
+
+    #[test]
+    fn test_group_messages() {
+        let mut messages = vec![
+            // 回复先于原邮件到达，并引用了本地没有的中间邮件
+            message("c", &["a", "b"], "Re: 项目计划"),
+            message("a", &[], "项目计划"),
+            // 不带引用头部的回复按主题归入会话
+            message("d", &[], "回复：项目计划"),
+            // 主题相同但不是回复的邮件不合并
+            message("e", &[], "项目计划"),
+            message("f", &[], "午餐"),
+            message("g", &[], "Fwd: 其他"),
+        ];
+        messages[5].gmail_thread_id = Some("1234".to_string());
+        messages[4].gmail_thread_id = Some("1234".to_string());
+
+        let groups = group_messages(&messages);
+        assert_eq!(groups.len(), 3, "{:?}", groups);
+        assert!(groups.contains(&vec![0, 1, 2]));
+        assert!(groups.contains(&vec![3]));
+        assert!(groups.contains(&vec![4, 5]));
+
+        assert_eq!(strip_subject_prefixes("Re: RE[2]: Fwd: 项目计划"), "项目计划");
+        assert_eq!(strip_subject_prefixes("答复： 项目计划"), "项目计划");
+    }
+
+    #[test]
+    fn test_thread_new_messages_joins_related_threads() {
+        use crate::models::email::Email;
+
+        let db = Database::new(":memory:").unwrap();
+        let save = |id: &str, in_reply_to: Option<&str>, subject: &str| {
+            let mut email = Email::new("a@example.com".into(), "b@example.com".into(), subject.into(), String::new(), "收件箱".into());
+            email.id = id.to_string();
+            email.message_id = Some(format!("{}@example.com", id));
+            email.in_reply_to = in_reply_to.map(|r| format!("{}@example.com", r));
+            email.references = email.in_reply_to.iter().cloned().collect();
+            db.insert_email(&email).unwrap();
+            thread_new_messages(&db, None, &[email.id]).unwrap();
+        };
+        let thread_of = |id: &str| db.get_email_by_id(id).unwrap().unwrap().thread_id;
+
+        // 回复先到达，原邮件到达后合并为同一会话
+        save("b", Some("a"), "Re: 项目计划");
+        save("x", None, "午餐");
+        save("a", None, "项目计划");
+        save("c", Some("b"), "Re: Re: 项目计划");
+        // 不带引用头部的回复按主题归入会话
+        save("d", None, "回复：项目计划");
+
+        assert_eq!(thread_of("b"), thread_of("a"));
+        assert_eq!(thread_of("c"), thread_of("a"));
+        assert_eq!(thread_of("d"), thread_of("a"));
+        assert_ne!(thread_of("x"), thread_of("a"));
+        assert_eq!(db.get_threads(None).unwrap().len(), 2);
+
+        // 与重新计算全部会话的结果一致
+        let before: Vec<_> = ["a", "b", "c", "d", "x"].iter().map(|id| thread_of(id)).collect();
+        rethread(&db, None).unwrap();
+        let after: Vec<_> = ["a", "b", "c", "d", "x"].iter().map(|id| thread_of(id)).collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn test_thread_new_messages_child_before_parent() {
+        use crate::models::email::Email;
+
+        let db = Database::new(":memory:").unwrap();
+        let save = |id: &str, references: &[&str], subject: &str| {
+            let mut email = Email::new("a@example.com".into(), "b@example.com".into(), subject.into(), String::new(), "收件箱".into());
+            email.id = id.to_string();
+            email.message_id = Some(format!("{}@example.com", id));
+            email.references = references.iter().map(|r| format!("{}@example.com", r)).collect();
+            db.insert_email(&email).unwrap();
+            thread_new_messages(&db, None, &[email.id]).unwrap();
+        };
+        let thread_of = |id: &str| db.get_email_by_id(id).unwrap().unwrap().thread_id;
+
+        // 只带 References 的回复先到达，且主题不像回复
+        save("child", &["parent"], "Budget");
+        save("parent", &[], "ÉTÉ");
+        assert_eq!(thread_of("child"), thread_of("parent"));
+
+        // 非 ASCII 主题按相同的规则比较大小写
+        save("reply", &[], "Re: été");
+        assert_eq!(thread_of("reply"), thread_of("parent"));
+        assert_eq!(db.get_threads(None).unwrap().len(), 1);
+
+        let before: Vec<_> = ["child", "parent", "reply"].iter().map(|id| thread_of(id)).collect();
+        rethread(&db, None).unwrap();
+        let after: Vec<_> = ["child", "parent", "reply"].iter().map(|id| thread_of(id)).collect();
+        assert_eq!(before, after);
+    }
+}